@@ -1,6 +1,31 @@
 from .agnr_ml import AGNR as NativeAGNR
+from .agnr_ml import ActiveLearner as NativeActiveLearner
+from .agnr_ml import AtomicStructure, BandEdges, DatasetReader, DatasetWriter, DftInputs, DftOutput, Multilayer, Substrate
+from .agnr_ml import GenerationInterrupted
+from .agnr_ml import KernelModel as NativeKernelModel
+from .agnr_ml import PassivationReport
 from pymatgen import Lattice, Structure
-from typing import List, Tuple, Union, Generator
+import numpy as np
+import os
+import warnings
+from typing import Any, Callable, Dict, Iterable, List, Optional, Tuple, Union, Generator
+
+
+def _to_pymatgen(structure: AtomicStructure) -> Structure:
+    return Structure(
+        lattice=Lattice(structure.lattice()),
+        species=structure.types(),
+        coords=structure.coords(),
+        coords_are_cartesian=True,
+    )
+
+
+def _from_pymatgen(structure: Structure) -> AtomicStructure:
+    return AtomicStructure(
+        [list(v) for v in structure.lattice.matrix],
+        [str(s) for s in structure.species],
+        [list(c) for c in structure.cart_coords],
+    )
 
 
 class AGNR:
@@ -9,6 +34,11 @@ class AGNR:
             self._spec = spec
         else:
             self._spec = NativeAGNR(spec)
+            if not self._spec.is_primitive():
+                warnings.warn(
+                    f"AGNR spec {spec} repeats a shorter section, use primitive() to get the minimal unit",
+                    stacklevel=2,
+                )
 
     def __len__(self):
         return len(self.spec())
@@ -16,12 +46,61 @@ class AGNR:
     def spec(self) -> List[Tuple[int, int]]:
         return self._spec.spec
 
+    def name(self) -> Optional[str]:
+        """Short name of the AGNR (as used by the `agnr` command-line tool), or None if it's too
+        wide to have one."""
+        return self._spec.name()
+
+    @staticmethod
+    def from_structure(structure: Structure) -> Optional["AGNR"]:
+        """Recognize the (canonical) AGNR a possibly relaxed ribbon structure was built from, or
+        None if its carbon atoms don't form one. The ribbon must be periodic along x and lie in
+        the xy plane, as in `to_structure`."""
+        agnr = NativeAGNR.from_structure(_from_pymatgen(structure))
+        return None if agnr is None else AGNR(agnr)
+
+    def is_primitive(self) -> bool:
+        return self._spec.is_primitive()
+
+    def primitive(self) -> "AGNR":
+        """Get the minimal repeating unit of this AGNR (in canonical form)."""
+        return AGNR(self._spec.primitive())
+
+    def neighbors(self, max_edits: int = 1) -> List["AGNR"]:
+        """Get all distinct (canonical) AGNRs reachable from this one with at most `max_edits`
+        single edge step changes, column insertions, or column removals."""
+        return [AGNR(agnr) for agnr in self._spec.neighbors(max_edits)]
+
+    def to_svg(self, repeats: int = 3, scale: float = 30.0) -> str:
+        return self._spec.to_svg(repeats=repeats, scale=scale)
+
+    def _repr_svg_(self) -> str:
+        return self.to_svg()
+
+    def descriptors(self) -> Dict[str, float]:
+        """Descriptors computed directly from the spec, such as width statistics and kinks."""
+        return dict(self._spec.descriptors())
+
+    def distinct_defects(
+        self,
+        kind: str,
+        repeats: int = 1,
+        element: str = "H",
+        height: float = 1.1,
+    ) -> List[Tuple[Structure, List[int], int]]:
+        """All symmetry-distinct single defects of the given kind ("vacancy", "stone_wales", or
+        "adatom") in this AGNR repeated `repeats` times, as (structure, sites, multiplicity)."""
+        defects = self._spec.distinct_defects(kind, repeats, element, height)
+        return [(_to_pymatgen(s), sites, count) for s, sites, count in defects]
+
     def to_structure(
             self,
             cc_bond: float = 1.42045,
             ch_bond: float = 1.09047,
             vacuum_sep: float = 15.0,
     ) -> Structure:
+        """Structure of the AGNR with hydrogen-passivated edges. Raises ValueError for non-positive
+        bond lengths or vacuum, and warns (RuntimeWarning) if any carbons couldn't be capped."""
         # get the native structure representation and
         # convert to pymatgen's structure type
         structure = self._spec.to_structure(
@@ -29,12 +108,7 @@ class AGNR:
             ch_bond=ch_bond,
             vacuum_sep=vacuum_sep,
         )
-        return Structure(
-            lattice=Lattice(structure.lattice()),
-            species=structure.types(),
-            coords=structure.coords(),
-            coords_are_cartesian=True,
-        )
+        return _to_pymatgen(structure)
 
     def to_poscar_string(
             self,
@@ -48,6 +122,17 @@ class AGNR:
             vacuum_sep=vacuum_sep,
         ).to_poscar_string()
 
+    def passivation_report(
+            self,
+            cc_bond: float = 1.42045,
+            ch_bond: float = 1.09047,
+            vacuum_sep: float = 15.0,
+    ) -> PassivationReport:
+        """Which carbons of `to_structure` were capped with hydrogen (and the indices of their
+        caps), and which are still short of bonds."""
+        _, report = self._spec.to_structure_with_report(cc_bond, ch_bond, vacuum_sep)
+        return report
+
 
 def generate_all_possible_agnrs(
     min_len: int,
@@ -55,13 +140,603 @@ def generate_all_possible_agnrs(
     min_width: int,
     max_width: int,
     symmetric_only: bool = False,
+    progress: Optional[Callable[[int, int], None]] = None,
 ) -> Generator[AGNR, None, None]:
-    all_agnrs = NativeAGNR.generate_all_agnrs(
-        min_len=min_len,
-        max_len=max_len,
-        min_width=min_width,
-        max_width=max_width,
-        symmetric_only=symmetric_only,
-    )
+    """Generate every distinct AGNR within the given bounds. `progress`, if given, is called
+    periodically with the number of search nodes visited and AGNRs found so far. Generation can be
+    interrupted with Ctrl-C (or by raising `KeyboardInterrupt` from `progress`), which raises
+    `GenerationInterrupted` with the AGNRs found so far in its `partial` attribute."""
+    try:
+        all_agnrs = NativeAGNR.generate_all_agnrs(
+            min_len=min_len,
+            max_len=max_len,
+            min_width=min_width,
+            max_width=max_width,
+            symmetric_only=symmetric_only,
+            progress=progress,
+        )
+    except GenerationInterrupted as e:
+        e.partial = [AGNR(agnr) for agnr in e.args[1]]
+        raise
     for agnr in all_agnrs:
         yield AGNR(agnr)
+
+
+def structure_to_svg(
+    structure: Structure,
+    values: Optional[List[float]] = None,
+    repeats: int = 1,
+    scale: float = 30.0,
+    bond_cutoff: float = 1.6,
+) -> str:
+    """Render a (planar) structure as an SVG image viewed down the z axis, optionally coloring
+    each atom by a value such as charge or magnetic moment."""
+    return _from_pymatgen(structure).to_svg(
+        values=None if values is None else [float(v) for v in values],
+        repeats=repeats,
+        scale=scale,
+        bond_cutoff=bond_cutoff,
+    )
+
+
+def genetic_search(
+    fitness: Callable[[AGNR], float],
+    min_len: int,
+    max_len: int,
+    min_width: int,
+    max_width: int,
+    population_size: int = 50,
+    generations: int = 20,
+    mutation_rate: float = 0.5,
+    crossover_rate: float = 0.5,
+    initial: Optional[Iterable[AGNR]] = None,
+    seed: Optional[int] = None,
+) -> List[Tuple[AGNR, float]]:
+    """Search for AGNRs that maximize `fitness` using a genetic algorithm. Returns every unique
+    AGNR that was evaluated along with its fitness, best first."""
+    results = NativeAGNR.genetic_search(
+        lambda agnr: fitness(AGNR(agnr)),
+        min_len,
+        max_len,
+        min_width,
+        max_width,
+        population_size=population_size,
+        generations=generations,
+        mutation_rate=mutation_rate,
+        crossover_rate=crossover_rate,
+        initial=None if initial is None else [agnr._spec for agnr in initial],
+        seed=seed,
+    )
+    return [(AGNR(agnr), value) for agnr, value in results]
+
+
+def write_dataset(
+    path: str,
+    agnrs: Iterable[AGNR],
+    descriptors: Optional[Iterable[Dict[str, float]]] = None,
+    cc_bond: float = 1.42045,
+    ch_bond: float = 1.09047,
+    vacuum_sep: float = 15.0,
+    outputs: Optional[Iterable[Optional[DftOutput]]] = None,
+) -> int:
+    """Write AGNRs (and optionally per-AGNR descriptors) to a JSON Lines dataset file. If
+    `outputs` are given (see `read_dft_outputs`), each record uses the final structure of its DFT
+    run and stores the run's labels (energy, band gap, ...) instead of a freshly built structure.
+    Returns the number of records written."""
+    writer = DatasetWriter(path, cc_bond=cc_bond, ch_bond=ch_bond, vacuum_sep=vacuum_sep)
+    # optional per-AGNR inputs, which have to line up with the AGNRs
+    extras = {"descriptors": descriptors, "outputs": outputs}
+    extras = {name: iter(values) for name, values in extras.items() if values is not None}
+    missing = object()
+
+    def next_extra(name: str, count: int) -> Any:
+        if name not in extras:
+            return None
+        value = next(extras[name], missing)
+        if value is missing:
+            raise ValueError(f"fewer {name} than AGNRs (ran out after {count})")
+        return value
+
+    count = 0
+    try:
+        for agnr in agnrs:
+            desc = next_extra("descriptors", count)
+            output = next_extra("outputs", count)
+            if output is None:
+                writer.write(agnr._spec, desc)
+            else:
+                writer.write_output(agnr._spec, output, desc)
+            count += 1
+        for name, values in extras.items():
+            if next(values, missing) is not missing:
+                raise ValueError(f"more {name} than AGNRs ({count})")
+    finally:
+        writer.close()
+    return count
+
+
+def read_dataset(path: str, labels: bool = False) -> Generator[tuple, None, None]:
+    """Read back a dataset written by `write_dataset` as (agnr, structure, descriptors) tuples,
+    followed by the labels of each record if `labels` is true."""
+    for agnr, structure, *rest in DatasetReader(path, labels):
+        yield (AGNR(agnr), _to_pymatgen(structure), *rest)
+
+
+def _dft_output_files(directory: str) -> Generator[str, None, None]:
+    for root, dirs, files in os.walk(directory):
+        dirs.sort()
+        for file in sorted(files):
+            if file in ("vasprun.xml", "data-file-schema.xml"):
+                yield os.path.join(root, file)
+            elif file.endswith(".xml"):
+                # pw.x writes the same file as <prefix>.xml and <prefix>.save/data-file-schema.xml
+                save_dir = os.path.join(root, file[: -len(".xml")] + ".save")
+                if not os.path.exists(os.path.join(save_dir, "data-file-schema.xml")):
+                    yield os.path.join(root, file)
+
+
+def read_dft_outputs(directory: str) -> List[Tuple[str, Optional[AGNR], DftOutput]]:
+    """Find and parse every DFT output (`vasprun.xml`, or pw.x's XML output) below `directory`,
+    e.g. one written by `write_dft_inputs` after running the calculations. Returns
+    (path, agnr, output) for each, where the AGNR is recognized from the final structure or,
+    failing that, from the name of the run's top-level subdirectory. The AGNR is None if neither
+    works. Other XML files that aren't DFT outputs are skipped."""
+    results = []
+    for path in _dft_output_files(directory):
+        try:
+            output = DftOutput.read(path)
+        except ValueError:
+            if os.path.basename(path) in ("vasprun.xml", "data-file-schema.xml"):
+                raise
+            continue
+
+        relative = os.path.relpath(path, directory).split(os.sep)
+        name = relative[0] if len(relative) > 1 else os.path.basename(os.path.abspath(directory))
+        agnr = output.agnr(name)
+        results.append((path, None if agnr is None else AGNR(agnr), output))
+    return results
+
+
+def augment_structures(
+    structures: Iterable[Structure],
+    count: int,
+    rattle_stddev: float = 0.01,
+    max_strain: float = 0.02,
+    biaxial: bool = False,
+    max_angle: float = 0.0,
+    seed: int = 0,
+) -> List[List[Tuple[Structure, List[Dict[str, Any]]]]]:
+    """Generate `count` randomly strained, rotated, and rattled copies of each structure. Each
+    copy is returned along with the list of transformations that were applied to it."""
+    batch = AtomicStructure.augment_batch(
+        [_from_pymatgen(s) for s in structures],
+        count,
+        rattle_stddev,
+        max_strain,
+        biaxial,
+        max_angle,
+        seed,
+    )
+    return [[(_to_pymatgen(s), meta) for s, meta in augmented] for augmented in batch]
+
+
+def add_defect(
+    structure: Structure,
+    kind: str,
+    sites: List[int],
+    element: str = "H",
+    height: float = 1.1,
+) -> Structure:
+    """Apply a point defect to a (planar) structure: "vacancy" removes the atoms at `sites` and
+    re-passivates any carbon left with a dangling bond, "stone_wales" rotates the C-C bond
+    between the two `sites` by 90 degrees, and "adatom" adds an `element` atom `height` above the
+    center of `sites`."""
+    return _to_pymatgen(_from_pymatgen(structure).with_defect(kind, sites, element, height))
+
+
+def structure_hash(structure: Structure, cutoff: float = 1.6) -> str:
+    """Weisfeiler-Lehman hash of the periodic bond graph (bonds up to `cutoff`), with atoms
+    labelled by element. Structures that are the same material hash the same regardless of atom
+    order, periodic images, or small distortions. Supercells hash differently from their unit
+    cell. Confirm matching hashes with `is_same_structure`."""
+    return _from_pymatgen(structure).graph_hash(cutoff)
+
+
+def is_same_structure(a: Structure, b: Structure, cutoff: float = 1.6) -> bool:
+    """Whether two structures have isomorphic periodic bond graphs, including which periodic
+    image each bond connects to. Lattice vectors may be permuted or reversed, but must otherwise
+    describe the same cell."""
+    return _from_pymatgen(a).is_isomorphic(_from_pymatgen(b), cutoff)
+
+
+def unique_structures(structures: Iterable[Structure], cutoff: float = 1.6) -> List[int]:
+    """Indices of the first of each group of identical structures (see `is_same_structure`), e.g.
+    to deduplicate structures collected from several datasets."""
+    return AtomicStructure.unique_structures([_from_pymatgen(s) for s in structures], cutoff)
+
+
+ModelInput = Union[AGNR, Structure, List[float]]
+
+
+def _to_model_input(x: ModelInput) -> Union[NativeAGNR, AtomicStructure, List[float]]:
+    if isinstance(x, AGNR):
+        return x._spec
+    if isinstance(x, Structure):
+        return _from_pymatgen(x)
+    return [float(v) for v in x]
+
+
+class _KernelModel:
+    """Shared implementation of `KernelRidge` and `GaussianProcess`, which are the same model
+    (kernel ridge regression is the posterior mean of a Gaussian process) with different
+    defaults."""
+
+    def __init__(self, native: NativeKernelModel):
+        self._model = native
+
+    def fit(self, inputs: Iterable[ModelInput], targets: Iterable[float]):
+        self._model.fit([_to_model_input(x) for x in inputs], [float(t) for t in targets])
+        return self
+
+    def predict(self, inputs: Iterable[ModelInput]) -> Tuple[List[float], List[float]]:
+        """Predicted means and standard deviations for each input."""
+        return self._model.predict([_to_model_input(x) for x in inputs])
+
+    def screen(
+        self,
+        min_len: int,
+        max_len: int,
+        min_width: int,
+        max_width: int,
+        symmetric_only: bool = False,
+        top: Optional[int] = None,
+    ) -> List[Tuple[AGNR, float, float]]:
+        """Generate, featurize, and predict for every AGNR within the given bounds in one pass,
+        returning (AGNR, mean, std) for the (at most `top`) highest means first."""
+        results = self._model.screen(min_len, max_len, min_width, max_width, symmetric_only, top)
+        return [(AGNR(agnr), mean, std) for agnr, mean, std in results]
+
+    def save(self, path: str):
+        self._model.save(path)
+
+    @classmethod
+    def load(cls, path: str):
+        model = cls.__new__(cls)
+        _KernelModel.__init__(model, NativeKernelModel.load(path))
+        return model
+
+
+class KernelRidge(_KernelModel):
+    """Kernel ridge regression over AGNRs, structures, or descriptor vectors, using either an
+    "rbf" kernel on descriptors or a "graph" (Weisfeiler-Lehman) kernel on the bond graph."""
+
+    def __init__(
+        self,
+        kernel: str = "rbf",
+        alpha: float = 1e-3,
+        length_scale: Optional[float] = None,
+        iterations: Optional[int] = None,
+    ):
+        super().__init__(NativeKernelModel(kernel, alpha, length_scale, iterations))
+
+    def predict(self, inputs: Iterable[ModelInput]) -> List[float]:
+        return super().predict(inputs)[0]
+
+    def screen(
+        self,
+        min_len: int,
+        max_len: int,
+        min_width: int,
+        max_width: int,
+        symmetric_only: bool = False,
+        top: Optional[int] = None,
+    ) -> List[Tuple[AGNR, float]]:
+        """Same as `GaussianProcess.screen`, without the standard deviations."""
+        results = super().screen(min_len, max_len, min_width, max_width, symmetric_only, top)
+        return [(agnr, mean) for agnr, mean, _ in results]
+
+
+class GaussianProcess(_KernelModel):
+    """Gaussian process regression over AGNRs, structures, or descriptor vectors, see
+    `KernelRidge` for the available kernels. Predictions include a standard deviation."""
+
+    def __init__(
+        self,
+        kernel: str = "rbf",
+        noise: float = 1e-6,
+        length_scale: Optional[float] = None,
+        iterations: Optional[int] = None,
+    ):
+        super().__init__(NativeKernelModel(kernel, noise, length_scale, iterations))
+
+
+class ActiveLearner:
+    """Bayesian-optimization active learning over a pool of candidate AGNRs (e.g. from
+    `generate_all_possible_agnrs`), maximizing labels supplied by a callback such as a DFT
+    calculation. Symmetry-equivalent candidates are only ever labelled once."""
+
+    def __init__(
+        self,
+        pool: Iterable[AGNR],
+        model: Optional[GaussianProcess] = None,
+        acquisition: str = "ei",
+        xi: float = 0.01,
+        kappa: float = 2.0,
+        seed: int = 0,
+        _native: Optional[NativeActiveLearner] = None,
+    ):
+        self._learner = _native or NativeActiveLearner(
+            [agnr._spec for agnr in pool],
+            None if model is None else model._model,
+            acquisition,
+            xi,
+            kappa,
+            seed,
+        )
+
+    @property
+    def iteration(self) -> int:
+        return self._learner.iteration
+
+    @property
+    def labelled(self) -> List[Tuple[AGNR, float]]:
+        return [(AGNR(agnr), value) for agnr, value in self._learner.labelled]
+
+    @property
+    def best(self) -> Optional[Tuple[AGNR, float]]:
+        best = self._learner.best
+        return None if best is None else (AGNR(best[0]), best[1])
+
+    def add_label(self, agnr: AGNR, value: float):
+        self._learner.add_label(agnr._spec, float(value))
+
+    def propose(self, batch_size: int = 1) -> List[AGNR]:
+        """The next `batch_size` AGNRs to label. Each pick is treated as labelled with its
+        predicted mean while choosing the rest, so a batch doesn't cluster around one optimum."""
+        return [AGNR(agnr) for agnr in self._learner.propose(batch_size)]
+
+    def run(
+        self,
+        label: Callable[[AGNR], float],
+        iterations: int,
+        batch_size: int = 1,
+        checkpoint: Optional[str] = None,
+    ) -> List[Tuple[AGNR, float]]:
+        """Propose and label `iterations` batches, saving the state to `checkpoint` (if given)
+        after every label. Returns the newly labelled AGNRs."""
+        results = self._learner.run(
+            lambda agnr: float(label(AGNR(agnr))), iterations, batch_size, checkpoint
+        )
+        return [(AGNR(agnr), value) for agnr, value in results]
+
+    def save(self, path: str):
+        self._learner.save(path)
+
+    @staticmethod
+    def load(path: str) -> "ActiveLearner":
+        return ActiveLearner([], _native=NativeActiveLearner.load(path))
+
+
+def build_multilayer(
+    layers: Iterable[Union[AGNR, Structure]],
+    stacking: str = "ab",
+    layer_sep: float = 3.35,
+    shift: Optional[Tuple[float, float]] = None,
+    vacuum_sep: float = 15.0,
+    cc_bond: float = 1.42045,
+    ch_bond: float = 1.09047,
+) -> Tuple[Structure, List[int], str]:
+    """Stack AGNRs (or structures) along z, bottom layer first. Stacking is "ab" (every other
+    layer shifted by `shift`, one C-C bond of the bottom layer along x by default), "aa", or
+    "offset" (layer `i` shifted by `i * shift`). Layers with different periodic lengths are
+    repeated to a common length. Returns the structure, the layer index of each atom, and an rsp2
+    `layers.yaml`."""
+    structures = [
+        _from_pymatgen(layer)
+        if isinstance(layer, Structure)
+        else layer._spec.to_structure(cc_bond=cc_bond, ch_bond=ch_bond, vacuum_sep=vacuum_sep)
+        for layer in layers
+    ]
+    multilayer = Multilayer(structures, stacking, layer_sep, shift, vacuum_sep)
+    return _to_pymatgen(multilayer.structure), multilayer.layer_indices, multilayer.layers_yaml()
+
+
+def write_dft_inputs(
+    ribbons: Iterable[Union[AGNR, Structure]],
+    directory: str,
+    code: str = "vasp",
+    k_spacing: float = 0.15,
+    band_points: int = 40,
+    settings: Optional[Dict[str, Union[bool, int, float, str]]] = None,
+    pseudopotentials: Optional[Dict[str, str]] = None,
+    templates: Optional[Dict[str, str]] = None,
+    cc_bond: float = 1.42045,
+    ch_bond: float = 1.09047,
+    vacuum_sep: float = 15.0,
+) -> List[str]:
+    """Write a complete set of DFT inputs for each ribbon into its own subdirectory of `directory`,
+    named after the AGNR (or numbered, for structures). `code` is "vasp" (POSCAR, INCAR, KPOINTS,
+    and a non-self-consistent band structure run in bands/, which needs the scf CHGCAR copied into
+    it) or "qe" (pw.x scf.in and bands.in). The k-point mesh has a spacing of at most `k_spacing`
+    (1/angstrom) along the ribbon and a single point along the vacuum axes, and band structures go
+    from Gamma to X with `band_points` points. `settings` adds to or replaces the default INCAR
+    tags, or pw.x namelist values given as "namelist.key". `templates` gives the contents of files
+    by name, with `{{key}}` placeholders (see `DftInputs`). Returns the directories that were
+    written."""
+    inputs = DftInputs(code, k_spacing, band_points, settings, pseudopotentials, templates)
+
+    written = []
+    for i, ribbon in enumerate(ribbons):
+        if isinstance(ribbon, Structure):
+            structure, name = _from_pymatgen(ribbon), f"structure_{i}"
+        else:
+            structure = ribbon._spec.to_structure(cc_bond=cc_bond, ch_bond=ch_bond, vacuum_sep=vacuum_sep)
+            name = ribbon.name() or f"agnr_{i}"
+
+        path = os.path.join(directory, name)
+        inputs.write(structure, path, name)
+        written.append(path)
+    return written
+
+
+def place_on_substrate(
+    ribbon: Union[AGNR, Structure],
+    substrate: Union[str, Substrate] = "Au",
+    height: float = 3.2,
+    max_strain: float = 0.03,
+    max_repeats: int = 10,
+    layers: Optional[int] = None,
+    lateral_sep: float = 10.0,
+    vacuum_sep: float = 15.0,
+    cc_bond: float = 1.42045,
+    ch_bond: float = 1.09047,
+) -> Tuple[Structure, List[bool], float]:
+    """Place a ribbon `height` above a commensurate supercell of a substrate slab. The substrate
+    is an fcc(111) metal given by its element ("Au", "Ag", "Cu", ...), "hBN", a path to a POSCAR
+    of the slab, or a `Substrate`. The ribbon is strained along its periodic axis by at most
+    `max_strain` to match as few as possible (up to `max_repeats`) of its cells to a substrate
+    lattice vector. Returns the structure (substrate atoms first), whether each atom belongs to
+    the substrate, and the residual strain applied to the ribbon."""
+    if isinstance(substrate, str):
+        if substrate.lower() == "hbn":
+            substrate = Substrate.hbn(layers=layers)
+        elif os.path.isfile(substrate):
+            substrate = Substrate.from_poscar(substrate)
+        else:
+            substrate = Substrate.fcc111(substrate, layers=layers)
+
+    if isinstance(ribbon, Structure):
+        structure = _from_pymatgen(ribbon)
+    else:
+        structure = ribbon._spec.to_structure(cc_bond=cc_bond, ch_bond=ch_bond)
+
+    match = substrate.place(structure, height, max_strain, max_repeats, lateral_sep, vacuum_sep)
+    return _to_pymatgen(match.structure), match.is_substrate, match.strain
+
+
+def optical_absorption(
+    ribbon: Union[AGNR, Structure],
+    polarization: str = "along",
+    num_kpoints: int = 200,
+    broadening: float = 0.05,
+    max_energy: float = 6.0,
+    num_energies: int = 601,
+    hopping: float = 2.7,
+    num_transitions: int = 4,
+) -> Tuple[List[float], List[float], List[Tuple[float, float]]]:
+    """Independent-particle optical absorption of a periodic ribbon from the nearest-neighbor pz
+    tight-binding model of its carbon atoms, with light polarized "along" or "across" the
+    periodic axis. The spectrum is Lorentzian broadened (`broadening` is the half width in eV) and
+    evaluated from 0 to `max_energy` eV. Returns the energies, the absorption (arbitrary units),
+    and the lowest bright transitions as (energy, strength relative to the strongest)."""
+    if isinstance(ribbon, Structure):
+        structure = _from_pymatgen(ribbon)
+    else:
+        structure = ribbon._spec.to_structure()
+
+    spectrum = structure.optical_absorption(
+        polarization, num_kpoints, broadening, max_energy, num_energies, hopping, num_transitions
+    )
+    return spectrum.energies, spectrum.absorption, spectrum.bright_transitions
+
+
+def stm_image(
+    ribbon: Union[AGNR, Structure],
+    bias: float,
+    height: float = 3.0,
+    resolution: float = 0.1,
+    repeats: int = 3,
+    margin: float = 3.0,
+    num_kpoints: int = 50,
+    hopping: float = 2.7,
+    orbital_decay: float = 3.0708,
+) -> Tuple[np.ndarray, Tuple[float, float, float, float]]:
+    """Simulated constant-height STM image (Tersoff-Hamann LDOS) of a periodic ribbon at a sample
+    bias in volts, from the pz tight-binding states between the middle of the gap and `bias`,
+    with Slater-type pz orbitals (decay in 1/angstrom) evaluated `height` angstroms above the
+    ribbon. The image covers `repeats` cells along the periodic axis and extends `margin`
+    angstroms beyond the outermost carbons. Returns the image, with rows across the ribbon and
+    columns along its axis, and its extent (left, right, bottom, top) in angstroms, as used by
+    `matplotlib.pyplot.imshow(image, extent=extent, origin="lower")`."""
+    if isinstance(ribbon, Structure):
+        structure = _from_pymatgen(ribbon)
+    else:
+        structure = ribbon._spec.to_structure()
+
+    image = structure.stm_image(
+        bias, height, resolution, repeats, margin, num_kpoints, hopping, orbital_decay
+    )
+    return np.array(image.values), image.extent
+
+
+def precursor_monomer(
+    agnr: AGNR, halogen: str = "Br", units: Optional[int] = None
+) -> Optional[Tuple[Structure, List[int]]]:
+    """Dihalogenated monomer that gives the AGNR through Ullmann coupling and
+    cyclodehydrogenation, e.g. 10,10'-dibromo-9,9'-bianthryl for 7-AGNR. The ribbon is split into
+    aromatic units at columns one pair narrower than their neighbors, and the monomer is `units`
+    of them (by default one period, and at least two) joined by single bonds and capped with
+    hydrogen, except for the two halogen sites where it couples to its neighbors in the polymer.
+    Returns the molecule and the indices of the halogen atoms, or `None` if the AGNR can't be
+    split this way."""
+    monomer = agnr._spec.monomer(halogen, units)
+    if monomer is None:
+        return None
+    return _to_pymatgen(monomer.structure), monomer.halogen_sites
+
+
+def band_edges(
+    ribbon: Union[AGNR, Structure],
+    num_kpoints: int = 100,
+    hopping: float = 2.7,
+    edge_threshold: float = 0.5,
+) -> BandEdges:
+    """Band edges of a periodic ribbon from the nearest-neighbor pz tight-binding model of its
+    carbon atoms: the gap, the VBM/CBM (with fractional k-points), and the hole and electron
+    effective masses (in electron masses) from the band curvature at the extrema. States of the
+    highest valence and lowest conduction band with at least `edge_threshold` of their weight on
+    the edge carbons (those bonded to only two other carbons) are listed in `edge_states`, and
+    flagged by `has_edge_states`."""
+    if isinstance(ribbon, Structure):
+        structure = _from_pymatgen(ribbon)
+    else:
+        structure = ribbon._spec.to_structure()
+    return structure.band_edges(num_kpoints, hopping, edge_threshold)
+
+
+def passivate_structure(
+    structure: Structure,
+    rules: Optional[Dict[str, Tuple[int, str]]] = None,
+    bond_lengths: Optional[Dict[str, float]] = None,
+    cutoff_distance: float = 1.6,
+) -> Tuple[Structure, PassivationReport]:
+    """Cap undercoordinated atoms with hydrogen. By default only carbon is capped (sp2, 3 bonds);
+    `rules` maps elements to (valence, "sp2" or "sp3") and `bond_lengths` maps elements to their
+    bond length to hydrogen, overriding the defaults. Returns the capped structure, with the
+    hydrogen appended, and the report of which sites were capped or left short of bonds."""
+    capped, report = _from_pymatgen(structure).passivate(rules, bond_lengths, cutoff_distance)
+    return _to_pymatgen(capped), report
+
+
+def relax_structure(
+    structure: Structure,
+    potential: str = "rebo",
+    fmax: float = 0.01,
+    relax_cell_x: bool = True,
+    max_iterations: int = 1000,
+    minimizer: str = "acgsd",
+) -> Tuple[Structure, Dict[str, Any]]:
+    """Relax a structure with a classical potential, returning the relaxed structure along with
+    its energy and convergence info. Requires agnr_ml to be built with the `relax` feature."""
+    native = _from_pymatgen(structure)
+    if not hasattr(native, "relax"):
+        raise RuntimeError("agnr_ml was built without relaxation support (the `relax` feature)")
+
+    result = native.relax(potential, fmax, relax_cell_x, max_iterations, minimizer)
+    info = {
+        "energy": result.energy,
+        "max_force": result.max_force,
+        "iterations": result.iterations,
+        "converged": result.converged,
+    }
+    return _to_pymatgen(result.structure), info
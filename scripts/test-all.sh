@@ -20,6 +20,7 @@ elif ! sha512sum --quiet -c scratch/dist.chk || [ ! -e scratch/dist ]; then
     --env CARGO_TARGET_DIR=scratch/maturin-target \
     maturin build \
     --bindings pyo3 \
+    --cargo-extra-args="--features extension-module" \
     --manylinux 2010 \
     --interpreter python3.7 \
     --interpreter python3.8 \
@@ -0,0 +1,335 @@
+use super::{CompressedGraph, Edge};
+
+/// Adjacency-list graph for incremental edits (e.g. while generating defects or passivating a
+/// structure), which can be frozen into a `CompressedGraph` once it's done.
+///
+/// Vertex IDs are stable: removing a vertex leaves a hole rather than renumbering the others,
+/// and `freeze` reports where each ID ended up in the compacted graph. Parallel edges are
+/// allowed, as in `CompressedGraph`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphBuilder<M = ()> {
+    /// outgoing edges (to, meta) of each vertex, `None` for removed vertices
+    outgoing: Vec<Option<Vec<(usize, M)>>>,
+    /// source of each incoming edge of each vertex, so that removing a vertex doesn't need to
+    /// look at every edge
+    incoming: Vec<Vec<usize>>,
+    n_vertices: usize,
+    n_edges: usize,
+}
+
+// explicit implementation because derive will normally require M to be Default
+impl<M> Default for GraphBuilder<M> {
+    fn default() -> Self {
+        Self {
+            outgoing: Default::default(),
+            incoming: Default::default(),
+            n_vertices: 0,
+            n_edges: 0,
+        }
+    }
+}
+
+impl<M> From<CompressedGraph<M>> for GraphBuilder<M> {
+    fn from(graph: CompressedGraph<M>) -> Self {
+        let (n_vertices, edges) = graph.into_parts();
+        let mut ret = Self::with_vertices(n_vertices);
+        for edge in edges {
+            ret.add_edge(edge.from, edge.to, edge.meta);
+        }
+        ret
+    }
+}
+
+impl<M> GraphBuilder<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Graph with vertices `0..n_vertices` and no edges.
+    pub fn with_vertices(n_vertices: usize) -> Self {
+        let mut ret = Self::default();
+        ret.outgoing.resize_with(n_vertices, || Some(vec![]));
+        ret.incoming.resize_with(n_vertices, Vec::new);
+        ret.n_vertices = n_vertices;
+        ret
+    }
+
+    /// Number of vertices that haven't been removed.
+    pub fn n_vertices(&self) -> usize {
+        self.n_vertices
+    }
+
+    pub fn n_edges(&self) -> usize {
+        self.n_edges
+    }
+
+    /// One more than the largest vertex ID handed out so far, including removed vertices.
+    pub fn id_bound(&self) -> usize {
+        self.outgoing.len()
+    }
+
+    pub fn contains_vertex(&self, vertex: usize) -> bool {
+        matches!(self.outgoing.get(vertex), Some(Some(_)))
+    }
+
+    /// IDs of the vertices that haven't been removed, in increasing order.
+    pub fn vertices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.id_bound()).filter(move |&v| self.contains_vertex(v))
+    }
+
+    /// Add a vertex without edges, returning its ID. IDs of removed vertices aren't reused.
+    pub fn add_vertex(&mut self) -> usize {
+        self.outgoing.push(Some(vec![]));
+        self.incoming.push(vec![]);
+        self.n_vertices += 1;
+        self.outgoing.len() - 1
+    }
+
+    /// Remove a vertex along with all of its incoming and outgoing edges. Returns false if the
+    /// vertex doesn't exist (or was already removed).
+    pub fn remove_vertex(&mut self, vertex: usize) -> bool {
+        let outgoing = match self.outgoing.get_mut(vertex).and_then(Option::take) {
+            Some(outgoing) => outgoing,
+            None => return false,
+        };
+
+        for &(to, _) in &outgoing {
+            if to != vertex {
+                remove_one(&mut self.incoming[to], |&from| from == vertex);
+            }
+        }
+        let incoming = std::mem::take(&mut self.incoming[vertex]);
+        for &from in &incoming {
+            if from != vertex {
+                let edges = self.outgoing[from].as_mut().unwrap();
+                remove_one(edges, |&(to, _)| to == vertex);
+            }
+        }
+
+        // self loops are in both lists, but only count once
+        let n_loops = outgoing.iter().filter(|&&(to, _)| to == vertex).count();
+        self.n_edges -= outgoing.len() + incoming.len() - n_loops;
+        self.n_vertices -= 1;
+        true
+    }
+
+    /// Add a directed edge, both vertices must exist.
+    pub fn add_edge(&mut self, from: usize, to: usize, meta: M) {
+        assert!(self.contains_vertex(to), "no vertex {}", to);
+        match self.outgoing.get_mut(from) {
+            Some(Some(edges)) => edges.push((to, meta)),
+            _ => panic!("no vertex {}", from),
+        }
+        self.incoming[to].push(from);
+        self.n_edges += 1;
+    }
+
+    /// Remove one edge from `from` to `to`, returning its metadata, or `None` if there's no such
+    /// edge. Parallel edges have to be removed one at a time.
+    pub fn remove_edge(&mut self, from: usize, to: usize) -> Option<M> {
+        let edges = self.outgoing.get_mut(from)?.as_mut()?;
+        let (_, meta) = remove_one(edges, |&(t, _)| t == to)?;
+        remove_one(&mut self.incoming[to], |&f| f == from);
+        self.n_edges -= 1;
+        Some(meta)
+    }
+
+    pub fn contains_edge(&self, from: usize, to: usize) -> bool {
+        self.neighbors_meta(from).any(|(t, _)| t == to)
+    }
+
+    /// Targets and metadata of the outgoing edges of a vertex, in the order they were added
+    /// (removals may reorder them). Empty for vertices that don't exist.
+    pub fn neighbors_meta(&self, vertex: usize) -> impl Iterator<Item = (usize, &M)> + '_ {
+        let edges = match self.outgoing.get(vertex) {
+            Some(Some(edges)) => edges.as_slice(),
+            _ => &[],
+        };
+        edges.iter().map(|(to, meta)| (*to, meta))
+    }
+
+    pub fn neighbors(&self, vertex: usize) -> impl Iterator<Item = usize> + '_ {
+        self.neighbors_meta(vertex).map(|(to, _)| to)
+    }
+
+    /// Convert into a `CompressedGraph` in O(V + E), renumbering the remaining vertices in order
+    /// to close the gaps left by removed ones. Also returns the new ID of each old vertex ID
+    /// (`None` for removed vertices).
+    pub fn freeze(self) -> (CompressedGraph<M>, Vec<Option<usize>>) {
+        let mut remap = Vec::with_capacity(self.outgoing.len());
+        let mut n_vertices = 0;
+        for edges in &self.outgoing {
+            remap.push(edges.as_ref().map(|_| {
+                n_vertices += 1;
+                n_vertices - 1
+            }));
+        }
+
+        // counting sort by target, then (stably) by source, so edges end up sorted the same way
+        // as `CompressedGraph` sorts them
+        let mut by_target: Vec<Vec<(usize, M)>> = (0..n_vertices).map(|_| vec![]).collect();
+        for (from, edges) in self.outgoing.into_iter().enumerate() {
+            for (to, meta) in edges.into_iter().flatten() {
+                by_target[remap[to].unwrap()].push((remap[from].unwrap(), meta));
+            }
+        }
+        let mut by_source: Vec<Vec<Edge<M>>> = (0..n_vertices).map(|_| vec![]).collect();
+        for (to, edges) in by_target.into_iter().enumerate() {
+            for (from, meta) in edges {
+                by_source[from].push(Edge::new_with_meta(from, to, meta));
+            }
+        }
+        let edges = by_source.into_iter().flatten().collect();
+
+        (CompressedGraph::from_sorted_edges(n_vertices, edges), remap)
+    }
+}
+
+/// Swap-remove the first item matching `predicate`.
+fn remove_one<T>(items: &mut Vec<T>, predicate: impl FnMut(&T) -> bool) -> Option<T> {
+    let index = items.iter().position(predicate)?;
+    Some(items.swap_remove(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+    use rand::prelude::*;
+
+    /// Edges of a compressed graph at each vertex as sorted (to, meta) pairs, since the order of
+    /// parallel edges isn't specified.
+    fn adjacency(graph: &CompressedGraph<u32>) -> Vec<Vec<(usize, u32)>> {
+        graph
+            .vertices()
+            .map(|v| {
+                graph
+                    .neighbors_meta(v)
+                    .map(|(to, &meta)| (to, meta))
+                    .sorted()
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Random sequence of edits, including parallel edges, self loops, and invalid removals.
+    fn random_builder(seed: u64) -> GraphBuilder<u32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut builder = GraphBuilder::with_vertices(rng.gen_range(0, 8));
+        for step in 0..rng.gen_range(0, 100) {
+            let bound = builder.id_bound();
+            let (a, b) = (rng.gen_range(0, bound + 2), rng.gen_range(0, bound + 2));
+            match rng.gen_range(0, 10) {
+                0 | 1 => {
+                    builder.add_vertex();
+                }
+                2 => {
+                    builder.remove_vertex(a);
+                }
+                3 | 4 => {
+                    builder.remove_edge(a, b);
+                }
+                _ if builder.contains_vertex(a) && builder.contains_vertex(b) => {
+                    builder.add_edge(a, b, step);
+                }
+                _ => {}
+            }
+        }
+        builder
+    }
+
+    #[test]
+    fn freeze_matches_compressed_graph() {
+        for seed in 0..500 {
+            let builder = random_builder(seed);
+            let edges = builder
+                .vertices()
+                .flat_map(|v| {
+                    builder
+                        .neighbors_meta(v)
+                        .map(move |(to, &meta)| (v, to, meta))
+                })
+                .collect_vec();
+            assert_eq!(edges.len(), builder.n_edges());
+
+            let n_vertices = builder.n_vertices();
+            let id_bound = builder.id_bound();
+            let (graph, remap) = builder.freeze();
+            assert_eq!(graph.n_vertices(), n_vertices);
+            assert_eq!(graph.n_edges(), edges.len());
+            assert_eq!(remap.len(), id_bound);
+
+            // same graph as building it directly, with the vertices renumbered
+            let expected = CompressedGraph::new(
+                n_vertices,
+                edges
+                    .iter()
+                    .map(|&(from, to, meta)| (remap[from].unwrap(), remap[to].unwrap(), meta)),
+            );
+            assert_eq!(adjacency(&graph), adjacency(&expected));
+            // edges are sorted by source and target, like CompressedGraph keeps them
+            assert!(graph
+                .edges()
+                .iter()
+                .tuple_windows()
+                .all(|(a, b)| (a.from, a.to) <= (b.from, b.to)));
+
+            // remapping keeps vertex order
+            let kept = remap.iter().flatten().copied().collect_vec();
+            assert_eq!(kept, (0..n_vertices).collect_vec());
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for seed in 0..500 {
+            let (graph, _) = random_builder(seed).freeze();
+            let (again, remap) = GraphBuilder::from(graph.clone()).freeze();
+            assert_eq!(adjacency(&again), adjacency(&graph));
+            assert!(remap.iter().enumerate().all(|(i, &v)| v == Some(i)));
+        }
+    }
+
+    #[test]
+    fn edits() {
+        let mut builder = GraphBuilder::with_vertices(4);
+        for &(a, b) in &[
+            (0, 1),
+            (1, 0),
+            (1, 2),
+            (2, 1),
+            (2, 3),
+            (3, 2),
+            (3, 3),
+            (0, 1),
+        ] {
+            builder.add_edge(a, b, ());
+        }
+        assert_eq!(builder.n_edges(), 8);
+
+        // parallel edges are removed one at a time
+        assert_eq!(builder.remove_edge(0, 1), Some(()));
+        assert!(builder.contains_edge(0, 1));
+        assert_eq!(builder.remove_edge(0, 2), None);
+
+        // removes edges in both directions, and the self loop
+        assert!(builder.remove_vertex(3));
+        assert!(!builder.remove_vertex(3));
+        assert_eq!(builder.n_edges(), 4);
+        assert_eq!(builder.neighbors(2).collect_vec(), vec![1]);
+
+        let v = builder.add_vertex();
+        assert_eq!(v, 4);
+        builder.add_edge(v, 0, ());
+        assert!(builder.remove_vertex(1));
+
+        let (graph, remap) = builder.freeze();
+        assert_eq!(remap, vec![Some(0), None, Some(1), None, Some(2)]);
+        assert_eq!(graph.n_vertices(), 3);
+        assert_eq!(
+            graph.edges(),
+            &[Edge::new(2, 0)],
+            "only the edge from the new vertex is left"
+        );
+    }
+}
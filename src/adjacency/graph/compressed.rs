@@ -1,11 +1,13 @@
 use itertools::Itertools;
 use rand::prelude::*;
+use std::collections::VecDeque;
 use std::iter::FromIterator;
 use std::ops::Range;
 
 use super::Edge;
 
-/// Note: `CompressedGraph` is not made for efficient modification, only access.
+/// Note: `CompressedGraph` is not made for efficient modification, only access. Use a
+/// `GraphBuilder` for incremental edits and freeze it when done.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CompressedGraph<M = ()> {
     /// graph edges
@@ -41,7 +43,7 @@ where
 
         // replace the old edges with the old ones + new ones merged together
         let old_edges = self.edges.drain(..);
-        self.edges = Itertools::merge_by(old_edges, new_edges.into_iter(), |a, b| {
+        self.edges = Itertools::merge_by(old_edges, new_edges, |a, b| {
             (a.from, a.to).lt(&(b.from, b.to))
         })
         .collect();
@@ -80,6 +82,25 @@ impl<M> CompressedGraph<M> {
         ret
     }
 
+    /// Graph from edges that are already sorted by (from, to), in O(V + E).
+    pub(super) fn from_sorted_edges(n_vertices: usize, edges: Vec<Edge<M>>) -> Self {
+        debug_assert!(edges
+            .iter()
+            .tuple_windows()
+            .all(|(a, b)| (a.from, a.to) <= (b.from, b.to)));
+        let mut ret = Self {
+            edges,
+            edge_ranges: vec![0..0; n_vertices],
+        };
+        ret.update_edge_ranges();
+        ret
+    }
+
+    /// Number of vertices and the edges, sorted by (from, to).
+    pub(super) fn into_parts(self) -> (usize, Vec<Edge<M>>) {
+        (self.n_vertices(), self.edges)
+    }
+
     /// Number of vertices, note: not all vertices necessarily have edges.
     #[inline(always)]
     pub fn n_vertices(&self) -> usize {
@@ -99,7 +120,7 @@ impl<M> CompressedGraph<M> {
     pub fn resize(&mut self, new_n_vertices: usize) {
         let n_vertices = self.n_vertices();
 
-        if n_vertices == 0 {
+        if new_n_vertices == 0 {
             self.edges.clear();
             self.edge_ranges.clear();
         } else if new_n_vertices < n_vertices {
@@ -142,7 +163,10 @@ impl<M> CompressedGraph<M> {
     }
 
     #[inline(always)]
-    pub fn neighbors_meta<'a>(&'a self, vertex: usize) -> impl Iterator<Item = (usize, &M)> + 'a {
+    pub fn neighbors_meta<'a>(
+        &'a self,
+        vertex: usize,
+    ) -> impl Iterator<Item = (usize, &'a M)> + 'a {
         self.edges_at(vertex).iter().map(|e| (e.to, &e.meta))
     }
 
@@ -166,12 +190,10 @@ impl<M> CompressedGraph<M> {
         todo!()
     }
 
+    /// Split the vertices into two sets such that every edge goes between them, coloring each
+    /// connected component separately (starting from its lowest vertex). Vertices without edges
+    /// are left out of both sets. Returns `None` if the graph isn't bipartite.
     pub fn bipartite_coloring(&self) -> Option<(Vec<usize>, Vec<usize>)> {
-        let (start, _) = self
-            .edge_ranges
-            .iter()
-            .find_position(|&r| r.end > r.start)?;
-
         #[derive(Copy, Clone, Eq, PartialEq)]
         enum Color {
             Red,
@@ -180,54 +202,132 @@ impl<M> CompressedGraph<M> {
         }
 
         let mut colors = vec![Color::None; self.n_vertices()];
-        let mut to_visit = vec![self.edge_ranges[start].clone()];
-        colors[start] = Color::Red;
-
-        while let Some(edge_ids) = to_visit.last_mut() {
-            match edge_ids.next().map(|id| &self.edges[id]) {
-                Some(edge) => {
-                    let next_color = match colors[edge.from] {
-                        Color::Red => Color::Blue,
-                        Color::Blue => Color::Red,
-                        Color::None => unreachable!("error"),
-                    };
-
-                    if colors[edge.to] == Color::None {
-                        // alternate colors for bipartite coloring
-                        colors[edge.to] = next_color;
-                        to_visit.push(self.edge_ranges[edge.to].clone());
-                    } else if colors[edge.to] != next_color {
-                        // wrong color, not bipartite
-                        dbg!("wrong color, not bipartite");
-                        return None;
+        for start in self.vertices() {
+            if colors[start] != Color::None || self.edges_at(start).is_empty() {
+                continue;
+            }
+
+            let mut to_visit = vec![self.edge_ranges[start].clone()];
+            colors[start] = Color::Red;
+
+            while let Some(edge_ids) = to_visit.last_mut() {
+                match edge_ids.next().map(|id| &self.edges[id]) {
+                    Some(edge) => {
+                        let next_color = match colors[edge.from] {
+                            Color::Red => Color::Blue,
+                            Color::Blue => Color::Red,
+                            Color::None => unreachable!("visited vertices are always colored"),
+                        };
+
+                        if colors[edge.to] == Color::None {
+                            // alternate colors for bipartite coloring
+                            colors[edge.to] = next_color;
+                            to_visit.push(self.edge_ranges[edge.to].clone());
+                        } else if colors[edge.to] != next_color {
+                            // wrong color, not bipartite
+                            return None;
+                        }
+                    }
+                    None => {
+                        to_visit.pop();
                     }
-                }
-                None => {
-                    to_visit.pop();
                 }
             }
         }
 
         let mut red = Vec::with_capacity(colors.len());
         let mut blue = Vec::with_capacity(colors.len());
-
         for (i, color) in colors.into_iter().enumerate() {
             match color {
                 Color::Red => red.push(i),
                 Color::Blue => blue.push(i),
-                Color::None => {
-                    // couldn't reach all vertices
-                    if !self.edges_at(i).is_empty() {
-                        dbg!("couldn't reach all vertices (multiple connected components)");
-                        return None;
-                    }
-                }
+                Color::None => {}
             }
         }
 
         Some((red, blue))
     }
 
+    /// Connected components of the graph (ignoring edge direction), each one sorted, and in order
+    /// of their lowest vertex. Vertices without edges are components of their own.
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        // union-find, with path halving
+        fn root(parents: &mut [usize], mut v: usize) -> usize {
+            while parents[v] != v {
+                parents[v] = parents[parents[v]];
+                v = parents[v];
+            }
+            v
+        }
+
+        let mut parents = self.vertices().collect_vec();
+        for edge in &self.edges {
+            let (a, b) = (root(&mut parents, edge.from), root(&mut parents, edge.to));
+            // keep the lowest vertex as the root, so components come out in order
+            parents[usize::max(a, b)] = usize::min(a, b);
+        }
+
+        let mut components: Vec<Vec<usize>> = Vec::new();
+        let mut component_of = vec![usize::MAX; self.n_vertices()];
+        for v in self.vertices() {
+            let r = root(&mut parents, v);
+            if r == v {
+                component_of[v] = components.len();
+                components.push(vec![v]);
+            } else {
+                components[component_of[r]].push(v);
+            }
+        }
+        components
+    }
+
+    /// Number of edges on the shortest path from `source` to every vertex (following edge
+    /// direction), or `None` for vertices that can't be reached.
+    pub fn distances_from(&self, source: usize) -> Vec<Option<usize>> {
+        self.bfs(source).0
+    }
+
+    /// A shortest path from `from` to `to` (following edge direction), including both ends.
+    pub fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let (distances, parents) = self.bfs(from);
+        distances[to]?;
+
+        let mut path = vec![to];
+        while let Some(parent) = parents[*path.last().unwrap()] {
+            path.push(parent);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// All-pairs topological distances, `matrix[i][j]` is the number of edges on the shortest
+    /// path from `i` to `j`, or `None` if there is no such path.
+    pub fn distance_matrix(&self) -> Vec<Vec<Option<usize>>> {
+        self.vertices().map(|v| self.distances_from(v)).collect()
+    }
+
+    /// Breadth-first search from `source`, returning the distance to and the previous vertex on
+    /// a shortest path to each vertex.
+    fn bfs(&self, source: usize) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+        let mut distances = vec![None; self.n_vertices()];
+        let mut parents = vec![None; self.n_vertices()];
+        let mut queue = VecDeque::new();
+
+        distances[source] = Some(0);
+        queue.push_back(source);
+        while let Some(v) = queue.pop_front() {
+            let distance = distances[v].map(|d| d + 1);
+            for to in self.neighbors(v) {
+                if distances[to].is_none() {
+                    distances[to] = distance;
+                    parents[to] = Some(v);
+                    queue.push_back(to);
+                }
+            }
+        }
+        (distances, parents)
+    }
+
     #[doc(hidden)]
     fn update_edge_ranges(&mut self) {
         let old_num_vertices = self.n_vertices();
@@ -258,3 +358,112 @@ impl<M> CompressedGraph<M> {
             .collect();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Graph with an edge in each direction for each pair.
+    fn undirected(n_vertices: usize, pairs: &[(usize, usize)]) -> CompressedGraph {
+        let edges = pairs.iter().flat_map(|&(a, b)| vec![(a, b), (b, a)]);
+        CompressedGraph::new(n_vertices, edges)
+    }
+
+    fn ring(n: usize) -> CompressedGraph {
+        undirected(n, &(0..n).map(|i| (i, (i + 1) % n)).collect_vec())
+    }
+
+    #[test]
+    fn connected_components() {
+        // triangle, path, and an isolated vertex
+        let graph = undirected(7, &[(0, 1), (1, 2), (2, 0), (3, 4), (5, 4)]);
+        let expected = vec![vec![0, 1, 2], vec![3, 4, 5], vec![6]];
+        assert_eq!(graph.connected_components(), expected);
+
+        // components are found from the lowest vertex regardless of edge order
+        let graph = undirected(5, &[(4, 2), (3, 1), (2, 0)]);
+        assert_eq!(
+            graph.connected_components(),
+            vec![vec![0, 2, 4], vec![1, 3]]
+        );
+
+        // edge direction is ignored
+        let graph = CompressedGraph::new(3, vec![(2, 0)]);
+        assert_eq!(graph.connected_components(), vec![vec![0, 2], vec![1]]);
+
+        assert!(CompressedGraph::<()>::default()
+            .connected_components()
+            .is_empty());
+    }
+
+    #[test]
+    fn resize() {
+        // vertices without edges, starting from an empty graph
+        let mut graph = CompressedGraph::new(3, Vec::<(usize, usize)>::new());
+        assert_eq!(graph.n_vertices(), 3);
+        assert_eq!(graph.edges_at(2), &[]);
+
+        graph.extend(vec![(0, 1), (1, 0)]);
+        graph.clear();
+        assert_eq!((graph.n_vertices(), graph.n_edges()), (0, 0));
+    }
+
+    #[test]
+    fn bipartite_coloring_per_component() {
+        // square, separate edge, isolated vertex
+        let graph = undirected(7, &[(0, 1), (1, 2), (2, 3), (3, 0), (4, 5)]);
+        let (red, blue) = graph.bipartite_coloring().unwrap();
+        assert_eq!(red, vec![0, 2, 4]);
+        assert_eq!(blue, vec![1, 3, 5]);
+
+        // odd cycle in the second component
+        let graph = undirected(5, &[(0, 1), (2, 3), (3, 4), (4, 2)]);
+        assert_eq!(graph.bipartite_coloring(), None);
+
+        assert_eq!(ring(6).bipartite_coloring().unwrap().0, vec![0, 2, 4]);
+        assert_eq!(ring(5).bipartite_coloring(), None);
+    }
+
+    #[test]
+    fn shortest_paths() {
+        let graph = ring(6);
+        let path = graph.shortest_path(0, 3).unwrap();
+        assert_eq!(path.len(), 4);
+        assert_eq!((path[0], path[3]), (0, 3));
+        for (&a, &b) in path.iter().tuple_windows() {
+            assert!(graph.neighbors(a).any(|n| n == b));
+        }
+
+        assert_eq!(graph.shortest_path(2, 1), Some(vec![2, 1]));
+        assert_eq!(graph.shortest_path(4, 4), Some(vec![4]));
+
+        let distances = graph.distances_from(1);
+        assert_eq!(
+            distances,
+            vec![Some(1), Some(0), Some(1), Some(2), Some(3), Some(2)]
+        );
+
+        // unreachable vertices, including against edge direction
+        let graph = CompressedGraph::new(4, vec![(0, 1), (1, 2)]);
+        assert_eq!(graph.shortest_path(0, 2), Some(vec![0, 1, 2]));
+        assert_eq!(graph.shortest_path(2, 0), None);
+        assert_eq!(graph.shortest_path(0, 3), None);
+        assert_eq!(graph.distances_from(1), vec![None, Some(0), Some(1), None]);
+    }
+
+    #[test]
+    fn distance_matrix() {
+        let n = 7;
+        let matrix = ring(n).distance_matrix();
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &distance) in row.iter().enumerate() {
+                let d = usize::max(i, j) - usize::min(i, j);
+                assert_eq!(distance, Some(usize::min(d, n - d)));
+            }
+        }
+
+        let matrix = undirected(3, &[(0, 1)]).distance_matrix();
+        assert_eq!(matrix[0], vec![Some(0), Some(1), None]);
+        assert_eq!(matrix[2], vec![None, None, Some(0)]);
+    }
+}
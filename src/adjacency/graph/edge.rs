@@ -26,7 +26,7 @@ impl From<(usize, usize)> for Edge {
     }
 }
 
-impl<'a> From<&'a (usize, usize)> for Edge {
+impl From<&(usize, usize)> for Edge {
     fn from(e: &(usize, usize)) -> Self {
         Self::new(e.0, e.1)
     }
@@ -38,7 +38,7 @@ impl<M> From<(usize, usize, M)> for Edge<M> {
     }
 }
 
-impl<'a, M: Copy> From<&'a (usize, usize, M)> for Edge<M> {
+impl<M: Copy> From<&(usize, usize, M)> for Edge<M> {
     fn from(e: &(usize, usize, M)) -> Self {
         Self::new_with_meta(e.0, e.1, e.2)
     }
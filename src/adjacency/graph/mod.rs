@@ -1,5 +1,7 @@
+mod builder;
 mod compressed;
 mod edge;
 
+pub use builder::GraphBuilder;
 pub use compressed::CompressedGraph;
 pub use edge::Edge;
@@ -5,6 +5,11 @@ use nalgebra::{Matrix3, Vector3};
 use vasp_poscar::Poscar;
 
 pub mod graph;
+mod passivation;
+
+pub use passivation::{
+    passivate, passivate_poscar, CapRule, Hybridization, PassivationOptions, PassivationReport, CAP,
+};
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, PartialEq, Eq)]
 pub struct Bond {
@@ -23,6 +28,16 @@ impl From<Bond> for Edge {
     }
 }
 
+/// Periodic information about a bond, kept as edge metadata by `calc_graph`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BondMeta {
+    /// periodic image of the cell containing the `to` atom, see `Bond`
+    pub image_offset: [i32; 3],
+    /// cartesian vector from the `from` atom to (the image of) the `to` atom
+    pub delta: Vector3<f64>,
+    pub length: f64,
+}
+
 impl Bond {
     pub fn to_delta(&self, lattice: &Matrix3<f64>, coords: &CoordMat<f64>) -> Vector3<f64> {
         let [a, b, c] = self.image_offset;
@@ -32,6 +47,31 @@ impl Bond {
             + c as f64 * lattice.column(2))
             - coords.column(self.from)
     }
+
+    pub fn to_edge(&self, lattice: &Matrix3<f64>, coords: &CoordMat<f64>) -> Edge<BondMeta> {
+        let delta = self.to_delta(lattice, coords);
+        Edge::new_with_meta(
+            self.from,
+            self.to,
+            BondMeta {
+                image_offset: self.image_offset,
+                delta,
+                length: delta.norm(),
+            },
+        )
+    }
+}
+
+/// Cartesian vector along a bond, from the `from` atom to the periodic image of the `to` atom
+/// that it's bonded to.
+pub fn bond_vector(edge: &Edge<BondMeta>) -> Vector3<f64> {
+    edge.meta.delta
+}
+
+/// Whether a bond crosses the boundary of the periodic cell, i.e. it connects to an atom in a
+/// neighboring image rather than the cell itself.
+pub fn wraps_boundary(edge: &Edge<BondMeta>) -> bool {
+    edge.meta.image_offset != [0, 0, 0]
 }
 
 pub fn calc_bonds(
@@ -65,6 +105,17 @@ pub fn calc_bonds(
             .flat_map(move |b| (0..ic)
                 .map(move |c| [a, b, c]
                 )));
+    // offsets with mixed signs (needed for oblique cells), only one of each +/- pair since both
+    // directions of a bond are added at once
+    #[rustfmt::skip]
+    let mixed_offsets = (1 - ia..ia)
+        .flat_map(move |a| (1 - ib..ib)
+            .flat_map(move |b| (1 - ic..ic)
+                .map(move |c| [a, b, c]
+                )))
+        .filter(|o| o.iter().any(|&x| x > 0) && o.iter().any(|&x| x < 0))
+        .filter(|o| o.iter().find(|&&x| x != 0) > Some(&0));
+    let image_offsets = image_offsets.chain(mixed_offsets);
 
     let n = coords.ncols();
     let cutoff_squared = cutoff_distance * cutoff_distance;
@@ -77,10 +128,7 @@ pub fn calc_bonds(
             + vb * image_offset[1] as f64
             + vc * image_offset[2] as f64;
 
-        let in_unit_cell = match image_offset {
-            [0, 0, 0] => true,
-            _ => false,
-        };
+        let in_unit_cell = matches!(image_offset, [0, 0, 0]);
 
         for (i, from) in coords.column_iter().enumerate() {
             let from = from + coord_offset;
@@ -108,51 +156,159 @@ pub fn calc_bonds(
     bonds
 }
 
+/// Bond graph with one vertex per atom (including atoms without bonds) and a directed edge in each
+/// direction for every bond, see `calc_bonds`.
 pub fn calc_graph(
     // columns are lattice vectors
     lattice: &Matrix3<f64>,
     coords: &CoordMat<f64>,
     cutoff_distance: f64,
-) -> CompressedGraph {
+) -> CompressedGraph<BondMeta> {
     let bond_list = calc_bonds(lattice, coords, cutoff_distance);
-    bond_list.into_iter().flat_map(|b| b.into_iter()).collect()
+    let edges = bond_list
+        .iter()
+        .flatten()
+        .map(|b| b.to_edge(lattice, coords));
+    CompressedGraph::new(coords.ncols(), edges)
 }
 
-pub fn add_hydrogen(poscar: Poscar, hydrogen_dist: f64, cutoff_distance: f64) -> Poscar {
-    use vasp_poscar::{Builder, Coords};
+/// Cap sp2 carbons with hydrogen at `hydrogen_dist`, with bonds between atoms closer than
+/// `cutoff_distance` (see `passivate_poscar` for other elements and geometries). Sites without
+/// symbols are taken to be carbon. Fails for non-positive distances, and sites that couldn't be
+/// capped are listed in the report.
+pub fn add_hydrogen(
+    poscar: Poscar,
+    hydrogen_dist: f64,
+    cutoff_distance: f64,
+) -> Result<(Poscar, PassivationReport), String> {
+    let mut options = PassivationOptions {
+        cutoff_distance,
+        ..Default::default()
+    };
+    options.set_bond_length("C", CAP, hydrogen_dist);
+    passivate_poscar(poscar, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chain of two atoms along x, 1 apart and bonded to each other both within the cell and
+    /// across its boundary, plus an atom that isn't bonded to anything.
+    fn chain_cell() -> (Matrix3<f64>, CoordMat<f64>) {
+        let lattice = Matrix3::from_diagonal(&Vector3::new(2.0, 10.0, 10.0));
+        let coords = CoordMat::from_columns(&[
+            Vector3::new(0.2, 5.0, 5.0),
+            Vector3::new(1.2, 5.0, 5.0),
+            Vector3::new(1.0, 8.0, 5.0),
+        ]);
+        (lattice, coords)
+    }
 
-    macro_rules! elements_of {
-        ($it:expr) => {
-            $it.iter().flat_map(|c| c.iter().copied())
+    fn approx_eq(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).norm() < 1e-12
+    }
+
+    #[test]
+    fn add_hydrogen_reports_instead_of_panicking() {
+        let poscar = vasp_poscar::Builder::new()
+            .group_counts(vec![1])
+            .group_symbols(vec!["C"])
+            .lattice_vectors(&[[10.0, 0.0, 0.0], [0.0, 10.0, 0.0], [0.0, 0.0, 10.0]])
+            .positions(vasp_poscar::Coords::Cart(vec![[5.0, 5.0, 5.0]]))
+            .build()
+            .unwrap();
+        assert!(add_hydrogen(poscar.clone(), 0.0, 1.6).is_err());
+
+        // a lone carbon has no bonds to orient its caps
+        let (capped, report) = add_hydrogen(poscar, 1.09, 1.6).unwrap();
+        assert_eq!(capped.num_sites(), 1);
+        assert_eq!(report.unsaturated, vec![(0, 3)]);
+        assert!(report.check_saturated().is_err());
+    }
+
+    #[test]
+    fn bond_to_edge() {
+        let (lattice, coords) = chain_cell();
+        let bond = Bond {
+            from: 1,
+            to: 0,
+            image_offset: [1, 0, 0],
         };
+
+        let edge = bond.to_edge(&lattice, &coords);
+        assert_eq!((edge.from, edge.to), (1, 0));
+        assert_eq!(edge.meta.image_offset, [1, 0, 0]);
+        assert!(approx_eq(edge.meta.delta, Vector3::new(1.0, 0.0, 0.0)));
+        assert!((edge.meta.length - 1.0).abs() < 1e-12);
+        assert_eq!(bond_vector(&edge), edge.meta.delta);
+        assert!(wraps_boundary(&edge));
+
+        let inside = Bond {
+            from: 0,
+            to: 1,
+            image_offset: [0, 0, 0],
+        };
+        assert!(!wraps_boundary(&inside.to_edge(&lattice, &coords)));
     }
 
-    let lattice = Matrix3::from_iterator(elements_of!(poscar.scaled_lattice_vectors()));
-    let mut new_coords = poscar.scaled_cart_positions().iter().copied().collect_vec();
-
-    let coords = CoordMat::from_iterator(new_coords.len(), elements_of!(new_coords));
-    let bond_list = calc_bonds(&lattice, &coords, cutoff_distance);
-
-    let num_carbon = new_coords.len();
-    new_coords.extend(bond_list.into_iter().enumerate().filter_map(|(i, bonds)| {
-        match bonds.as_slice() {
-            [b1, b2] => {
-                let b1 = b1.to_delta(&lattice, &coords).normalize();
-                let b2 = b2.to_delta(&lattice, &coords).normalize();
-                let h_bond = -hydrogen_dist * (b1 + b2).normalize();
-                let h = coords.column(i) + h_bond;
-                Some([h[0], h[1], h[2]])
-            }
-            _ => None,
+    #[test]
+    fn oblique_cell_mixed_offsets() {
+        // the only bond is between images one cell apart along a - b, which has mixed signs
+        let lattice = Matrix3::from_columns(&[
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(1.9, 0.5, 0.0),
+            Vector3::new(0.0, 0.0, 10.0),
+        ]);
+        let coords = CoordMat::from_columns(&[Vector3::new(0.5, 0.5, 5.0)]);
+
+        let bonds = calc_bonds(&lattice, &coords, 0.6);
+        let mut offsets = bonds[0].iter().map(|b| b.image_offset).collect::<Vec<_>>();
+        offsets.sort();
+        assert_eq!(offsets, vec![[-1, 1, 0], [1, -1, 0]]);
+        for bond in &bonds[0] {
+            let length = bond.to_delta(&lattice, &coords).norm();
+            assert!((length - f64::hypot(0.1, 0.5)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn periodic_chain_graph() {
+        let (lattice, coords) = chain_cell();
+        let graph = calc_graph(&lattice, &coords, 1.1);
+
+        // the unbonded atom still gets a vertex
+        assert_eq!(graph.n_vertices(), 3);
+        assert!(graph.edges_at(2).is_empty());
+
+        // atom 0 bonds to atom 1 to its right in the cell and to its image on its left
+        let mut at_zero = graph
+            .edges_at(0)
+            .iter()
+            .map(|e| (e.to, e.meta.image_offset, wraps_boundary(e), bond_vector(e)))
+            .collect::<Vec<_>>();
+        at_zero.sort_by_key(|&(_, offset, _, _)| offset);
+        assert_eq!(at_zero.len(), 2);
+        assert_eq!(
+            (at_zero[0].0, at_zero[0].1, at_zero[0].2),
+            (1, [-1, 0, 0], true)
+        );
+        assert!(approx_eq(at_zero[0].3, Vector3::new(-1.0, 0.0, 0.0)));
+        assert_eq!(
+            (at_zero[1].0, at_zero[1].1, at_zero[1].2),
+            (1, [0, 0, 0], false)
+        );
+        assert!(approx_eq(at_zero[1].3, Vector3::new(1.0, 0.0, 0.0)));
+
+        // every edge is matched by one in the other direction
+        for edge in graph.edges() {
+            let [a, b, c] = edge.meta.image_offset;
+            let reverse = graph
+                .edges_at(edge.to)
+                .iter()
+                .find(|e| e.to == edge.from && e.meta.image_offset == [-a, -b, -c])
+                .expect("missing reverse edge");
+            assert!(approx_eq(reverse.meta.delta, -edge.meta.delta));
         }
-    }));
-    let num_hydrogen = new_coords.len() - num_carbon;
-
-    Builder::new()
-        .group_counts(vec![num_carbon, num_hydrogen])
-        .group_symbols(vec!["C", "H"])
-        .lattice_vectors(&poscar.scaled_lattice_vectors())
-        .positions(Coords::Cart(new_coords))
-        .build()
-        .unwrap()
+    }
 }
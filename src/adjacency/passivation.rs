@@ -0,0 +1,485 @@
+use super::calc_bonds;
+use crate::CoordMat;
+use itertools::Itertools;
+use nalgebra::{Matrix3, Vector3};
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use vasp_poscar::{Builder, Coords, Poscar};
+
+/// Element of the capping atoms.
+pub const CAP: &str = "H";
+
+/// Angle between the bonds of a tetrahedral (sp3) site, acos(-1/3).
+const TETRAHEDRAL_ANGLE: f64 = 1.910_633_236_249_019;
+
+/// Ideal geometry of a site, which decides where the caps go relative to its existing bonds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Hybridization {
+    /// Three bonds at 120 degrees in a plane, e.g. aromatic carbon.
+    Sp2,
+    /// Four tetrahedral bonds, e.g. alkane carbon.
+    Sp3,
+}
+
+/// How many bonds an element should end up with, and in what geometry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CapRule {
+    /// Target number of bonds, including the caps. This can be less than the number of
+    /// directions of the geometry, e.g. 2 for pyridinic nitrogen, whose lone pair takes the
+    /// third sp2 direction.
+    pub valence: usize,
+    pub hybridization: Hybridization,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassivationOptions {
+    /// Rule for each element that should be passivated. Atoms of other elements are left alone
+    /// (and reported, except for the caps themselves).
+    pub rules: BTreeMap<String, CapRule>,
+    /// Bond length for each pair of elements in angstroms, in either order. Every element with a
+    /// rule needs a bond length to the cap.
+    pub bond_lengths: BTreeMap<(String, String), f64>,
+    /// Bonds are between atoms closer than this, see `calc_bonds`.
+    pub cutoff_distance: f64,
+}
+
+impl Default for PassivationOptions {
+    /// sp2 carbon, as in graphene nanoribbons, with common bond lengths to hydrogen.
+    fn default() -> Self {
+        let mut options = Self {
+            rules: BTreeMap::new(),
+            bond_lengths: BTreeMap::new(),
+            cutoff_distance: 1.6,
+        };
+        options.set_rule("C", 3, Hybridization::Sp2);
+        for &(elem, length) in &[
+            ("B", 1.19),
+            ("C", 1.09047),
+            ("N", 1.01),
+            ("O", 0.96),
+            ("Si", 1.48),
+        ] {
+            options.set_bond_length(elem, CAP, length);
+        }
+        options
+    }
+}
+
+impl PassivationOptions {
+    pub fn set_rule(&mut self, elem: &str, valence: usize, hybridization: Hybridization) {
+        let rule = CapRule {
+            valence,
+            hybridization,
+        };
+        self.rules.insert(elem.into(), rule);
+    }
+
+    pub fn set_bond_length(&mut self, a: &str, b: &str, length: f64) {
+        // keep one entry per pair, whatever order it was given in
+        self.bond_lengths.remove(&(b.into(), a.into()));
+        self.bond_lengths.insert((a.into(), b.into()), length);
+    }
+
+    pub fn bond_length(&self, a: &str, b: &str) -> Option<f64> {
+        let get = |a: &str, b: &str| self.bond_lengths.get(&(a.into(), b.into())).copied();
+        get(a, b).or_else(|| get(b, a))
+    }
+}
+
+/// Outcome of `passivate` for each site that has a rule (or should have had one).
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PassivationReport {
+    /// Sites that were capped, with the indices of their caps, which are appended after the
+    /// original atoms in the order of their sites.
+    #[pyo3(get)]
+    pub passivated: Vec<(usize, Vec<usize>)>,
+    /// Sites still short of their valence, with the number of missing bonds. Caps can't be
+    /// placed on sites without any bonds to orient them, or with bonds that don't fit the
+    /// geometry (e.g. two opposite bonds of an sp2 site).
+    #[pyo3(get)]
+    pub unsaturated: Vec<(usize, usize)>,
+    /// Sites of elements without a rule (other than the caps), which were left alone.
+    #[pyo3(get)]
+    pub unknown: Vec<usize>,
+}
+
+impl PassivationReport {
+    /// Error listing the sites still short of their valence, if there are any.
+    pub fn check_saturated(&self) -> Result<(), String> {
+        if self.unsaturated.is_empty() {
+            return Ok(());
+        }
+        let sites = self
+            .unsaturated
+            .iter()
+            .map(|(site, missing)| format!("{} ({} missing)", site, missing))
+            .join(", ");
+        Err(format!("couldn't passivate sites {}", sites))
+    }
+}
+
+#[pymethods]
+impl PassivationReport {
+    #[getter]
+    pub fn num_caps(&self) -> usize {
+        self.passivated.iter().map(|(_, caps)| caps.len()).sum()
+    }
+}
+
+/// Some unit vector perpendicular to `v`.
+fn perpendicular(v: &Vector3<f64>) -> Vector3<f64> {
+    let reference = match v.cross(&Vector3::z()).norm() > 1e-3 * v.norm() {
+        true => Vector3::z(),
+        false => Vector3::x(),
+    };
+    v.cross(&reference).normalize()
+}
+
+/// Directions of the caps of a site in the given geometry, from its unit bond vectors. For a
+/// single bond, `reference` is another bond of the neighbor, which fixes the rotation of the
+/// caps about the bond: sp2 caps lie in the plane of both bonds, and sp3 caps are staggered with
+/// respect to it.
+fn cap_directions(
+    hybridization: Hybridization,
+    bonds: &[Vector3<f64>],
+    reference: Option<Vector3<f64>>,
+) -> Vec<Vector3<f64>> {
+    let sum = bonds.iter().sum::<Vector3<f64>>();
+    // bonds that cancel out (e.g. a linear site) don't give a direction for the caps
+    let opposite = match sum.norm() > 1e-3 {
+        true => Some(-sum.normalize()),
+        false => None,
+    };
+    match (hybridization, bonds) {
+        (Hybridization::Sp2, [_, _]) | (Hybridization::Sp3, [_, _, _]) => {
+            opposite.into_iter().collect()
+        }
+        (Hybridization::Sp3, [a, b]) => match opposite {
+            Some(opposite) => {
+                let normal = a.cross(b).normalize();
+                let half = TETRAHEDRAL_ANGLE / 2.0;
+                let (sin, cos) = half.sin_cos();
+                vec![opposite * cos + normal * sin, opposite * cos - normal * sin]
+            }
+            None => vec![],
+        },
+        (_, [bond]) => {
+            // unit vector perpendicular to the bond, towards the neighbor's other bond
+            let toward = reference
+                .map(|r| r - bond * bond.dot(&r))
+                .filter(|r| r.norm() > 1e-3)
+                .map(|r| r.normalize())
+                .unwrap_or_else(|| perpendicular(bond));
+            let (angle, azimuths) = match hybridization {
+                Hybridization::Sp2 => (2.0 * std::f64::consts::FRAC_PI_3, vec![0.0, 180.0]),
+                Hybridization::Sp3 => (TETRAHEDRAL_ANGLE, vec![180.0, 300.0, 60.0]),
+            };
+            let side = bond.cross(&toward);
+            let (sin, cos) = angle.sin_cos();
+            azimuths
+                .into_iter()
+                .map(|azimuth: f64| {
+                    let (s, c) = azimuth.to_radians().sin_cos();
+                    bond * cos + (toward * c + side * s) * sin
+                })
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+/// Positions of the caps (see `CAP`) that bring every atom with a rule up to its valence, along
+/// with a report of what was (and wasn't) passivated. Caps of each site go in the free directions
+/// of its geometry, at the bond length for the pair of elements.
+pub fn passivate(
+    // columns are lattice vectors
+    lattice: &Matrix3<f64>,
+    coords: &CoordMat<f64>,
+    elements: &[&str],
+    options: &PassivationOptions,
+) -> Result<(Vec<Vector3<f64>>, PassivationReport), String> {
+    assert_eq!(elements.len(), coords.ncols());
+    if !(options.cutoff_distance > 0.0 && options.cutoff_distance.is_finite()) {
+        return Err("cutoff distance must be positive".into());
+    }
+
+    let bonds = calc_bonds(lattice, coords, options.cutoff_distance)
+        .iter()
+        .map(|bonds| {
+            bonds
+                .iter()
+                .map(|b| (b.to, b.to_delta(lattice, coords)))
+                .collect_vec()
+        })
+        .collect_vec();
+
+    let mut caps = vec![];
+    let mut report = PassivationReport::default();
+    for (i, &elem) in elements.iter().enumerate() {
+        let rule = match options.rules.get(elem) {
+            Some(rule) => rule,
+            None if elem == CAP => continue,
+            None => {
+                report.unknown.push(i);
+                continue;
+            }
+        };
+        let missing = rule.valence.saturating_sub(bonds[i].len());
+        if missing == 0 {
+            continue;
+        }
+        let length = options
+            .bond_length(elem, CAP)
+            .ok_or_else(|| format!("no bond length for {}-{}", elem, CAP))?;
+        if length.is_nan() || length <= 0.0 {
+            return Err(format!("{}-{} bond length must be positive", elem, CAP));
+        }
+
+        let units = bonds[i].iter().map(|(_, d)| d.normalize()).collect_vec();
+        let reference = match bonds[i].as_slice() {
+            [(j, delta)] => bonds[*j]
+                .iter()
+                .map(|(_, d)| *d)
+                .find(|d| (d + delta).norm() > 1e-6),
+            _ => None,
+        };
+        let directions = cap_directions(rule.hybridization, &units, reference);
+
+        let first = coords.ncols() + caps.len();
+        let added = directions.len().min(missing);
+        caps.extend(
+            directions
+                .into_iter()
+                .take(added)
+                .map(|d| coords.column(i) + length * d),
+        );
+        if added > 0 {
+            report
+                .passivated
+                .push((i, (first..first + added).collect()));
+        }
+        if added < missing {
+            report.unsaturated.push((i, missing - added));
+        }
+    }
+    Ok((caps, report))
+}
+
+/// `passivate` for a POSCAR, returning it with the caps appended. Sites without symbols are taken
+/// to be carbon.
+pub fn passivate_poscar(
+    poscar: Poscar,
+    options: &PassivationOptions,
+) -> Result<(Poscar, PassivationReport), String> {
+    macro_rules! elements_of {
+        ($it:expr) => {
+            $it.iter().flat_map(|c| c.iter().copied())
+        };
+    }
+
+    let lattice = Matrix3::from_iterator(elements_of!(poscar.scaled_lattice_vectors()));
+    let mut positions = poscar.scaled_cart_positions().iter().copied().collect_vec();
+    let coords = CoordMat::from_iterator(positions.len(), elements_of!(positions));
+    let mut symbols = match poscar.site_symbols() {
+        Some(symbols) => symbols.map(String::from).collect_vec(),
+        None => vec!["C".into(); positions.len()],
+    };
+
+    let elements = symbols.iter().map(String::as_str).collect_vec();
+    let (caps, report) = passivate(&lattice, &coords, &elements, options)?;
+    positions.extend(caps.iter().map(|h| [h[0], h[1], h[2]]));
+    symbols.extend(caps.iter().map(|_| CAP.into()));
+
+    let poscar = Builder::new()
+        .site_symbols(symbols)
+        .lattice_vectors(&poscar.scaled_lattice_vectors())
+        .positions(Coords::Cart(positions))
+        .build()
+        .unwrap();
+    Ok((poscar, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CC_BOND: f64 = 1.42045;
+    const CH_BOND: f64 = 1.09047;
+
+    /// Lattice of a cell big enough that periodic images don't bond.
+    fn box_lattice() -> Matrix3<f64> {
+        Matrix3::identity() * 30.0
+    }
+
+    fn to_coords(positions: &[Vector3<f64>]) -> CoordMat<f64> {
+        CoordMat::from_columns(positions)
+    }
+
+    /// Atoms of a chain in the xy plane, with bonds at the given angles (degrees) to the x axis.
+    fn chain(angles: &[f64]) -> Vec<Vector3<f64>> {
+        let mut positions = vec![Vector3::repeat(15.0)];
+        for angle in angles {
+            let (s, c) = angle.to_radians().sin_cos();
+            positions.push(positions.last().unwrap() + CC_BOND * Vector3::new(c, s, 0.0));
+        }
+        positions
+    }
+
+    /// Zigzag chain.
+    fn straight() -> Vec<Vector3<f64>> {
+        chain(&[30.0, -30.0, 30.0])
+    }
+
+    /// Chain with a kink like an armchair edge, where two consecutive bonds turn the same way.
+    fn kinked() -> Vec<Vector3<f64>> {
+        chain(&[30.0, -30.0, -90.0, -30.0, 30.0])
+    }
+
+    fn passivate_all(
+        positions: &[Vector3<f64>],
+        elements: &[&str],
+        options: &PassivationOptions,
+    ) -> (Vec<Vector3<f64>>, PassivationReport) {
+        passivate(&box_lattice(), &to_coords(positions), elements, options).unwrap()
+    }
+
+    /// Checks that the caps are at the right distance from their sites, and at least `min_angle`
+    /// degrees from every bond of the site (including the other caps).
+    fn check_caps(
+        positions: &[Vector3<f64>],
+        caps: &[Vector3<f64>],
+        report: &PassivationReport,
+        min_angle: f64,
+    ) {
+        let all = positions.iter().chain(caps).copied().collect_vec();
+        for (site, added) in &report.passivated {
+            let bonds = all
+                .iter()
+                .filter(|p| {
+                    let d = (*p - all[*site]).norm();
+                    d > 0.0 && d <= 1.6
+                })
+                .map(|p| (p - all[*site]).normalize())
+                .collect_vec();
+            for &cap in added {
+                let delta = all[cap] - all[*site];
+                assert!((delta.norm() - CH_BOND).abs() < 1e-9);
+                for bond in &bonds {
+                    let cos = bond.dot(&delta.normalize());
+                    if cos < 1.0 - 1e-9 {
+                        assert!(cos.acos().to_degrees() > min_angle - 1e-6);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bisector_matches_add_hydrogen() {
+        // inner atoms of a chain get one cap each on the outside of the zigzag, the ends get two
+        let positions = straight();
+        let (caps, report) = passivate_all(&positions, &["C"; 4], &Default::default());
+        assert_eq!(report.num_caps(), 6);
+        assert!(report.unsaturated.is_empty() && report.unknown.is_empty());
+        let sites = report
+            .passivated
+            .iter()
+            .map(|(s, c)| (*s, c.len()))
+            .collect_vec();
+        assert_eq!(sites, vec![(0, 2), (1, 1), (2, 1), (3, 2)]);
+        check_caps(&positions, &caps, &report, 120.0);
+
+        let (b1, b2) = (positions[0] - positions[1], positions[2] - positions[1]);
+        let expected = positions[1] - CH_BOND * (b1.normalize() + b2.normalize()).normalize();
+        assert!((caps[2] - expected).norm() < 1e-9);
+        // everything stays in the plane
+        assert!(caps.iter().all(|c| (c.z - 15.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn kinked_fragment_sp2() {
+        // the single-bonded end atoms need two caps, placed in the plane of their neighbor's bonds
+        let positions = kinked();
+        let (caps, report) = passivate_all(&positions, &["C"; 6], &Default::default());
+        assert_eq!(report.num_caps(), 8);
+        assert_eq!(report.passivated[0], (0, vec![6, 7]));
+        assert_eq!(report.passivated[5], (5, vec![12, 13]));
+        check_caps(&positions, &caps, &report, 120.0);
+        assert!(caps.iter().all(|c| (c.z - 15.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn kinked_fragment_sp3() {
+        let positions = kinked();
+        let mut options = PassivationOptions::default();
+        options.set_rule("C", 4, Hybridization::Sp3);
+        let (caps, report) = passivate_all(&positions, &["C"; 6], &options);
+        // methyl ends and methylene in between
+        let counts = report.passivated.iter().map(|(_, c)| c.len()).collect_vec();
+        assert_eq!(counts, vec![3, 2, 2, 2, 2, 3]);
+        // the 120 degree bonds of the chain aren't tetrahedral, but caps are at least tetrahedral
+        // from each other
+        for (_, added) in &report.passivated {
+            for (&a, &b) in added.iter().tuple_combinations() {
+                let (a, b) = (caps[a - 6], caps[b - 6]);
+                assert!((a - b).norm() > 1.7);
+            }
+        }
+        // methyl caps are tetrahedral about the bond
+        let bond = (positions[1] - positions[0]).normalize();
+        for cap in &caps[..3] {
+            let cos = bond.dot(&(cap - positions[0]).normalize());
+            assert!((cos.acos() - TETRAHEDRAL_ANGLE).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn per_element_rules() {
+        // an N at the end of the kinked chain with a lone pair only takes one cap, an unknown
+        // element at the other end is reported, and a lone carbon can't be oriented
+        let mut positions = kinked();
+        positions.push(Vector3::new(5.0, 5.0, 5.0));
+        positions.push(Vector3::new(25.0, 5.0, 5.0));
+        let elements = ["N", "C", "C", "C", "C", "Cl", "C", "H"];
+        let mut options = PassivationOptions::default();
+        options.set_rule("N", 2, Hybridization::Sp2);
+        let (caps, report) = passivate_all(&positions, &elements, &options);
+
+        assert_eq!(report.passivated[0].0, 0);
+        assert_eq!(report.passivated[0].1.len(), 1);
+        let nh = (caps[0] - positions[0]).norm();
+        assert!((nh - 1.01).abs() < 1e-9);
+        assert_eq!(report.unknown, vec![5]);
+        assert_eq!(report.unsaturated, vec![(6, 3)]);
+        // the carbon bonded to the Cl still counts it
+        assert_eq!(report.passivated.last().unwrap().1.len(), 1);
+
+        options.bond_lengths.clear();
+        let result = passivate(&box_lattice(), &to_coords(&positions), &elements, &options);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn bond_lengths_either_order() {
+        let mut options = PassivationOptions::default();
+        options.set_bond_length("H", "C", 1.1);
+        assert_eq!(options.bond_length("C", "H"), Some(1.1));
+        assert_eq!(options.bond_length("H", "C"), Some(1.1));
+        assert_eq!(options.bond_length("C", "F"), None);
+    }
+
+    #[test]
+    fn bad_cutoff_distance() {
+        let positions = straight();
+        let elements = ["C"; 4];
+        for &cutoff_distance in &[0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let options = PassivationOptions {
+                cutoff_distance,
+                ..Default::default()
+            };
+            let result = passivate(&box_lattice(), &to_coords(&positions), &elements, &options);
+            assert!(result.is_err());
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::agnr::AGNR;
+use itertools::Itertools;
+use pyo3::prelude::*;
+
+impl AGNR {
+    /// Names of the values returned by `descriptors`, in order.
+    pub const DESCRIPTOR_NAMES: [&'static str; 7] = [
+        "length",
+        "min_width",
+        "max_width",
+        "mean_width",
+        "width_std",
+        "kink_fraction",
+        "symmetric",
+    ];
+
+    /// Fixed-length descriptors computed directly from the spec (no structure needed), with
+    /// widths in the same units as the spec. `kink_fraction` is the fraction of edge steps that go
+    /// the same direction as the previous step (zero for a straight ribbon).
+    pub fn descriptors(&self) -> Vec<f64> {
+        let n = self.len();
+        let widths = self.spec.iter().map(|&(l, h)| (h - l) as f64).collect_vec();
+        let mean_width = widths.iter().sum::<f64>() / n as f64;
+        let width_var = widths.iter().map(|w| (w - mean_width).powi(2)).sum::<f64>() / n as f64;
+
+        let steps = |edge: fn(&(i32, i32)) -> i32| {
+            (0..n)
+                .map(|i| edge(&self.spec[(i + 1) % n]) - edge(&self.spec[i]))
+                .collect_vec()
+        };
+        let kinks: usize = [steps(|s| s.0), steps(|s| s.1)]
+            .iter()
+            .map(|steps| (0..n).filter(|&i| steps[i] == steps[(i + 1) % n]).count())
+            .sum();
+
+        vec![
+            (n / 2) as f64,
+            widths.iter().copied().fold(f64::INFINITY, f64::min),
+            widths.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mean_width,
+            width_var.sqrt(),
+            kinks as f64 / (2 * n) as f64,
+            self.minimum_image().1 as u8 as f64,
+        ]
+    }
+}
+
+#[pymethods]
+impl AGNR {
+    /// Spec-derived descriptors as (name, value) pairs, see `DESCRIPTOR_NAMES`.
+    #[name = "descriptors"]
+    fn py_descriptors(&self) -> Vec<(&'static str, f64)> {
+        Self::DESCRIPTOR_NAMES
+            .iter()
+            .copied()
+            .zip(self.descriptors())
+            .collect()
+    }
+}
@@ -1,50 +1,100 @@
 use crate::agnr::AGNR;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyKeyboardInterrupt, PyValueError};
 use pyo3::prelude::*;
 use std::collections::HashSet;
-use std::convert::TryInto;
+use std::convert::{Infallible, TryInto};
 
-fn recursive_gen(
-    current: &mut AGNR,
+/// How often (in nodes of the search tree) generation stops to check for interrupts and report
+/// progress.
+const POLL_INTERVAL: usize = 10_000;
+
+create_exception!(agnr_ml, GenerationInterrupted, PyKeyboardInterrupt);
+
+/// State shared by every level of `recursive_gen`.
+struct Search<P, F> {
     length: usize,
+    min_len: usize,
     symmetric_only: bool,
     min_width: usize,
     max_width: usize,
-    possible_agnrs: &mut HashSet<AGNR>,
-) {
-    if current.len() != length {
+    /// nodes of the search tree visited so far
+    nodes: usize,
+    /// AGNRs passed to `found` so far
+    found_count: usize,
+    poll: P,
+    found: F,
+}
+
+impl<P, F> Search<P, F> {
+    /// Whether an AGNR of the current length repeats a section with a length we're also
+    /// generating, in which case it is generated for that smaller length instead.
+    fn repeats_shorter(&self, agnr: &AGNR) -> bool {
+        let length = self.length / 2;
+        let period = agnr.period() / 2;
+        (self.min_len..length)
+            .any(|section_len| length % section_len == 0 && section_len % period == 0)
+    }
+}
+
+fn recursive_gen<E, P, F>(current: &mut AGNR, search: &mut Search<P, F>) -> Result<(), E>
+where
+    P: FnMut(usize, usize) -> Result<(), E>,
+    F: FnMut(AGNR) -> Result<(), E>,
+{
+    search.nodes += 1;
+    if search.nodes % POLL_INTERVAL == 0 {
+        (search.poll)(search.nodes, search.found_count)?;
+    }
+
+    if current.len() != search.length {
         for &next in &current.possible_extensions().unwrap() {
             let next_width = next.1 - next.0;
             // TODO: check if we can even reach the end, could save time
-            if next_width <= 2 * max_width as i32
-                && next_width >= 2 * min_width as i32
+            if next_width <= 2 * search.max_width as i32
+                && next_width >= 2 * search.min_width as i32
                 && next.0 >= 0
             {
-                // TODO: check python signals
                 current.spec.push(next);
-                recursive_gen(
-                    current,
-                    length,
-                    symmetric_only,
-                    min_width,
-                    max_width,
-                    possible_agnrs,
-                );
+                recursive_gen(current, search)?;
                 current.spec.pop();
             }
         }
     } else if current.is_periodic() {
-        // get the so-called "minimum" agnr so that we don't include duplicates
+        // the so-called "minimum" image of every AGNR is also generated (it starts at zero and
+        // has the same column widths), so only keeping that one skips duplicates
         let (minimum_image, has_symmetry) = current.minimum_image();
-        if !symmetric_only || has_symmetry {
-            possible_agnrs.insert(minimum_image);
+        if minimum_image == *current
+            && (!search.symmetric_only || has_symmetry)
+            && !search.repeats_shorter(current)
+        {
+            search.found_count += 1;
+            (search.found)(minimum_image)?;
         }
     }
+    Ok(())
 }
 
-#[pymethods]
 impl AGNR {
-    /// Generate all possible AGNRs by brute force.
-    #[staticmethod]
+    /// Check bounds for `generate_all_agnrs`: lengths (in hexagonal segments along the periodic
+    /// axis) and widths (in half column heights, see `is_valid`) must be positive, and minimums
+    /// no larger than maximums.
+    pub fn check_bounds(
+        min_len: usize,
+        max_len: usize,
+        min_width: usize,
+        max_width: usize,
+    ) -> Result<(), String> {
+        if min_len == 0 || min_width == 0 {
+            return Err("minimum length and width must be positive".into());
+        }
+        if min_len > max_len || min_width > max_width {
+            return Err("minimum length and width can't be larger than the maximums".into());
+        }
+        Ok(())
+    }
+
+    /// Generate all possible AGNRs by brute force, the bounds must pass `check_bounds`.
     pub fn generate_all_agnrs(
         min_len: usize,
         max_len: usize,
@@ -52,43 +102,146 @@ impl AGNR {
         max_width: usize,
         symmetric_only: bool,
     ) -> HashSet<AGNR> {
-        assert!(min_len > 0);
-        assert!(min_len <= max_len);
-
-        assert!(min_width >= 1);
-        assert!(min_width <= max_width);
+        let never_stop = |_, _| Ok::<_, Infallible>(());
+        match Self::generate_all_agnrs_with(
+            min_len,
+            max_len,
+            min_width,
+            max_width,
+            symmetric_only,
+            never_stop,
+        ) {
+            Ok(all_gnrs) => all_gnrs,
+            Err((never, _)) => match never {},
+        }
+    }
 
+    /// Same as `generate_all_agnrs`, but calls `poll` periodically (and once at the end) with the
+    /// number of search nodes visited and AGNRs found so far. If `poll` returns an error,
+    /// generation stops and the error is returned along with everything found until then.
+    pub fn generate_all_agnrs_with<E, P>(
+        min_len: usize,
+        max_len: usize,
+        min_width: usize,
+        max_width: usize,
+        symmetric_only: bool,
+        poll: P,
+    ) -> Result<HashSet<AGNR>, (E, HashSet<AGNR>)>
+    where
+        P: FnMut(usize, usize) -> Result<(), E>,
+    {
         let mut all_gnrs = HashSet::<AGNR>::default();
+        let result = Self::for_each_agnr_with(
+            min_len,
+            max_len,
+            min_width,
+            max_width,
+            symmetric_only,
+            poll,
+            |agnr| {
+                all_gnrs.insert(agnr);
+                Ok(())
+            },
+        );
+        match result {
+            Ok(()) => Ok(all_gnrs),
+            Err(e) => Err((e, all_gnrs)),
+        }
+    }
+
+    /// Same as `generate_all_agnrs_with`, but passes each AGNR to `found` as soon as it is
+    /// generated instead of collecting them, so memory use doesn't grow with the number of AGNRs.
+    /// Every AGNR is passed exactly once, and an error from either callback stops generation.
+    pub fn for_each_agnr_with<E, P, F>(
+        min_len: usize,
+        max_len: usize,
+        min_width: usize,
+        max_width: usize,
+        symmetric_only: bool,
+        poll: P,
+        found: F,
+    ) -> Result<(), E>
+    where
+        P: FnMut(usize, usize) -> Result<(), E>,
+        F: FnMut(AGNR) -> Result<(), E>,
+    {
+        if let Err(e) = Self::check_bounds(min_len, max_len, min_width, max_width) {
+            panic!("{}", e);
+        }
+
+        let mut search = Search {
+            length: 0,
+            min_len,
+            symmetric_only,
+            min_width,
+            max_width,
+            nodes: 0,
+            found_count: 0,
+            poll,
+            found,
+        };
+
         for length in min_len..=max_len {
-            let mut all_gnrs_with_len = HashSet::default();
+            search.length = length * 2;
             for width in min_width..=max_width {
                 let width: i32 = width.try_into().unwrap();
                 let initial = &mut AGNR::new(vec![(0, 2 * width)]);
-                recursive_gen(
-                    initial,
-                    length * 2,
-                    symmetric_only,
-                    min_width,
-                    max_width,
-                    &mut all_gnrs_with_len,
-                );
+                recursive_gen(initial, &mut search)?;
             }
+        }
 
-            for section_len in min_len..length {
-                if length % section_len == 0 {
-                    // only keep GNRs which don't repeat, since they will be
-                    // generated for smaller lengths
-                    all_gnrs_with_len.retain(|gnr| {
-                        let beginning = &gnr.spec[0..(section_len * 2)];
-                        let symm = beginning.repeat(length / section_len);
-                        gnr.spec != symm
-                    });
-                }
-            }
+        (search.poll)(search.nodes, search.found_count)
+    }
+}
 
-            all_gnrs.extend(all_gnrs_with_len.into_iter());
-        }
+#[pymethods]
+impl AGNR {
+    /// Generate all possible AGNRs by brute force, without holding the GIL. If given, `progress`
+    /// is called periodically (and once at the end) with the number of search nodes visited and
+    /// AGNRs found so far. Raises `GenerationInterrupted` (a `KeyboardInterrupt`) on Ctrl-C or if
+    /// `progress` raises `KeyboardInterrupt`, with the AGNRs found so far as its second argument.
+    #[staticmethod]
+    #[name = "generate_all_agnrs"]
+    pub fn py_generate_all_agnrs(
+        py: Python,
+        min_len: usize,
+        max_len: usize,
+        min_width: usize,
+        max_width: usize,
+        symmetric_only: bool,
+        progress: Option<PyObject>,
+    ) -> PyResult<HashSet<AGNR>> {
+        Self::check_bounds(min_len, max_len, min_width, max_width)
+            .map_err(PyValueError::new_err)?;
+        let result = py.allow_threads(|| {
+            Self::generate_all_agnrs_with(
+                min_len,
+                max_len,
+                min_width,
+                max_width,
+                symmetric_only,
+                |nodes, found| {
+                    Python::with_gil(|py| -> PyResult<()> {
+                        py.check_signals()?;
+                        if let Some(progress) = &progress {
+                            progress.call1(py, (nodes, found))?;
+                        }
+                        Ok(())
+                    })
+                },
+            )
+        });
 
-        all_gnrs
+        match result {
+            Ok(all_gnrs) => Ok(all_gnrs),
+            Err((e, partial)) if e.is_instance::<PyKeyboardInterrupt>(py) => {
+                let partial = partial.into_iter().collect::<Vec<_>>();
+                Err(GenerationInterrupted::new_err((
+                    "AGNR generation interrupted",
+                    partial,
+                )))
+            }
+            Err((e, _)) => Err(e),
+        }
     }
 }
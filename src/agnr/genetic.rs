@@ -0,0 +1,322 @@
+use crate::agnr::AGNR;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Bounds on the AGNRs considered by a search, in the same units as `generate_all_agnrs`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SearchSpace {
+    pub min_len: usize,
+    pub max_len: usize,
+    pub min_width: usize,
+    pub max_width: usize,
+}
+
+impl SearchSpace {
+    /// Whether the AGNR, or one of its supercells, is within the bounds. Supercells count since
+    /// AGNRs are compared by their canonical (primitive) form.
+    pub fn contains(&self, agnr: &AGNR) -> bool {
+        let n = agnr.len();
+        let shortest = (2 * self.min_len + n - 1) / n * n;
+        shortest <= 2 * self.max_len && agnr.is_valid(self.min_width, self.max_width)
+    }
+
+    /// Build a random (canonical) AGNR within the search space, if one can be found.
+    pub fn random<R: Rng>(&self, rng: &mut R) -> Option<AGNR> {
+        const MAX_ATTEMPTS: usize = 1000;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let n = 2 * rng.gen_range(self.min_len, self.max_len + 1);
+            let width = rng.gen_range(self.min_width, self.max_width + 1) as i32;
+
+            // an equal number of up and down steps on each edge guarantees periodicity
+            let mut random_steps = || {
+                let mut steps = [1, -1].repeat(n / 2);
+                steps.shuffle(rng);
+                steps
+            };
+            let (low_steps, high_steps) = (random_steps(), random_steps());
+
+            let mut spec = vec![(0, 2 * width)];
+            for (dl, dh) in low_steps.into_iter().zip(high_steps).take(n - 1) {
+                let &(l, h) = spec.last().unwrap();
+                spec.push((l + dl, h + dh));
+            }
+
+            let agnr = AGNR::new(spec);
+            if self.contains(&agnr) {
+                return Some(agnr.canonical());
+            }
+        }
+        None
+    }
+}
+
+/// Elementary changes to an AGNR spec that preserve periodicity.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mutation {
+    /// Flip a single edge step, turning a peak on one edge into a valley (or vice versa).
+    Flip,
+    /// Widen or narrow a run of columns by moving one of their edges.
+    Resize,
+    /// Cyclically shift the spec (a translation along the periodic axis).
+    Shift,
+}
+
+impl Mutation {
+    pub const ALL: [Mutation; 3] = [Mutation::Flip, Mutation::Resize, Mutation::Shift];
+}
+
+fn edge_mut(column: &mut (i32, i32), edge: usize) -> &mut i32 {
+    match edge {
+        0 => &mut column.0,
+        _ => &mut column.1,
+    }
+}
+
+impl AGNR {
+    /// All specs that result from moving one edge of the cyclic run of columns `start..start + len`
+    /// by two (in either direction) while keeping every column connected to its neighbors.
+    pub(crate) fn resized_runs(&self, max_run_len: usize) -> Vec<Self> {
+        let n = self.len();
+        let edge = |i: usize, e: usize| match e {
+            0 => self.spec[i % n].0,
+            _ => self.spec[i % n].1,
+        };
+
+        let mut ret = Vec::new();
+        for start in 0..n {
+            for run_len in 1..usize::min(n, max_run_len + 1) {
+                let end = start + run_len;
+                for e in 0..2 {
+                    for &delta in &[-2, 2] {
+                        // edge steps into and out of the run must both flip direction
+                        let before = edge(start + n - 1, e) - edge(start, e);
+                        let after = edge(end, e) - edge(end - 1, e);
+                        if before != delta / 2 || after != delta / 2 {
+                            continue;
+                        }
+
+                        let mut spec = self.spec.clone();
+                        for i in start..end {
+                            *edge_mut(&mut spec[i % n], e) += delta;
+                        }
+                        ret.push(Self::new(spec));
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    /// Apply a random mutation of the given type, returns `None` if there's no way to apply it.
+    /// Note: the result is always periodic, but may not be within any particular width bounds.
+    pub fn mutated<R: Rng>(&self, mutation: Mutation, rng: &mut R) -> Option<Self> {
+        match mutation {
+            Mutation::Flip => self.resized_runs(1).choose(rng).cloned(),
+            Mutation::Resize => self.resized_runs(self.len()).choose(rng).cloned(),
+            Mutation::Shift if self.len() > 1 => {
+                let mut spec = self.spec.clone();
+                spec.rotate_left(rng.gen_range(1, self.len()));
+                Some(Self::new(spec))
+            }
+            Mutation::Shift => None,
+        }
+    }
+
+    /// Combine the beginning of this spec with the end of another, shifting the other vertically
+    /// so that the two halves join up. Returns `None` if there's no way to join them.
+    pub fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Option<Self> {
+        let (a, b) = (&self.spec, &other.spec);
+        let join = |i: usize, j: usize, shift: i32| {
+            let spec = a[..i]
+                .iter()
+                .copied()
+                .chain(b[j..].iter().map(|&(l, h)| (l + shift, h + shift)))
+                .collect();
+            Self::new(spec)
+        };
+
+        let mut candidates = vec![];
+        for i in 1..a.len() {
+            let next = Self::new(a[..i].to_vec()).possible_extensions().unwrap();
+            for (j, &(l, h)) in b.iter().enumerate() {
+                // shift b[j] onto a column that can follow a[i - 1], the end of b then has to
+                // connect back to the start of a
+                for &(next_l, next_h) in &next {
+                    if next_h - next_l == h - l && join(i, j, next_l - l).is_periodic() {
+                        candidates.push((i, j, next_l - l));
+                    }
+                }
+            }
+        }
+
+        let &(i, j, shift) = candidates.choose(rng)?;
+        Some(join(i, j, shift).normalized())
+    }
+}
+
+/// Order by descending fitness, ties are broken by spec so that results are reproducible.
+fn best_first(a: &(AGNR, f64), b: &(AGNR, f64)) -> Ordering {
+    b.1.partial_cmp(&a.1)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| a.0.cmp(&b.0))
+}
+
+/// Simple generational genetic algorithm maximizing `fitness` over the AGNRs in `space`.
+/// Every AGNR is deduplicated by its canonical (primitive) form, so fitness is evaluated at most
+/// once per unique AGNR. Returns every AGNR evaluated along with its fitness, best first. The
+/// mutation and crossover rates are probabilities (per child), and must be between 0 and 1.
+#[allow(clippy::too_many_arguments)]
+pub fn genetic_search<F, R>(
+    mut fitness: F,
+    space: SearchSpace,
+    population_size: usize,
+    generations: usize,
+    mutation_rate: f64,
+    crossover_rate: f64,
+    initial: Vec<AGNR>,
+    rng: &mut R,
+) -> PyResult<Vec<(AGNR, f64)>>
+where
+    F: FnMut(&AGNR) -> PyResult<f64>,
+    R: Rng,
+{
+    const MAX_ATTEMPTS_PER_MEMBER: usize = 100;
+
+    if !(0.0..=1.0).contains(&mutation_rate) || !(0.0..=1.0).contains(&crossover_rate) {
+        return Err(PyValueError::new_err(
+            "mutation and crossover rates must be between 0 and 1",
+        ));
+    }
+
+    let mut evaluated = HashMap::<AGNR, f64>::new();
+    let mut evaluate = |agnr: AGNR| -> PyResult<(AGNR, f64)> {
+        let value = match evaluated.get(&agnr) {
+            Some(&value) => value,
+            None => {
+                let value = fitness(&agnr)?;
+                evaluated.insert(agnr.clone(), value);
+                value
+            }
+        };
+        Ok((agnr, value))
+    };
+
+    // initial population, filled up with random AGNRs if needed
+    let mut seen = HashSet::new();
+    let mut population = Vec::with_capacity(population_size);
+    let initial = initial.into_iter().map(|a| a.canonical());
+    let random = (0..population_size * MAX_ATTEMPTS_PER_MEMBER).filter_map(|_| space.random(rng));
+    for agnr in initial.chain(random) {
+        if population.len() >= population_size {
+            break;
+        }
+        if space.contains(&agnr) && seen.insert(agnr.clone()) {
+            population.push(evaluate(agnr)?);
+        }
+    }
+
+    let n_elite = usize::max(1, population_size / 10);
+    for _ in 0..generations {
+        population.sort_by(best_first);
+
+        // keep the best members of the last generation as-is
+        let mut next: Vec<_> = population.iter().take(n_elite).cloned().collect();
+        let mut next_seen: HashSet<_> = next.iter().map(|(a, _)| a.clone()).collect();
+
+        for _ in 0..population_size * MAX_ATTEMPTS_PER_MEMBER {
+            if next.len() >= population_size || population.is_empty() {
+                break;
+            }
+
+            // binary tournament selection
+            let select = |rng: &mut R| {
+                let a = &population[rng.gen_range(0, population.len())];
+                let b = &population[rng.gen_range(0, population.len())];
+                if a.1 >= b.1 {
+                    a.0.clone()
+                } else {
+                    b.0.clone()
+                }
+            };
+
+            let mut child = select(rng);
+            if rng.gen_bool(crossover_rate) {
+                let other = select(rng);
+                if let Some(c) = child.crossover(&other, rng) {
+                    child = c;
+                }
+            }
+            if rng.gen_bool(mutation_rate) {
+                let mutation = *Mutation::ALL.choose(rng).unwrap();
+                if let Some(c) = child.mutated(mutation, rng) {
+                    child = c;
+                }
+            }
+
+            let child = child.canonical();
+            if space.contains(&child) && next_seen.insert(child.clone()) {
+                next.push(evaluate(child)?);
+            }
+        }
+
+        population = next;
+    }
+
+    let mut all: Vec<_> = evaluated.into_iter().collect();
+    all.sort_by(best_first);
+    Ok(all)
+}
+
+#[pymethods]
+impl AGNR {
+    /// Search for AGNRs maximizing the given fitness function (called with a single AGNR) using a
+    /// genetic algorithm. Returns all evaluated AGNRs along with their fitness, best first.
+    #[staticmethod]
+    #[allow(clippy::too_many_arguments)]
+    pub fn genetic_search(
+        py: Python,
+        fitness: PyObject,
+        min_len: usize,
+        max_len: usize,
+        min_width: usize,
+        max_width: usize,
+        population_size: Option<usize>,
+        generations: Option<usize>,
+        mutation_rate: Option<f64>,
+        crossover_rate: Option<f64>,
+        initial: Option<Vec<AGNR>>,
+        seed: Option<u64>,
+    ) -> PyResult<Vec<(AGNR, f64)>> {
+        AGNR::check_bounds(min_len, max_len, min_width, max_width)
+            .map_err(PyValueError::new_err)?;
+        let mutation_rate = mutation_rate.unwrap_or(0.5);
+        let crossover_rate = crossover_rate.unwrap_or(0.5);
+
+        let space = SearchSpace {
+            min_len,
+            max_len,
+            min_width,
+            max_width,
+        };
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        genetic_search(
+            |agnr| fitness.call1(py, (agnr.clone(),))?.extract(py),
+            space,
+            population_size.unwrap_or(50),
+            generations.unwrap_or(20),
+            mutation_rate,
+            crossover_rate,
+            initial.unwrap_or_default(),
+            &mut rng,
+        )
+    }
+}
@@ -1,11 +1,38 @@
-use crate::adjacency::add_hydrogen;
-use crate::structure::AtomicStructure;
+use crate::adjacency::{add_hydrogen, PassivationReport};
+use crate::structure::{AtomicStructure, DefectKind, SvgOptions};
 use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use std::convert::TryInto;
 use vasp_poscar::Poscar;
 
+mod descriptors;
 mod generation;
+mod genetic;
+mod monomer;
+mod neighbors;
+mod recognition;
+
+pub use generation::GenerationInterrupted;
+pub use monomer::Monomer;
+
+/// All columns that can follow the given one in a spec.
+pub(crate) fn extensions((l, h): (i32, i32)) -> [(i32, i32); 4] {
+    [
+        // grow by 1 (y must shift down)
+        (l - 1, h + 1),
+        // shrink by 1 (y must shift up)
+        (l + 1, h - 1),
+        // stay same width (can do either)
+        (l + 1, h + 1),
+        (l - 1, h - 1),
+    ]
+}
+
+/// Whether column `b` can follow column `a` in a spec.
+pub(crate) fn connects(a: (i32, i32), b: (i32, i32)) -> bool {
+    i32::abs(a.0 - b.0) == 1 && i32::abs(a.1 - b.1) == 1
+}
 
 #[pyclass(module = "agnr_ml")]
 #[derive(Default, Debug, Clone, PartialOrd, PartialEq, Eq, Ord, Hash)]
@@ -28,14 +55,94 @@ impl AGNR {
         self.spec.clone()
     }
 
-    /// Build an AtomicStructure from an AGNR
+    /// Build an AtomicStructure from an AGNR, warning if any carbons couldn't be capped.
     pub fn to_structure(
         &self,
+        py: Python,
         cc_bond: Option<f64>,
         ch_bond: Option<f64>,
         vacuum_sep: Option<f64>,
-    ) -> AtomicStructure {
-        self.to_poscar(cc_bond, ch_bond, vacuum_sep).into()
+    ) -> PyResult<AtomicStructure> {
+        let (structure, report) = self.to_structure_with_report(cc_bond, ch_bond, vacuum_sep)?;
+        if let Err(e) = report.check_saturated() {
+            let category = py.import("builtins")?.getattr("RuntimeWarning")?;
+            PyErr::warn(py, category, &e, 1)?;
+        }
+        Ok(structure)
+    }
+
+    /// Build an AtomicStructure from an AGNR, along with the report of its hydrogen
+    /// passivation.
+    pub fn to_structure_with_report(
+        &self,
+        cc_bond: Option<f64>,
+        ch_bond: Option<f64>,
+        vacuum_sep: Option<f64>,
+    ) -> PyResult<(AtomicStructure, PassivationReport)> {
+        let (poscar, report) = self
+            .to_poscar(cc_bond, ch_bond, vacuum_sep)
+            .map_err(PyValueError::new_err)?;
+        Ok((poscar.into(), report))
+    }
+
+    /// Render the AGNR as an SVG image, with the given number of periodic repeats (default 3).
+    pub fn to_svg(&self, repeats: Option<usize>, scale: Option<f64>) -> PyResult<String> {
+        let default = SvgOptions::default();
+        let structure = self.default_structure().map_err(PyValueError::new_err)?;
+        Ok(structure.to_svg(&SvgOptions {
+            repeats: repeats.unwrap_or(3),
+            scale: scale.unwrap_or(default.scale),
+            ..default
+        }))
+    }
+
+    fn _repr_svg_(&self) -> PyResult<String> {
+        self.to_svg(None, None)
+    }
+
+    /// Get the minimal repeating unit of this AGNR, as its canonical (minimum) image. This is
+    /// the same as `canonical`.
+    pub fn primitive(&self) -> Self {
+        self.canonical()
+    }
+
+    /// Whether this AGNR is its own minimal repeating unit (i.e. not a supercell).
+    pub fn is_primitive(&self) -> bool {
+        self.period() == self.spec.len()
+    }
+
+    /// Short name of the AGNR, or `None` if it's too wide to have one or its spec isn't
+    /// normalized.
+    #[name = "name"]
+    fn py_name(&self) -> Option<String> {
+        self.name()
+    }
+
+    /// All symmetry-distinct single defects of the given kind (see
+    /// `AtomicStructure.with_defect`) in the structure of this AGNR repeated `repeats` times
+    /// (default 1) along x, as (structure, sites, multiplicity).
+    pub fn distinct_defects(
+        &self,
+        kind: &str,
+        repeats: Option<usize>,
+        element: Option<&str>,
+        height: Option<f64>,
+    ) -> PyResult<Vec<(AtomicStructure, Vec<usize>, usize)>> {
+        let kind = DefectKind::from_py(kind, element, height)?;
+        let structure = self
+            .default_structure()
+            .map_err(PyValueError::new_err)?
+            .repeated_x(repeats.unwrap_or(1));
+        structure
+            .distinct_defects(&kind)
+            .into_iter()
+            .map(|(defect, multiplicity)| {
+                let defected = structure
+                    .with_defect(&defect)
+                    .map_err(PyValueError::new_err)?;
+                Ok((defected, defect.sites(), multiplicity))
+            })
+            .collect()
     }
 }
 
@@ -44,46 +151,62 @@ impl AGNR {
         self.spec.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.spec.is_empty()
+    }
+
     pub fn width(&self) -> Option<i32> {
         self.spec.iter().map(|s| s.1).max()
     }
 
     pub fn possible_extensions(&self) -> Option<[(i32, i32); 4]> {
-        self.spec.last().map(|&(l, h)| {
-            [
-                // grow by 1 (y must shift down)
-                (l - 1, h + 1),
-                // shrink by 1 (y must shift up)
-                (l + 1, h - 1),
-                // stay same width (can do either)
-                (l + 1, h + 1),
-                (l - 1, h - 1),
-            ]
-        })
+        self.spec.last().map(|&column| extensions(column))
     }
 
+    /// Short name of the spec, or `None` if it doesn't start at zero like generated specs (see
+    /// `normalized`) or is too wide to have one.
     pub fn name(&self) -> Option<String> {
-        const CHAR_MAP: &'static [u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        const CHAR_MAP: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        let to_char = |x: i32| -> Option<u8> {
+            let x: usize = x.try_into().ok()?;
+            CHAR_MAP.get(x).copied()
+        };
         let mut name = Vec::new();
         for &(low, high) in &self.spec {
-            let low: usize = low.try_into().ok()?;
-            let high: usize = high.try_into().ok().filter(|&h| h < CHAR_MAP.len())?;
-
             // dont output first number since it's always 0 according to our convention
             if !name.is_empty() {
-                name.push(CHAR_MAP[low]);
-            } else {
-                assert_eq!(low, 0, "spec should always start with 0");
+                name.push(to_char(low)?);
+            } else if low != 0 {
+                return None;
             }
 
-            name.push(CHAR_MAP[high]);
+            name.push(to_char(high)?);
         }
 
         Some(String::from_utf8(name).unwrap())
     }
 
+    /// Inverse of `name()`, returns `None` if the name has invalid characters or an odd number of
+    /// them (names always have one character for the first column and two for each after that).
+    pub fn from_name(name: &str) -> Option<Self> {
+        let values = name
+            .chars()
+            .map(|c| c.to_digit(36).map(|v| v as i32))
+            .collect::<Option<Vec<_>>>()?;
+
+        if values.is_empty() || values.len() % 2 != 1 {
+            return None;
+        }
+
+        let spec = std::iter::once((0, values[0]))
+            .chain(values[1..].chunks(2).map(|c| (c[0], c[1])))
+            .collect();
+
+        Some(Self::new(spec))
+    }
+
     pub fn is_periodic(&self) -> bool {
-        if self.spec.len() == 0 {
+        if self.spec.is_empty() {
             return true;
         }
 
@@ -96,6 +219,57 @@ impl AGNR {
         false
     }
 
+    /// Whether every column connects to the next one (including across the periodic boundary)
+    /// and all column widths are within the given bounds (same units as `generate_all_agnrs`).
+    pub fn is_valid(&self, min_width: usize, max_width: usize) -> bool {
+        self.is_valid_unbounded(min_width)
+            && self.spec.iter().all(|s| s.1 - s.0 <= 2 * max_width as i32)
+    }
+
+    /// Same as `is_valid`, but without a maximum width.
+    pub fn is_valid_unbounded(&self, min_width: usize) -> bool {
+        !self.spec.is_empty()
+            && self.spec.iter().all(|s| s.1 - s.0 >= 2 * min_width as i32)
+            && self
+                .spec
+                .iter()
+                .tuple_windows()
+                .all(|(&a, &b)| connects(a, b))
+            && connects(*self.spec.last().unwrap(), self.spec[0])
+    }
+
+    /// Shift the spec vertically so that its lowest edge is at zero, which is the convention
+    /// used by generation (and required by `name()`).
+    pub fn normalized(&self) -> Self {
+        let min_low = self.spec.iter().map(|s| s.0).min().unwrap_or(0);
+        Self::new(
+            self.spec
+                .iter()
+                .map(|&(l, h)| (l - min_low, h - min_low))
+                .collect(),
+        )
+    }
+
+    /// The unique representative of this AGNR, equal for any two specs that describe the same
+    /// structure, including supercells of each other: the minimum image of the minimal repeating
+    /// unit.
+    pub fn canonical(&self) -> Self {
+        Self::new(self.spec[..self.period()].to_vec())
+            .normalized()
+            .minimum_image()
+            .0
+    }
+
+    /// Length of the shortest section of the spec that, repeated, gives the full spec.
+    pub fn period(&self) -> usize {
+        let n = self.spec.len();
+        // note: sections must have an even length to be periodic themselves
+        (1..=n)
+            .filter(|&p| p % 2 == 0 && n % p == 0)
+            .find(|&p| self.spec[..p].repeat(n / p) == self.spec)
+            .unwrap_or(n)
+    }
+
     /// Get the "minimum" spec after applying all possible transformations.
     /// Returns the minimum and whether or not the structure has any symmetries.
     pub fn minimum_image(&self) -> (Self, bool) {
@@ -135,19 +309,36 @@ impl AGNR {
         (Self::new(minimum_image), has_symmetry)
     }
 
-    /// Build a Poscar from an AGNR
+    /// Build the structure of an AGNR with the default bond lengths and vacuum, which always
+    /// passivates cleanly. Fails only for specs with empty columns.
+    pub fn default_structure(&self) -> Result<AtomicStructure, String> {
+        let (poscar, _) = self.to_poscar(None, None, None)?;
+        Ok(poscar.into())
+    }
+
+    /// Build a Poscar from an AGNR, along with the report of its hydrogen passivation. Fails
+    /// for non-positive bond lengths or vacuum, and for specs with empty (or inverted) columns.
     pub fn to_poscar(
         &self,
         cc_bond: Option<f64>,
         ch_bond: Option<f64>,
         vacuum_sep: Option<f64>,
-    ) -> Poscar {
+    ) -> Result<(Poscar, PassivationReport), String> {
         use std::iter::once;
         use vasp_poscar::{Builder, Coords};
 
         let cc_bond = cc_bond.unwrap_or(1.42045);
         let ch_bond = ch_bond.unwrap_or(1.09047);
         let vacuum_sep = vacuum_sep.unwrap_or(15.0);
+        if cc_bond.is_nan() || cc_bond <= 0.0 {
+            return Err("C-C bond length must be positive".to_string());
+        }
+        if vacuum_sep.is_nan() || vacuum_sep <= 0.0 {
+            return Err("vacuum separation must be positive".to_string());
+        }
+        if let Some((low, high)) = self.spec.iter().find(|(low, high)| low >= high) {
+            return Err(format!("spec column ({}, {}) has no atoms", low, high));
+        }
 
         // horizontal distance between each hexagonal "segment" of the GNR
         let dx = 1.5 * cc_bond;
@@ -187,7 +378,7 @@ impl AGNR {
             ])
             .positions(Coords::Cart(coords))
             .build()
-            .unwrap();
+            .map_err(|e| e.to_string())?;
 
         // add hydrogen and that's it
         let cutoff_distance = cc_bond * 1.1;
@@ -0,0 +1,239 @@
+use crate::adjacency::add_hydrogen;
+use crate::agnr::AGNR;
+use crate::structure::{Atom, AtomicStructure};
+use itertools::Itertools;
+use nalgebra::{Rotation3, Vector3};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::cmp::Ordering;
+use std::f64::consts::FRAC_PI_2;
+use vasp_poscar::{Builder, Coords};
+
+const CC_BOND: f64 = 1.42045;
+const CH_BOND: f64 = 1.09047;
+/// Vacuum around the molecule in each direction of its (non-periodic) cell.
+const VACUUM_SEP: f64 = 15.0;
+
+/// Aryl carbon-halogen bond lengths for the halogens used in Ullmann coupling.
+fn halogen_bond(halogen: &str) -> Option<f64> {
+    match halogen {
+        "Cl" => Some(1.74),
+        "Br" => Some(1.90),
+        "I" => Some(2.10),
+        _ => None,
+    }
+}
+
+/// Dihalogenated precursor of an AGNR for the usual bottom-up route: Ullmann coupling of the
+/// monomers at the halogen sites into a polymer, then cyclodehydrogenation into the ribbon.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monomer {
+    /// Hydrogen-capped molecule in a cell with vacuum on all sides. Consecutive aromatic units
+    /// are twisted by 90 degrees about the single bond joining them, as in 10,10'-dibromo-9,9'-
+    /// bianthryl.
+    #[pyo3(get)]
+    pub structure: AtomicStructure,
+    /// Indices of the two halogen atoms, which couple to the previous and next monomer.
+    #[pyo3(get)]
+    pub halogen_sites: Vec<usize>,
+    /// Number of aromatic units joined by single bonds.
+    #[pyo3(get)]
+    pub num_units: usize,
+}
+
+/// A carbon of a monomer, see `AGNR::monomer`.
+struct Carbon {
+    xyz: Vector3<f64>,
+    unit: usize,
+}
+
+impl AGNR {
+    /// Columns of the primitive period where the ribbon can be split into aromatic units joined
+    /// by single bonds. These are the columns one pair narrower than both of their neighbors at
+    /// either edge, so that splitting every pair in the column leaves no carbon outside of a
+    /// ring.
+    pub fn link_columns(&self) -> Vec<usize> {
+        let spec = &self.spec[..self.period()];
+        let n = spec.len();
+        (0..n)
+            .filter(|&i| {
+                let (l, h) = spec[i];
+                spec[(i + n - 1) % n] == (l - 1, h + 1) && spec[(i + 1) % n] == (l - 1, h + 1)
+            })
+            .collect()
+    }
+
+    /// The monomer that gives this AGNR through Ullmann coupling and cyclodehydrogenation, made of
+    /// `units` aromatic units (a multiple of the number of link columns in the primitive period,
+    /// by default the smallest such multiple of at least two, like 10,10'-dibromo-9,9'-bianthryl
+    /// for 7-AGNR). Units are split at the link columns (see `link_columns`), each joined to the
+    /// next by the middle pair of carbons of the link column, while cyclodehydrogenation forms
+    /// the other bonds across it. Returns `None` if the AGNR has no link columns.
+    pub fn monomer(&self, halogen: &str, units: Option<usize>) -> Result<Option<Monomer>, String> {
+        let halogen_bond =
+            halogen_bond(halogen).ok_or_else(|| format!("unsupported halogen '{}'", halogen))?;
+        if !self.is_periodic() {
+            return Err("AGNR must be periodic".into());
+        }
+
+        let spec = &self.spec[..self.period()];
+        let n = spec.len();
+        let links = self.link_columns();
+        if links.is_empty() {
+            return Ok(None);
+        }
+        let default_units = if links.len() == 1 { 2 } else { links.len() };
+        let units = units.unwrap_or(default_units);
+        if units == 0 || units % links.len() != 0 {
+            let msg = format!(
+                "number of units must be a positive multiple of {}",
+                links.len()
+            );
+            return Err(msg);
+        }
+
+        let dx = 1.5 * CC_BOND;
+        let dy = f64::sqrt(3.0) * CC_BOND / 2.0;
+        let middle = |(l, h): (i32, i32)| l + 2 * (((h - l) / 2 - 1) / 2);
+
+        // columns from one link column to the same one `units` units later, where the first only
+        // has the right atom of each pair and the last only the left one
+        let start = links[0];
+        let end = start + n * units / links.len();
+        let mut carbons = vec![];
+        let mut bonds = vec![];
+        let mut ends = vec![];
+        let mut unit = 0;
+        for i in start..=end {
+            let column = spec[i % n];
+            let is_link = links.contains(&(i % n));
+            let x = (i - start) as f64 * dx;
+            for j in (column.0..column.1).step_by(2) {
+                let y = j as f64 * dy;
+                let left = Vector3::new(x, y, 0.0);
+                let right = Vector3::new(x + CC_BOND, y, 0.0);
+                match (i == start, i == end, is_link) {
+                    (true, _, _) => {
+                        if j == middle(column) {
+                            ends.push(carbons.len());
+                        }
+                        carbons.push(Carbon { xyz: right, unit });
+                    }
+                    (_, true, _) => {
+                        if j == middle(column) {
+                            ends.push(carbons.len());
+                        }
+                        carbons.push(Carbon { xyz: left, unit });
+                    }
+                    (_, _, true) => {
+                        if j == middle(column) {
+                            bonds.push(carbons.len());
+                        }
+                        carbons.push(Carbon { xyz: left, unit });
+                        carbons.push(Carbon {
+                            xyz: right,
+                            unit: unit + 1,
+                        });
+                    }
+                    _ => {
+                        carbons.push(Carbon { xyz: left, unit });
+                        carbons.push(Carbon { xyz: right, unit });
+                    }
+                }
+            }
+            if is_link && i != start && i != end {
+                unit += 1;
+            }
+        }
+
+        // twist each unit about the single bond to the previous one, alternating directions so
+        // that every other unit is parallel to the first
+        for (t, &bond) in bonds.iter().enumerate() {
+            let axis = carbons[bond].xyz;
+            let angle = if t % 2 == 0 { FRAC_PI_2 } else { -FRAC_PI_2 };
+            let rotation = Rotation3::from_axis_angle(&Vector3::x_axis(), angle);
+            for carbon in carbons.iter_mut().filter(|c| c.unit > t) {
+                carbon.xyz = axis + rotation * (carbon.xyz - axis);
+            }
+        }
+
+        // center in a box with vacuum on all sides
+        let mut min = Vector3::repeat(f64::INFINITY);
+        let mut max = Vector3::repeat(f64::NEG_INFINITY);
+        for carbon in &carbons {
+            min = min.inf(&carbon.xyz);
+            max = max.sup(&carbon.xyz);
+        }
+        let mut lattice = [[0.0; 3]; 3];
+        for k in 0..3 {
+            lattice[k][k] = max[k] - min[k] + VACUUM_SEP;
+        }
+        let shift = Vector3::repeat(VACUUM_SEP / 2.0) - min;
+        let poscar = Builder::new()
+            .group_counts(vec![carbons.len()])
+            .group_symbols(vec!["C"])
+            .lattice_vectors(&lattice)
+            .positions(Coords::Cart(
+                carbons
+                    .iter()
+                    .map(|c| {
+                        let xyz = c.xyz + shift;
+                        [xyz[0], xyz[1], xyz[2]]
+                    })
+                    .collect_vec(),
+            ))
+            .build()
+            .unwrap();
+        let (poscar, report) = add_hydrogen(poscar, CH_BOND, 1.1 * CC_BOND)?;
+        report.check_saturated()?;
+        let mut structure: AtomicStructure = poscar.into();
+
+        // replace the hydrogen on each end with the halogen
+        let position = |i: usize| Vector3::from(structure.atoms[i].xyz);
+        let mut replaced = vec![];
+        let mut halogens = vec![];
+        for &carbon in &ends {
+            let hydrogen = (0..structure.atoms.len())
+                .filter(|&i| structure.atoms[i].elem == "H")
+                .min_by(|&a, &b| {
+                    let da = (position(a) - position(carbon)).norm();
+                    let db = (position(b) - position(carbon)).norm();
+                    da.partial_cmp(&db).unwrap_or(Ordering::Equal)
+                })
+                .expect("end carbons have a hydrogen");
+            let direction = (position(hydrogen) - position(carbon)).normalize();
+            let xyz = position(carbon) + halogen_bond * direction;
+            replaced.push(hydrogen);
+            halogens.push(Atom {
+                elem: halogen.into(),
+                xyz: [xyz[0], xyz[1], xyz[2]],
+            });
+        }
+        let mut index = 0;
+        structure.atoms.retain(|_| {
+            index += 1;
+            !replaced.contains(&(index - 1))
+        });
+        let halogen_sites =
+            (structure.atoms.len()..structure.atoms.len() + halogens.len()).collect();
+        structure.atoms.extend(halogens);
+
+        Ok(Some(Monomer {
+            structure,
+            halogen_sites,
+            num_units: units,
+        }))
+    }
+}
+
+#[pymethods]
+impl AGNR {
+    /// Dihalogenated precursor monomer of this AGNR (see `AGNR::monomer`), or `None` if there's
+    /// no simple one. The halogen is one of "Cl", "Br" (default) or "I".
+    #[name = "monomer"]
+    fn py_monomer(&self, halogen: Option<&str>, units: Option<usize>) -> PyResult<Option<Monomer>> {
+        self.monomer(halogen.unwrap_or("Br"), units)
+            .map_err(PyValueError::new_err)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::agnr::{connects, extensions, AGNR};
+use itertools::Itertools;
+use pyo3::prelude::*;
+use std::collections::HashSet;
+
+impl AGNR {
+    /// All (non-canonical) specs that differ from this one by a single elementary edit: one edge
+    /// step changed, one column (hexagonal segment, two spec entries) inserted, or one removed.
+    /// Every result is periodic and at least the minimum width.
+    pub fn single_edits(&self) -> Vec<Self> {
+        let n = self.len();
+        let spec = &self.spec;
+
+        // changing a single edge step
+        let mut ret = self.resized_runs(1);
+
+        // inserting a column before spec[i]
+        for i in 0..n {
+            let (prev, next) = (spec[(i + n - 1) % n], spec[i]);
+            for &a in &extensions(prev) {
+                for &b in extensions(a).iter().filter(|&&b| connects(b, next)) {
+                    let mut new_spec = spec.clone();
+                    new_spec.splice(i..i, vec![a, b]);
+                    ret.push(Self::new(new_spec));
+                }
+            }
+        }
+
+        // removing the column made up of spec[i] and spec[i + 1]
+        if n > 2 {
+            for i in 0..n {
+                if connects(spec[(i + n - 1) % n], spec[(i + 2) % n]) {
+                    let new_spec = (0..n)
+                        .filter(|&j| j != i && j != (i + 1) % n)
+                        .map(|j| spec[j])
+                        .collect();
+                    ret.push(Self::new(new_spec));
+                }
+            }
+        }
+
+        ret.retain(|agnr| agnr.is_valid_unbounded(1));
+        ret
+    }
+}
+
+#[pymethods]
+impl AGNR {
+    /// Get every distinct canonical AGNR within `max_edits` (default 1) elementary edits of this
+    /// one, see `single_edits`. The canonical form of this AGNR is not included.
+    pub fn neighbors(&self, max_edits: Option<usize>) -> Vec<AGNR> {
+        let start = self.canonical();
+
+        let mut seen = HashSet::new();
+        seen.insert(start.clone());
+
+        let mut frontier = vec![start.clone()];
+        for _ in 0..max_edits.unwrap_or(1) {
+            frontier = frontier
+                .iter()
+                .flat_map(|agnr| agnr.single_edits())
+                .map(|agnr| agnr.canonical())
+                .filter(|agnr| seen.insert(agnr.clone()))
+                .collect_vec();
+        }
+
+        seen.remove(&start);
+        seen.into_iter().sorted().collect()
+    }
+}
@@ -0,0 +1,116 @@
+use crate::agnr::AGNR;
+use crate::structure::AtomicStructure;
+use pyo3::prelude::*;
+
+/// How far (as a fraction of the grid spacing) an atom may be from its ideal position and still
+/// be recognized, which leaves room for relaxation.
+const TOLERANCE: f64 = 0.35;
+
+/// Round `v` to the nearest integer if it's within `TOLERANCE` of it.
+fn snap(v: f64) -> Option<i64> {
+    let rounded = v.round();
+    if (v - rounded).abs() <= TOLERANCE {
+        Some(rounded as i64)
+    } else {
+        None
+    }
+}
+
+/// Assign carbons to columns of the spec, assuming the first one is the left (`offset` of -1)
+/// or right (`offset` of 1) atom of its pair. See `AGNR::to_poscar` for the layout.
+fn assign_columns(carbons: &[[f64; 2]], num_columns: usize, dx: f64, offset: f64) -> Option<AGNR> {
+    // the carbon pairs of column i are at x = (3i - 1) * half and (3i + 1) * half
+    let half = dx / 3.0;
+    let dy = f64::sqrt(3.0) * half;
+    let x0 = carbons[0][0] - offset * half;
+    let y0 = carbons.iter().map(|c| c[1]).fold(f64::INFINITY, f64::min);
+
+    let period = 3 * num_columns as i64;
+    let mut columns = vec![vec![]; num_columns];
+    for &[x, y] in carbons {
+        let k = snap((x - x0) / half)?.rem_euclid(period);
+        let column = match k % 3 {
+            1 => (k - 1) / 3,
+            2 => ((k + 1) / 3) % num_columns as i64,
+            _ => return None,
+        };
+        let j = snap((y - y0) / dy)?;
+        columns[column as usize].push((j, k % 3));
+    }
+
+    let mut spec = Vec::with_capacity(num_columns);
+    for mut column in columns {
+        column.sort_unstable();
+        let (low, high) = match (column.first(), column.last()) {
+            (Some(first), Some(last)) => (first.0, last.0 + 2),
+            _ => return None,
+        };
+        // every other height from low to high must have exactly one pair (right atoms have
+        // k % 3 == 1, left ones k % 3 == 2)
+        let expected = (low..high).step_by(2).flat_map(|j| vec![(j, 1), (j, 2)]);
+        if !column.into_iter().eq(expected) {
+            return None;
+        }
+        spec.push((low as i32, high as i32));
+    }
+
+    let agnr = AGNR::new(spec);
+    let max_width = agnr.width()? as usize;
+    Some(agnr).filter(|a| a.is_valid(1, max_width))
+}
+
+impl AGNR {
+    /// Recognize the AGNR that a (possibly relaxed) ribbon structure was built from, assuming
+    /// the same orientation as `to_structure`: periodic along x with the ribbon in the xy plane.
+    /// Only carbon atoms are considered, and the bond length is estimated from their nearest
+    /// neighbor distances. Returns the canonical AGNR, or `None` if the carbons don't form one.
+    pub fn from_structure(structure: &AtomicStructure) -> Option<Self> {
+        let [ax, ay, az] = structure.lattice_vectors[0];
+        if ax <= 0.0 || ay.abs() > 1e-6 * ax || az.abs() > 1e-6 * ax {
+            return None;
+        }
+
+        let carbons: Vec<[f64; 2]> = structure
+            .atoms
+            .iter()
+            .filter(|a| a.elem == "C")
+            .map(|a| [a.xyz[0], a.xyz[1]])
+            .collect();
+        if carbons.len() < 2 {
+            return None;
+        }
+
+        // nearest neighbor distances, including periodic images along x
+        let nearest = |&[x, y]: &[f64; 2]| {
+            carbons
+                .iter()
+                .flat_map(|&[x2, y2]| {
+                    (-1..=1).map(move |image| f64::hypot(x2 + image as f64 * ax - x, y2 - y))
+                })
+                .filter(|&d| d > 1e-3)
+                .fold(f64::INFINITY, f64::min)
+        };
+        let cc_bond = carbons.iter().map(nearest).sum::<f64>() / carbons.len() as f64;
+        let num_columns = (ax / (1.5 * cc_bond)).round() as usize;
+        if num_columns == 0 {
+            return None;
+        }
+
+        let dx = ax / num_columns as f64;
+        [-1.0, 1.0]
+            .iter()
+            .find_map(|&offset| assign_columns(&carbons, num_columns, dx, offset))
+            .map(|agnr| agnr.canonical())
+    }
+}
+
+#[pymethods]
+impl AGNR {
+    /// Recognize the AGNR that a ribbon structure was built from (see `AGNR::from_structure`),
+    /// or `None` if it isn't one.
+    #[staticmethod]
+    #[name = "from_structure"]
+    fn py_from_structure(structure: &AtomicStructure) -> Option<Self> {
+        Self::from_structure(structure)
+    }
+}
@@ -0,0 +1,288 @@
+use agnr_ml::structure::{DftCode, DftInputs};
+use agnr_ml::{AtomicStructure, AGNR};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+struct Bounds {
+    /// Minimum AGNR length (number of hexagonal segments along the periodic axis)
+    #[structopt(long)]
+    min_len: usize,
+    /// Maximum AGNR length
+    #[structopt(long)]
+    max_len: usize,
+    /// Minimum AGNR width (half the height of every column, as printed by `info`)
+    #[structopt(long)]
+    min_width: usize,
+    /// Maximum AGNR width
+    #[structopt(long)]
+    max_width: usize,
+    /// Only include AGNRs with at least one symmetry
+    #[structopt(long)]
+    symmetric_only: bool,
+}
+
+impl Bounds {
+    fn generate(&self) -> Result<Vec<AGNR>, String> {
+        AGNR::check_bounds(self.min_len, self.max_len, self.min_width, self.max_width)?;
+        let mut agnrs: Vec<_> = AGNR::generate_all_agnrs(
+            self.min_len,
+            self.max_len,
+            self.min_width,
+            self.max_width,
+            self.symmetric_only,
+        )
+        .into_iter()
+        .collect();
+        // HashSet ordering isn't stable, so sort for reproducible output
+        agnrs.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+        Ok(agnrs)
+    }
+}
+
+#[derive(Debug)]
+enum Format {
+    Poscar,
+    Xyz,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "poscar" | "vasp" => Ok(Format::Poscar),
+            "xyz" => Ok(Format::Xyz),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "agnr",
+    about = "Generate and convert armchair graphene nanoribbons."
+)]
+enum Command {
+    /// Generate all unique AGNRs within the given bounds, one per line
+    Generate {
+        #[structopt(flatten)]
+        bounds: Bounds,
+        /// Write specs instead of names
+        #[structopt(long)]
+        specs: bool,
+        /// Output file (defaults to stdout)
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Build the atomic structure for an AGNR given by name or spec, e.g. "415" or "(0,4),(1,5)"
+    Build {
+        ribbon: String,
+        /// Output format, either "poscar" or "xyz"
+        #[structopt(short, long, default_value = "poscar")]
+        format: Format,
+        /// Carbon-carbon bond length
+        #[structopt(long)]
+        cc_bond: Option<f64>,
+        /// Carbon-hydrogen bond length
+        #[structopt(long)]
+        ch_bond: Option<f64>,
+        /// Vacuum separation between periodic images
+        #[structopt(long)]
+        vacuum_sep: Option<f64>,
+        /// Output file (defaults to stdout)
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write DFT inputs for every unique AGNR within the given bounds, each in a directory named
+    /// after the AGNR
+    Inputs {
+        #[structopt(flatten)]
+        bounds: Bounds,
+        /// DFT code, either "vasp" or "qe" (Quantum ESPRESSO)
+        #[structopt(long, default_value = "vasp")]
+        code: DftCode,
+        /// Maximum k-point spacing along the ribbon (1/angstrom)
+        #[structopt(long, default_value = "0.15")]
+        k_spacing: f64,
+        /// Number of k-points on the band structure path
+        #[structopt(long, default_value = "40")]
+        band_points: usize,
+        /// Template for an input file as FILE=PATH, e.g. INCAR=incar.template (can be repeated)
+        #[structopt(long = "template")]
+        templates: Vec<String>,
+        /// Output directory
+        #[structopt(short, long)]
+        output: PathBuf,
+    },
+    /// Print information about an AGNR given by name or spec
+    Info { ribbon: String },
+    /// Count the number of unique AGNRs within the given bounds
+    Count {
+        #[structopt(flatten)]
+        bounds: Bounds,
+    },
+}
+
+/// Parse an AGNR from either its name or its spec (any list of integer pairs).
+fn parse_ribbon(ribbon: &str) -> Result<AGNR, Box<dyn Error>> {
+    let is_spec = ribbon.contains(&[',', '(', '['][..]);
+    let agnr = if is_spec {
+        let values = ribbon
+            .split(|c: char| !(c.is_ascii_digit() || c == '-'))
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse::<i32>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if values.is_empty() || values.len() % 2 != 0 {
+            return Err(format!("spec '{}' must be a list of integer pairs", ribbon).into());
+        }
+        AGNR::new(values.chunks(2).map(|c| (c[0], c[1])).collect())
+    } else {
+        AGNR::from_name(ribbon).ok_or_else(|| format!("invalid AGNR name '{}'", ribbon))?
+    };
+
+    if let Some((low, high)) = agnr.spec().into_iter().find(|(low, high)| low >= high) {
+        return Err(format!(
+            "column ({}, {}) of AGNR '{}' has no atoms",
+            low, high, ribbon
+        )
+        .into());
+    }
+    if !agnr.is_valid_unbounded(1) {
+        return Err(format!("AGNR '{}' is not a connected, periodic ribbon", ribbon).into());
+    }
+    Ok(agnr)
+}
+
+fn write_output(output: Option<PathBuf>, contents: &str) -> io::Result<()> {
+    match output {
+        Some(path) => fs::write(path, contents),
+        None => io::stdout().write_all(contents.as_bytes()),
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    match Command::from_args() {
+        Command::Generate {
+            bounds,
+            specs,
+            output,
+        } => {
+            let mut lines = String::new();
+            for agnr in bounds.generate()? {
+                // names can't represent very wide AGNRs, so fall back to the spec
+                match agnr.name().filter(|_| !specs) {
+                    Some(name) => lines.push_str(&name),
+                    None => lines.push_str(&format!("{:?}", agnr.spec())),
+                }
+                lines.push('\n');
+            }
+            write_output(output, &lines)?;
+        }
+        Command::Build {
+            ribbon,
+            format,
+            cc_bond,
+            ch_bond,
+            vacuum_sep,
+            output,
+        } => {
+            let agnr = parse_ribbon(&ribbon)?;
+            let (poscar, report) = agnr.to_poscar(cc_bond, ch_bond, vacuum_sep)?;
+            if let Err(e) = report.check_saturated() {
+                eprintln!("warning: {}", e);
+            }
+            let contents = match format {
+                Format::Poscar => format!("{}", poscar),
+                Format::Xyz => {
+                    let structure: AtomicStructure = poscar.into();
+                    let mut xyz = String::new();
+                    structure.write_xyz(&mut xyz)?;
+                    xyz
+                }
+            };
+            write_output(output, &contents)?;
+        }
+        Command::Inputs {
+            bounds,
+            code,
+            k_spacing,
+            band_points,
+            templates,
+            output,
+        } => {
+            let mut contents = BTreeMap::new();
+            for template in &templates {
+                let mut parts = template.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(file), Some(path)) => {
+                        contents.insert(file.to_string(), fs::read_to_string(path)?);
+                    }
+                    _ => return Err(format!("template '{}' should be FILE=PATH", template).into()),
+                }
+            }
+            let inputs = DftInputs::new(
+                code,
+                k_spacing,
+                band_points,
+                Default::default(),
+                Default::default(),
+                contents,
+            )?;
+            let agnrs = bounds.generate()?;
+            for (i, agnr) in agnrs.iter().enumerate() {
+                // names can't represent very wide AGNRs, so fall back to the index
+                let name = agnr.name().unwrap_or_else(|| format!("agnr_{}", i));
+                let structure = agnr.default_structure()?;
+                inputs.write(&structure, &output.join(&name), &name)?;
+            }
+            eprintln!("wrote inputs for {} AGNRs", agnrs.len());
+        }
+        Command::Info { ribbon } => {
+            let agnr = parse_ribbon(&ribbon)?;
+            let (minimum_image, has_symmetry) = agnr.minimum_image();
+            let structure = agnr.default_structure()?;
+
+            let mut counts = BTreeMap::new();
+            for atom in &structure.atoms {
+                *counts.entry(atom.elem.as_str()).or_insert(0) += 1;
+            }
+
+            // same units as the bounds for `generate` and `count`
+            let (min_width, max_width) = agnr
+                .spec()
+                .iter()
+                .map(|&(low, high)| (high - low) / 2)
+                .minmax()
+                .into_option()
+                .unwrap();
+            let width = match min_width == max_width {
+                true => format!("{}", min_width),
+                false => format!("{} to {}", min_width, max_width),
+            };
+
+            let name_or_none = |a: &AGNR| a.name().unwrap_or_else(|| "none".into());
+            println!("name: {}", name_or_none(&agnr.normalized()));
+            println!("spec: {:?}", agnr.spec());
+            println!("canonical name: {}", name_or_none(&minimum_image));
+            println!("canonical spec: {:?}", minimum_image.spec());
+            println!("length: {}", agnr.len() / 2);
+            println!("width: {}", width);
+            println!("symmetric: {}", has_symmetry);
+            println!("atoms: {}", structure.atoms.len());
+            for (elem, count) in counts {
+                println!("  {}: {}", elem, count);
+            }
+        }
+        Command::Count { bounds } => {
+            println!("{}", bounds.generate()?.len());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use crate::agnr::AGNR;
+use crate::structure::{Atom, AtomicStructure, DftOutput};
+use pyo3::class::iter::{IterNextOutput, PyIterProtocol};
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+/// A single AGNR along with its structure and any computed metadata, stored as one line of a
+/// JSON Lines dataset file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub name: Option<String>,
+    pub spec: Vec<(i32, i32)>,
+    pub width: i32,
+    pub length: usize,
+    pub symmetric: bool,
+    pub atom_counts: BTreeMap<String, usize>,
+    pub lattice: [[f64; 3]; 3],
+    pub species: Vec<String>,
+    pub coords: Vec<[f64; 3]>,
+    #[serde(default)]
+    pub descriptors: BTreeMap<String, f64>,
+    /// Computed properties to learn, e.g. from `DftOutput::labels`.
+    #[serde(default)]
+    pub labels: BTreeMap<String, f64>,
+}
+
+impl Record {
+    /// Record of an AGNR and its structure, the AGNR must be a valid (connected and periodic)
+    /// ribbon.
+    pub fn new(
+        agnr: &AGNR,
+        structure: &AtomicStructure,
+        descriptors: BTreeMap<String, f64>,
+    ) -> Result<Self, String> {
+        let width = match agnr.width() {
+            Some(width) if agnr.is_valid_unbounded(1) => width,
+            _ => return Err(format!("{:?} is not a valid AGNR spec", agnr.spec())),
+        };
+        let mut atom_counts = BTreeMap::new();
+        for atom in &structure.atoms {
+            *atom_counts.entry(atom.elem.clone()).or_insert(0) += 1;
+        }
+
+        Ok(Self {
+            name: agnr.name(),
+            spec: agnr.spec(),
+            width,
+            length: agnr.len(),
+            symmetric: agnr.minimum_image().1,
+            atom_counts,
+            lattice: structure.lattice_vectors,
+            species: structure.atoms.iter().map(|a| a.elem.clone()).collect(),
+            coords: structure.atoms.iter().map(|a| a.xyz).collect(),
+            descriptors,
+            labels: BTreeMap::new(),
+        })
+    }
+
+    pub fn agnr(&self) -> AGNR {
+        AGNR::new(self.spec.clone())
+    }
+
+    pub fn structure(&self) -> AtomicStructure {
+        AtomicStructure {
+            lattice_vectors: self.lattice,
+            atoms: self
+                .species
+                .iter()
+                .zip(&self.coords)
+                .map(|(elem, &xyz)| Atom {
+                    elem: elem.clone(),
+                    xyz,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Streams records to a JSON Lines dataset file, one record per line.
+#[pyclass(module = "agnr_ml")]
+pub struct DatasetWriter {
+    output: Option<BufWriter<File>>,
+    cc_bond: Option<f64>,
+    ch_bond: Option<f64>,
+    vacuum_sep: Option<f64>,
+}
+
+#[pymethods]
+impl DatasetWriter {
+    /// Create (or truncate) a dataset file, structures are built using the given parameters.
+    #[new]
+    pub fn new(
+        path: &str,
+        cc_bond: Option<f64>,
+        ch_bond: Option<f64>,
+        vacuum_sep: Option<f64>,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            output: Some(BufWriter::new(File::create(path)?)),
+            cc_bond,
+            ch_bond,
+            vacuum_sep,
+        })
+    }
+
+    /// Build the structure for an AGNR and write it, along with any descriptors, to the dataset.
+    pub fn write(
+        &mut self,
+        agnr: &AGNR,
+        descriptors: Option<HashMap<String, f64>>,
+    ) -> io::Result<()> {
+        let (poscar, report) = agnr
+            .to_poscar(self.cc_bond, self.ch_bond, self.vacuum_sep)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        report
+            .check_saturated()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let structure = poscar.into();
+        let descriptors = descriptors.unwrap_or_default().into_iter().collect();
+        let record = Record::new(agnr, &structure, descriptors)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.write_record(&record)
+    }
+
+    /// Write the results of a DFT run for an AGNR to the dataset, using the run's final structure
+    /// and its labels (see `DftOutput.labels`).
+    pub fn write_output(
+        &mut self,
+        agnr: &AGNR,
+        output: &DftOutput,
+        descriptors: Option<HashMap<String, f64>>,
+    ) -> io::Result<()> {
+        let descriptors = descriptors.unwrap_or_default().into_iter().collect();
+        let mut record = Record::new(agnr, &output.structure, descriptors)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        record.labels = output.labels();
+        self.write_record(&record)
+    }
+
+    /// Flush and close the dataset file, further writes will fail.
+    pub fn close(&mut self) -> io::Result<()> {
+        match self.output.take() {
+            Some(mut output) => output.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl DatasetWriter {
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        let output = self
+            .output
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "dataset writer is closed"))?;
+
+        serde_json::to_writer(&mut *output, record)?;
+        output.write_all(b"\n")
+    }
+}
+
+/// Reads records back from a JSON Lines dataset file.
+#[pyclass(module = "agnr_ml")]
+pub struct DatasetReader {
+    lines: io::Lines<BufReader<File>>,
+    with_labels: bool,
+}
+
+#[pymethods]
+impl DatasetReader {
+    /// Records are read as (agnr, structure, descriptors) tuples, with their labels appended if
+    /// `labels` is true.
+    #[new]
+    pub fn new(path: &str, labels: Option<bool>) -> io::Result<Self> {
+        Ok(Self {
+            lines: BufReader::new(File::open(path)?).lines(),
+            with_labels: labels.unwrap_or(false),
+        })
+    }
+}
+
+impl DatasetReader {
+    /// Get the next record in the dataset, skipping blank lines.
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        for line in &mut self.lines {
+            let line = line?;
+            if !line.trim().is_empty() {
+                return Ok(Some(serde_json::from_str(&line)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for DatasetReader {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<IterNextOutput<PyObject, ()>> {
+        let record = match slf.next_record()? {
+            Some(record) => record,
+            None => return Ok(IterNextOutput::Return(())),
+        };
+
+        let py = slf.py();
+        let (agnr, structure) = (record.agnr(), record.structure());
+        let descriptors: HashMap<_, _> = record.descriptors.into_iter().collect();
+        Ok(IterNextOutput::Yield(if slf.with_labels {
+            let labels: HashMap<_, _> = record.labels.into_iter().collect();
+            (agnr, structure, descriptors, labels).into_py(py)
+        } else {
+            (agnr, structure, descriptors).into_py(py)
+        }))
+    }
+}
@@ -7,15 +7,40 @@ pub type CoordMat<N> = MatrixMN<N, U3, Dynamic>;
 
 pub mod adjacency;
 mod agnr;
+pub mod dataset;
+pub mod model;
 pub mod structure;
 
-pub use agnr::AGNR;
+pub use agnr::{GenerationInterrupted, Monomer, AGNR};
+pub use dataset::{DatasetReader, DatasetWriter};
+pub use model::{ActiveLearner, KernelModel};
 pub use structure::AtomicStructure;
 
 /// Native extension module for agnr_ml.
 #[pymodule]
-fn agnr_ml(_py: Python, m: &PyModule) -> PyResult<()> {
+fn agnr_ml(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<AGNR>()?;
     m.add_class::<AtomicStructure>()?;
+    m.add_class::<DatasetReader>()?;
+    m.add_class::<DatasetWriter>()?;
+    m.add_class::<ActiveLearner>()?;
+    m.add_class::<KernelModel>()?;
+    m.add_class::<Monomer>()?;
+    m.add_class::<adjacency::PassivationReport>()?;
+    m.add_class::<structure::BandEdges>()?;
+    m.add_class::<structure::BandGap>()?;
+    m.add_class::<structure::DftInputs>()?;
+    m.add_class::<structure::DftOutput>()?;
+    m.add_class::<structure::Multilayer>()?;
+    m.add_class::<structure::OpticalSpectrum>()?;
+    #[cfg(feature = "relax")]
+    m.add_class::<structure::Relaxation>()?;
+    m.add_class::<structure::StmImage>()?;
+    m.add_class::<structure::Substrate>()?;
+    m.add_class::<structure::SubstrateMatch>()?;
+    m.add(
+        "GenerationInterrupted",
+        py.get_type::<GenerationInterrupted>(),
+    )?;
     Ok(())
 }
@@ -0,0 +1,400 @@
+use crate::agnr::AGNR;
+use crate::model::{Features, Input, Kernel, KernelModel};
+use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+
+/// How to score unlabelled candidates given the surrogate's prediction (for maximization).
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Acquisition {
+    /// Expected improvement over the best label so far, by at least `xi`.
+    ExpectedImprovement { xi: f64 },
+    /// Upper confidence bound, `mean + kappa * std`.
+    UpperConfidenceBound { kappa: f64 },
+    /// Pure exploration, picks the most uncertain candidates.
+    MaxVariance,
+}
+
+/// Complementary error function, from Numerical Recipes (`erfcc`), accurate to about 1e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |acc, c| acc * t + c);
+    let ans = t * f64::exp(-z * z + poly);
+    if x >= 0.0 {
+        ans
+    } else {
+        2.0 - ans
+    }
+}
+
+/// The highest of the given labels.
+fn best_label(labels: &[f64]) -> f64 {
+    labels.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+}
+
+impl Acquisition {
+    pub fn score(&self, mean: f64, std: f64, best: f64) -> f64 {
+        match *self {
+            Acquisition::ExpectedImprovement { xi } => {
+                let improvement = mean - best - xi;
+                if std <= 0.0 {
+                    return f64::max(0.0, improvement);
+                }
+                let z = improvement / std;
+                let cdf = 0.5 * erfc(-z / std::f64::consts::SQRT_2);
+                let pdf = f64::exp(-0.5 * z * z) / (2.0 * std::f64::consts::PI).sqrt();
+                improvement * cdf + std * pdf
+            }
+            Acquisition::UpperConfidenceBound { kappa } => mean + kappa * std,
+            Acquisition::MaxVariance => std,
+        }
+    }
+}
+
+/// Everything needed to resume an active learning run, see `ActiveLearner::save`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct State {
+    kernel: Kernel,
+    noise: f64,
+    acquisition: Acquisition,
+    seed: u64,
+    iteration: usize,
+    pool: Vec<Vec<(i32, i32)>>,
+    /// labelled AGNRs in the order they were labelled
+    labelled: Vec<(Vec<(i32, i32)>, f64)>,
+}
+
+/// Bayesian optimization over a fixed pool of candidate AGNRs: each round the surrogate model is
+/// fit to everything labelled so far and the unlabelled candidates with the highest acquisition
+/// score are proposed for labelling. All AGNRs are compared by their canonical form, so
+/// symmetry-equivalent candidates are never proposed (or labelled) twice.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone)]
+pub struct ActiveLearner {
+    model: KernelModel,
+    acquisition: Acquisition,
+    seed: u64,
+    iteration: usize,
+    pool: Vec<AGNR>,
+    labelled: Vec<(AGNR, f64)>,
+}
+
+impl ActiveLearner {
+    pub fn new(pool: Vec<AGNR>, model: KernelModel, acquisition: Acquisition, seed: u64) -> Self {
+        let pool = pool
+            .iter()
+            .map(|agnr| agnr.canonical())
+            .sorted()
+            .dedup()
+            .collect();
+
+        Self {
+            model,
+            acquisition,
+            seed,
+            iteration: 0,
+            pool,
+            labelled: Vec::new(),
+        }
+    }
+
+    fn labelled_set(&self) -> HashSet<&AGNR> {
+        self.labelled.iter().map(|(agnr, _)| agnr).collect()
+    }
+
+    /// Record a label, replacing any previous label for the same (canonical) AGNR.
+    pub fn add_label(&mut self, agnr: &AGNR, value: f64) {
+        let agnr = agnr.canonical();
+        self.labelled.retain(|(a, _)| a != &agnr);
+        self.labelled.push((agnr, value));
+    }
+
+    /// Unlabelled candidates in the pool, along with their features for the surrogate.
+    fn candidates(&self) -> Result<Vec<(AGNR, Features)>, String> {
+        let labelled = self.labelled_set();
+        self.pool
+            .iter()
+            .filter(|agnr| !labelled.contains(agnr))
+            .map(|agnr| Ok((agnr.clone(), self.features(agnr)?)))
+            .collect()
+    }
+
+    fn features(&self, agnr: &AGNR) -> Result<Features, String> {
+        self.model.kernel.features(&Input::Agnr(agnr.clone()))
+    }
+
+    /// The surrogate fit to the given labels.
+    fn fit(&self, features: &[Features], targets: &[f64]) -> Result<KernelModel, String> {
+        let mut model = self.model.clone();
+        model.fit(features.to_vec(), targets.to_vec())?;
+        Ok(model)
+    }
+
+    /// Score `candidates` with a fitted surrogate, highest score first (as indices into
+    /// `candidates`). `best` is the highest label so far.
+    fn rank(
+        &self,
+        model: &KernelModel,
+        candidates: &[(AGNR, Features)],
+        best: f64,
+    ) -> Result<Vec<(usize, f64)>, String> {
+        let mut scored = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, (_, features))| {
+                let (mean, std) = model.predict(features)?;
+                Ok((i, self.acquisition.score(mean, std, best)))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // ties are broken by spec so that proposals are reproducible
+        scored.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| candidates[a.0].0.cmp(&candidates[b.0].0))
+        });
+        Ok(scored)
+    }
+
+    fn labelled_features(&self) -> Result<(Vec<Features>, Vec<f64>), String> {
+        self.labelled
+            .iter()
+            .map(|(agnr, value)| Ok((self.features(agnr)?, *value)))
+            .collect::<Result<Vec<_>, String>>()
+            .map(|labelled| labelled.into_iter().unzip())
+    }
+
+    /// Score every unlabelled candidate in the pool, highest score first. Without any labelled
+    /// data there's nothing to fit, so candidates are ordered randomly instead.
+    pub fn ranked_candidates(&self) -> Result<Vec<(AGNR, f64)>, String> {
+        if self.labelled.is_empty() {
+            let labelled = self.labelled_set();
+            let mut shuffled = self
+                .pool
+                .iter()
+                .filter(|agnr| !labelled.contains(agnr))
+                .map(|agnr| (agnr.clone(), 0.0))
+                .collect_vec();
+            let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(self.iteration as u64));
+            shuffled.shuffle(&mut rng);
+            return Ok(shuffled);
+        }
+
+        let candidates = self.candidates()?;
+        let (features, targets) = self.labelled_features()?;
+        let model = self.fit(&features, &targets)?;
+        let ranked = self.rank(&model, &candidates, best_label(&targets))?;
+        Ok(ranked
+            .into_iter()
+            .map(|(i, score)| (candidates[i].0.clone(), score))
+            .collect())
+    }
+
+    /// Propose the next `batch_size` AGNRs to label. The best candidates by acquisition score are
+    /// often near-duplicates of each other, so after each pick the rest of the batch is ranked as
+    /// if it had been labelled with its predicted mean (the "kriging believer" heuristic). This
+    /// shrinks the uncertainty around it and steers the rest of the batch elsewhere.
+    pub fn propose(&self, batch_size: usize) -> Result<Vec<AGNR>, String> {
+        if self.labelled.is_empty() || batch_size <= 1 {
+            let mut ranked = self.ranked_candidates()?;
+            ranked.truncate(batch_size);
+            return Ok(ranked.into_iter().map(|(agnr, _)| agnr).collect());
+        }
+
+        let mut candidates = self.candidates()?;
+        let (mut features, mut targets) = self.labelled_features()?;
+        let mut batch = Vec::new();
+        while batch.len() < batch_size && !candidates.is_empty() {
+            let model = self.fit(&features, &targets)?;
+            let (pick, _) = self.rank(&model, &candidates, best_label(&targets))?[0];
+            let (agnr, believed) = candidates.remove(pick);
+            let (mean, _) = model.predict(&believed)?;
+            features.push(believed);
+            targets.push(mean);
+            batch.push(agnr);
+        }
+        Ok(batch)
+    }
+
+    fn state(&self) -> State {
+        State {
+            kernel: self.model.kernel,
+            noise: self.model.regularization,
+            acquisition: self.acquisition,
+            seed: self.seed,
+            iteration: self.iteration,
+            pool: self.pool.iter().map(|agnr| agnr.spec()).collect(),
+            labelled: self
+                .labelled
+                .iter()
+                .map(|(agnr, value)| (agnr.spec(), *value))
+                .collect(),
+        }
+    }
+
+    fn from_state(state: State) -> Result<Self, String> {
+        Ok(Self {
+            model: KernelModel::new(state.kernel, state.noise)?,
+            acquisition: state.acquisition,
+            seed: state.seed,
+            iteration: state.iteration,
+            pool: state.pool.into_iter().map(AGNR::new).collect(),
+            labelled: state
+                .labelled
+                .into_iter()
+                .map(|(spec, value)| (AGNR::new(spec), value))
+                .collect(),
+        })
+    }
+}
+
+#[pymethods]
+impl ActiveLearner {
+    /// Create a learner over the given candidate pool, using the kernel and noise of `model` as
+    /// the surrogate. The acquisition function is one of "ei" (default), "ucb", or "max_variance",
+    /// with `xi` (default 0.01) and `kappa` (default 2.0) as their parameters.
+    #[new]
+    pub fn py_new(
+        pool: Vec<AGNR>,
+        model: Option<KernelModel>,
+        acquisition: Option<&str>,
+        xi: Option<f64>,
+        kappa: Option<f64>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let acquisition = match acquisition.unwrap_or("ei") {
+            "ei" => Acquisition::ExpectedImprovement {
+                xi: xi.unwrap_or(0.01),
+            },
+            "ucb" => Acquisition::UpperConfidenceBound {
+                kappa: kappa.unwrap_or(2.0),
+            },
+            "max_variance" => Acquisition::MaxVariance,
+            other => {
+                let msg = format!("unknown acquisition function '{}'", other);
+                return Err(PyValueError::new_err(msg));
+            }
+        };
+
+        let model = match model {
+            Some(model) => KernelModel::new(model.kernel, model.regularization)
+                .map_err(PyValueError::new_err)?,
+            None => KernelModel::py_new(None, None, None, None)?,
+        };
+        Ok(Self::new(pool, model, acquisition, seed.unwrap_or(0)))
+    }
+
+    #[getter]
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    #[getter]
+    pub fn pool(&self) -> Vec<AGNR> {
+        self.pool.clone()
+    }
+
+    /// All labelled (canonical) AGNRs and their labels, in the order they were labelled.
+    #[getter]
+    pub fn labelled(&self) -> Vec<(AGNR, f64)> {
+        self.labelled.clone()
+    }
+
+    /// The labelled AGNR with the highest label, if any.
+    #[getter]
+    pub fn best(&self) -> Option<(AGNR, f64)> {
+        self.labelled
+            .iter()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            .cloned()
+    }
+
+    #[name = "add_label"]
+    fn py_add_label(&mut self, agnr: &AGNR, value: f64) {
+        self.add_label(agnr, value);
+    }
+
+    /// Propose (without labelling) the next `batch_size` AGNRs to label, see `propose`.
+    #[name = "propose"]
+    fn py_propose(&self, batch_size: usize) -> PyResult<Vec<AGNR>> {
+        self.propose(batch_size).map_err(PyValueError::new_err)
+    }
+
+    /// Run `iterations` rounds, each proposing a batch and labelling it with `label`, which is
+    /// called with a single AGNR and must return a float. If `checkpoint` is given, the state is
+    /// saved there after every label so that an interrupted run can be resumed with `load`.
+    /// Returns everything labelled by this call, stopping early if the pool runs out.
+    pub fn run(
+        &mut self,
+        py: Python,
+        label: PyObject,
+        iterations: usize,
+        batch_size: Option<usize>,
+        checkpoint: Option<&str>,
+    ) -> PyResult<Vec<(AGNR, f64)>> {
+        let mut new_labels = Vec::new();
+        for _ in 0..iterations {
+            let batch = self
+                .propose(batch_size.unwrap_or(1))
+                .map_err(PyValueError::new_err)?;
+            if batch.is_empty() {
+                break;
+            }
+
+            for agnr in batch {
+                let value: f64 = label.call1(py, (agnr.clone(),))?.extract(py)?;
+                self.add_label(&agnr, value);
+                new_labels.push((agnr, value));
+                if let Some(path) = checkpoint {
+                    self.save(path)?;
+                }
+            }
+            self.iteration += 1;
+        }
+
+        if let Some(path) = checkpoint {
+            self.save(path)?;
+        }
+        Ok(new_labels)
+    }
+
+    /// Save the state to `path`, by writing a temporary file next to it and renaming it over
+    /// `path`, so that an interrupted save never leaves a truncated checkpoint behind.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let temp_path = format!("{}.tmp", path);
+        let mut output = BufWriter::new(File::create(&temp_path)?);
+        serde_json::to_writer_pretty(&mut output, &self.state())?;
+        output.into_inner()?.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+
+    #[staticmethod]
+    pub fn load(path: &str) -> io::Result<Self> {
+        let input = BufReader::new(File::open(path)?);
+        Self::from_state(serde_json::from_reader(input)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
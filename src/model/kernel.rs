@@ -0,0 +1,89 @@
+use crate::agnr::AGNR;
+use crate::structure::AtomicStructure;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Anything a model can be fit to or make predictions for.
+#[derive(Debug, Clone, FromPyObject)]
+pub enum Input {
+    Agnr(AGNR),
+    Structure(AtomicStructure),
+    /// Precomputed descriptors, only usable with the RBF kernel.
+    Descriptors(Vec<f64>),
+}
+
+/// Features of a single input, as used by a particular kernel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Features {
+    Dense(Vec<f64>),
+    Sparse(BTreeMap<u64, f64>),
+}
+
+/// Covariance function between two inputs, both kernels have unit variance.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Kernel {
+    /// Squared exponential kernel on (standardized) descriptors. For AGNRs the descriptors are
+    /// those of the AGNR itself followed by those of its structure.
+    Rbf { length_scale: f64 },
+    /// Squared exponential kernel on normalized Weisfeiler-Lehman features of the bond graph,
+    /// see `AtomicStructure::wl_features`.
+    Graph {
+        iterations: usize,
+        length_scale: f64,
+    },
+}
+
+impl Kernel {
+    pub fn length_scale(&self) -> f64 {
+        match *self {
+            Kernel::Rbf { length_scale } | Kernel::Graph { length_scale, .. } => length_scale,
+        }
+    }
+
+    pub fn features(&self, input: &Input) -> Result<Features, String> {
+        match (self, input) {
+            (Kernel::Rbf { .. }, Input::Agnr(agnr)) => {
+                let mut descriptors = agnr.descriptors();
+                descriptors.extend(agnr.default_structure()?.descriptors());
+                Ok(Features::Dense(descriptors))
+            }
+            (Kernel::Rbf { .. }, Input::Structure(s)) => Ok(Features::Dense(s.descriptors())),
+            (Kernel::Rbf { .. }, Input::Descriptors(d)) => Ok(Features::Dense(d.clone())),
+            (&Kernel::Graph { iterations, .. }, Input::Agnr(agnr)) => Ok(Features::Sparse(
+                agnr.default_structure()?.wl_features(iterations),
+            )),
+            (&Kernel::Graph { iterations, .. }, Input::Structure(s)) => {
+                Ok(Features::Sparse(s.wl_features(iterations)))
+            }
+            (Kernel::Graph { .. }, Input::Descriptors(_)) => {
+                Err("the graph kernel requires AGNRs or structures, not descriptors".to_string())
+            }
+        }
+    }
+
+    /// Panics if the features are of different kinds or lengths. `KernelModel` rules both out,
+    /// since its features all come from one kernel and their lengths are checked when
+    /// standardizing.
+    pub fn eval(&self, a: &Features, b: &Features) -> f64 {
+        let distance_squared = match (a, b) {
+            (Features::Dense(a), Features::Dense(b)) => {
+                assert_eq!(a.len(), b.len(), "mismatched descriptor lengths");
+                a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum()
+            }
+            // unit vectors, so |a - b|^2 = 2 - 2 a.b
+            (Features::Sparse(a), Features::Sparse(b)) => {
+                let dot: f64 = a.iter().filter_map(|(k, x)| b.get(k).map(|y| x * y)).sum();
+                f64::max(0.0, 2.0 - 2.0 * dot)
+            }
+            _ => panic!("mismatched feature types"),
+        };
+
+        let length_scale = match *self {
+            Kernel::Rbf { length_scale } | Kernel::Graph { length_scale, .. } => length_scale,
+        };
+        f64::exp(-0.5 * distance_squared / (length_scale * length_scale))
+    }
+}
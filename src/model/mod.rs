@@ -0,0 +1,388 @@
+use crate::agnr::AGNR;
+use itertools::Itertools;
+use nalgebra::{Cholesky, DMatrix, DVector};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+
+mod active;
+mod kernel;
+
+pub use active::{Acquisition, ActiveLearner};
+pub use kernel::{Features, Input, Kernel};
+
+/// Everything derived from the training data by `KernelModel::fit`.
+#[derive(Debug, Clone)]
+struct Fitted {
+    /// per-dimension mean and standard deviation of dense training features
+    feature_mean: Vec<f64>,
+    feature_std: Vec<f64>,
+    /// training features after standardization
+    train: Vec<Features>,
+    target_mean: f64,
+    target_std: f64,
+    /// `(K + regularization I)^-1 y` for the standardized targets `y`
+    weights: DVector<f64>,
+    /// lower triangular Cholesky factor of `K + regularization I`
+    l: DMatrix<f64>,
+}
+
+/// Kernel ridge regression, which is equivalent to the posterior mean of a Gaussian process with
+/// noise variance equal to the regularization, so both share this implementation. Targets are
+/// standardized before fitting, so the regularization is relative to the target variance.
+///
+/// Only the kernel, regularization, and training data are saved; everything else is re-derived
+/// when loading.
+///
+/// The Python `KernelRidge` and `GaussianProcess` classes both wrap this, and only differ in
+/// their default regularization and in whether predictions include the standard deviation.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelModel {
+    pub kernel: Kernel,
+    pub regularization: f64,
+    features: Vec<Features>,
+    targets: Vec<f64>,
+    #[serde(skip)]
+    fitted: Option<Fitted>,
+}
+
+impl KernelModel {
+    /// Create an unfit model, the length scale and regularization must be positive and finite.
+    pub fn new(kernel: Kernel, regularization: f64) -> Result<Self, String> {
+        let model = Self {
+            kernel,
+            regularization,
+            features: Vec::new(),
+            targets: Vec::new(),
+            fitted: None,
+        };
+        model.check_parameters()?;
+        Ok(model)
+    }
+
+    fn check_parameters(&self) -> Result<(), String> {
+        let length_scale = self.kernel.length_scale();
+        if !(length_scale > 0.0 && length_scale.is_finite()) {
+            return Err(format!(
+                "length scale must be positive, not {}",
+                length_scale
+            ));
+        }
+        if !(self.regularization > 0.0 && self.regularization.is_finite()) {
+            let msg = format!(
+                "regularization must be positive, not {}",
+                self.regularization
+            );
+            return Err(msg);
+        }
+        Ok(())
+    }
+
+    pub fn is_fitted(&self) -> bool {
+        self.fitted.is_some()
+    }
+
+    pub fn features(&self, inputs: &[Input]) -> Result<Vec<Features>, String> {
+        inputs.iter().map(|i| self.kernel.features(i)).collect()
+    }
+
+    pub fn fit(&mut self, features: Vec<Features>, targets: Vec<f64>) -> Result<(), String> {
+        if features.len() != targets.len() {
+            return Err("need exactly one target per input".to_string());
+        }
+        if features.is_empty() {
+            return Err("need at least one input to fit".to_string());
+        }
+        self.features = features;
+        self.targets = targets;
+        self.refit()
+    }
+
+    fn refit(&mut self) -> Result<(), String> {
+        let n = self.features.len();
+
+        let dense = self
+            .features
+            .iter()
+            .filter_map(|f| match f {
+                Features::Dense(d) => Some(d),
+                Features::Sparse(_) => None,
+            })
+            .collect_vec();
+        let dims = dense.first().map_or(0, |d| d.len());
+        if dense.iter().any(|d| d.len() != dims) {
+            return Err("all descriptors must have the same length".to_string());
+        }
+
+        // standardize each descriptor, constant ones are left as-is
+        let mean_std = |values: &[f64]| {
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+            match var.sqrt() {
+                std if std > 0.0 => (mean, std),
+                _ => (mean, 1.0),
+            }
+        };
+        let (feature_mean, feature_std) = (0..dims)
+            .map(|k| mean_std(&dense.iter().map(|d| d[k]).collect_vec()))
+            .unzip();
+        let (target_mean, target_std) = mean_std(&self.targets);
+
+        let mut fitted = Fitted {
+            feature_mean,
+            feature_std,
+            train: Vec::new(),
+            target_mean,
+            target_std,
+            weights: DVector::zeros(n),
+            l: DMatrix::zeros(n, n),
+        };
+        fitted.train = self
+            .features
+            .iter()
+            .map(|f| fitted.standardize(f))
+            .collect::<Result<_, _>>()?;
+
+        let kernel = self.kernel;
+        let train = &fitted.train;
+        let k = DMatrix::from_fn(n, n, |i, j| kernel.eval(&train[i], &train[j]))
+            + DMatrix::identity(n, n) * self.regularization;
+        let cholesky = Cholesky::new(k).ok_or_else(|| {
+            "kernel matrix is not positive definite, try a larger regularization".to_string()
+        })?;
+
+        let y = DVector::from_iterator(
+            n,
+            self.targets.iter().map(|t| (t - target_mean) / target_std),
+        );
+        fitted.weights = cholesky.solve(&y);
+        fitted.l = cholesky.unpack();
+
+        self.fitted = Some(fitted);
+        Ok(())
+    }
+
+    /// Predicted mean and standard deviation (of the latent function, excluding noise).
+    pub fn predict(&self, features: &Features) -> Result<(f64, f64), String> {
+        let fitted = self.fitted.as_ref().ok_or("model has not been fit")?;
+        let x = fitted.standardize(features)?;
+
+        let k = DVector::from_iterator(
+            fitted.train.len(),
+            fitted.train.iter().map(|t| self.kernel.eval(t, &x)),
+        );
+        let mean = k.dot(&fitted.weights);
+        let v = fitted
+            .l
+            .solve_lower_triangular(&k)
+            .ok_or("singular Cholesky factor")?;
+        let variance = f64::max(0.0, self.kernel.eval(&x, &x) - v.dot(&v));
+
+        Ok((
+            fitted.target_mean + fitted.target_std * mean,
+            fitted.target_std * variance.sqrt(),
+        ))
+    }
+
+    /// Generate every AGNR within the given bounds (see `AGNR::generate_all_agnrs`) and predict
+    /// for each one, returning the (at most `top`) AGNRs with the highest predicted mean first.
+    /// Candidates are predicted for as they are generated and only the best `top` are kept.
+    pub fn screen(
+        &self,
+        min_len: usize,
+        max_len: usize,
+        min_width: usize,
+        max_width: usize,
+        symmetric_only: bool,
+        top: Option<usize>,
+    ) -> Result<Vec<(AGNR, f64, f64)>, String> {
+        AGNR::check_bounds(min_len, max_len, min_width, max_width)?;
+        let top = top.unwrap_or(usize::MAX);
+
+        // min-heap of the best predictions so far, so the worst is the one dropped
+        let mut best = BinaryHeap::new();
+        let never_stop = |_, _| Ok(());
+        AGNR::for_each_agnr_with(
+            min_len,
+            max_len,
+            min_width,
+            max_width,
+            symmetric_only,
+            never_stop,
+            |agnr| {
+                let features = self.kernel.features(&Input::Agnr(agnr.clone()))?;
+                let (mean, std) = self.predict(&features)?;
+                best.push(Reverse(Screened { agnr, mean, std }));
+                if best.len() > top {
+                    best.pop();
+                }
+                Ok::<_, String>(())
+            },
+        )?;
+
+        Ok(best
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(s)| (s.agnr, s.mean, s.std))
+            .collect())
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let output = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(output, self).map_err(io::Error::from)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let input = BufReader::new(File::open(path)?);
+        let mut model: Self = serde_json::from_reader(input)?;
+        model
+            .check_parameters()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if !model.features.is_empty() {
+            model
+                .refit()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        Ok(model)
+    }
+}
+
+/// A prediction made by `KernelModel::screen`, ordered from worst to best. Ties are broken by
+/// spec so that results are reproducible.
+struct Screened {
+    agnr: AGNR,
+    mean: f64,
+    std: f64,
+}
+
+impl Ord for Screened {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.mean
+            .partial_cmp(&other.mean)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.agnr.cmp(&self.agnr))
+    }
+}
+
+impl PartialOrd for Screened {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Screened {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Screened {}
+
+impl Fitted {
+    fn standardize(&self, features: &Features) -> Result<Features, String> {
+        match features {
+            Features::Dense(d) if d.len() != self.feature_mean.len() => Err(format!(
+                "expected {} descriptors, got {}",
+                self.feature_mean.len(),
+                d.len()
+            )),
+            Features::Dense(d) => Ok(Features::Dense(
+                d.iter()
+                    .zip(&self.feature_mean)
+                    .zip(&self.feature_std)
+                    .map(|((x, mean), std)| (x - mean) / std)
+                    .collect(),
+            )),
+            Features::Sparse(s) => Ok(Features::Sparse(s.clone())),
+        }
+    }
+}
+
+fn py_kernel(
+    kernel: Option<&str>,
+    length_scale: Option<f64>,
+    iterations: Option<usize>,
+) -> PyResult<Kernel> {
+    match kernel.unwrap_or("rbf") {
+        "rbf" => Ok(Kernel::Rbf {
+            length_scale: length_scale.unwrap_or(3.0),
+        }),
+        "graph" => Ok(Kernel::Graph {
+            iterations: iterations.unwrap_or(3),
+            length_scale: length_scale.unwrap_or(0.5),
+        }),
+        other => Err(PyValueError::new_err(format!("unknown kernel '{}'", other))),
+    }
+}
+
+#[pymethods]
+impl KernelModel {
+    /// Create an (unfit) model using either the "rbf" (default) or "graph" kernel, see `Kernel`.
+    /// The regularization (default 1e-6) is relative to the variance of the targets.
+    #[new]
+    pub fn py_new(
+        kernel: Option<&str>,
+        regularization: Option<f64>,
+        length_scale: Option<f64>,
+        iterations: Option<usize>,
+    ) -> PyResult<Self> {
+        let kernel = py_kernel(kernel, length_scale, iterations)?;
+        Self::new(kernel, regularization.unwrap_or(1e-6)).map_err(PyValueError::new_err)
+    }
+
+    #[name = "fit"]
+    pub fn py_fit(&mut self, inputs: Vec<Input>, targets: Vec<f64>) -> PyResult<()> {
+        let features = self.features(&inputs).map_err(PyValueError::new_err)?;
+        self.fit(features, targets).map_err(PyValueError::new_err)
+    }
+
+    /// Predicted means and standard deviations for each input.
+    #[name = "predict"]
+    pub fn py_predict(&self, inputs: Vec<Input>) -> PyResult<(Vec<f64>, Vec<f64>)> {
+        let features = self.features(&inputs).map_err(PyValueError::new_err)?;
+        features
+            .iter()
+            .map(|f| self.predict(f).map_err(PyValueError::new_err))
+            .collect::<PyResult<Vec<_>>>()
+            .map(|predictions| predictions.into_iter().unzip())
+    }
+
+    /// Predict for every AGNR within the given bounds, returning (AGNR, mean, std) with the
+    /// highest means first.
+    #[name = "screen"]
+    pub fn py_screen(
+        &self,
+        min_len: usize,
+        max_len: usize,
+        min_width: usize,
+        max_width: usize,
+        symmetric_only: Option<bool>,
+        top: Option<usize>,
+    ) -> PyResult<Vec<(AGNR, f64, f64)>> {
+        self.screen(
+            min_len,
+            max_len,
+            min_width,
+            max_width,
+            symmetric_only.unwrap_or(false),
+            top,
+        )
+        .map_err(PyValueError::new_err)
+    }
+
+    #[name = "save"]
+    pub fn py_save(&self, path: &str) -> io::Result<()> {
+        self.save(path)
+    }
+
+    #[staticmethod]
+    #[name = "load"]
+    pub fn py_load(path: &str) -> io::Result<Self> {
+        Self::load(path)
+    }
+}
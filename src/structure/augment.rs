@@ -0,0 +1,218 @@
+use super::AtomicStructure;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+
+/// A perturbation applied to a structure, kept so that augmented data can be traced back to the
+/// structure it came from. Note: the periodic axis is assumed to be along x (the first lattice
+/// vector), as it is for all structures built from AGNRs.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Transformation {
+    /// Gaussian displacement of every atom in every direction.
+    Rattle { stddev: f64, seed: u64 },
+    /// Strain along the periodic axis (xx) and across the ribbon in-plane (yy), applied to both
+    /// the lattice and the atoms.
+    Strain { xx: f64, yy: f64 },
+    /// Rigid rotation (in degrees) of the atoms about the periodic axis, through their centroid.
+    Rotation { angle: f64 },
+}
+
+/// Perturbed copies of a structure, each with the transformations that produced it.
+pub type Augmented = Vec<(AtomicStructure, Vec<Transformation>)>;
+
+impl ToPyObject for Transformation {
+    fn to_object(&self, py: Python) -> PyObject {
+        let dict = PyDict::new(py);
+        let set = |k: &str, v: PyObject| dict.set_item(k, v).unwrap();
+        match *self {
+            Transformation::Rattle { stddev, seed } => {
+                set("kind", "rattle".to_object(py));
+                set("stddev", stddev.to_object(py));
+                set("seed", seed.to_object(py));
+            }
+            Transformation::Strain { xx, yy } => {
+                set("kind", "strain".to_object(py));
+                set("xx", xx.to_object(py));
+                set("yy", yy.to_object(py));
+            }
+            Transformation::Rotation { angle } => {
+                set("kind", "rotation".to_object(py));
+                set("angle", angle.to_object(py));
+            }
+        }
+        dict.into()
+    }
+}
+
+impl IntoPy<PyObject> for Transformation {
+    fn into_py(self, py: Python) -> PyObject {
+        self.to_object(py)
+    }
+}
+
+impl AtomicStructure {
+    pub fn rattled(&self, stddev: f64, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut ret = self.clone();
+        for atom in &mut ret.atoms {
+            for x in &mut atom.xyz {
+                *x += stddev * rng.sample::<f64, _>(StandardNormal);
+            }
+        }
+        ret
+    }
+
+    pub fn strained(&self, xx: f64, yy: f64) -> Self {
+        let scale = [1.0 + xx, 1.0 + yy, 1.0];
+        let mut ret = self.clone();
+        let vectors = ret.lattice_vectors.iter_mut();
+        let positions = ret.atoms.iter_mut().map(|a| &mut a.xyz);
+        for v in vectors.chain(positions) {
+            for (x, s) in v.iter_mut().zip(&scale) {
+                *x *= s;
+            }
+        }
+        ret
+    }
+
+    pub fn rotated(&self, angle: f64) -> Self {
+        let n = self.atoms.len().max(1) as f64;
+        let cy = self.atoms.iter().map(|a| a.xyz[1]).sum::<f64>() / n;
+        let cz = self.atoms.iter().map(|a| a.xyz[2]).sum::<f64>() / n;
+        let (sin, cos) = angle.to_radians().sin_cos();
+
+        let mut ret = self.clone();
+        for atom in &mut ret.atoms {
+            let (y, z) = (atom.xyz[1] - cy, atom.xyz[2] - cz);
+            atom.xyz[1] = cy + cos * y - sin * z;
+            atom.xyz[2] = cz + sin * y + cos * z;
+        }
+        ret
+    }
+
+    pub fn transformed(&self, transformation: &Transformation) -> Self {
+        match *transformation {
+            Transformation::Rattle { stddev, seed } => self.rattled(stddev, seed),
+            Transformation::Strain { xx, yy } => self.strained(xx, yy),
+            Transformation::Rotation { angle } => self.rotated(angle),
+        }
+    }
+
+    /// Generate `count` randomly perturbed copies of the structure. Each one is strained (uniform
+    /// in `[-max_strain, max_strain]`, along both in-plane axes if `biaxial`), rotated (uniform in
+    /// `[-max_angle, max_angle]`), and then rattled. `max_strain` must be in `[0, 1)` and the other
+    /// amplitudes non-negative, all finite.
+    pub fn augmented(
+        &self,
+        count: usize,
+        rattle_stddev: f64,
+        max_strain: f64,
+        biaxial: bool,
+        max_angle: f64,
+        seed: u64,
+    ) -> Result<Augmented, String> {
+        if !(0.0..1.0).contains(&max_strain) {
+            return Err(format!("max_strain must be in [0, 1), got {}", max_strain));
+        }
+        if !(rattle_stddev >= 0.0 && rattle_stddev.is_finite()) {
+            return Err(format!(
+                "rattle_stddev must be finite and non-negative, got {}",
+                rattle_stddev
+            ));
+        }
+        if !(max_angle >= 0.0 && max_angle.is_finite()) {
+            return Err(format!(
+                "max_angle must be finite and non-negative, got {}",
+                max_angle
+            ));
+        }
+
+        fn uniform(rng: &mut StdRng, max: f64) -> f64 {
+            match max {
+                max if max > 0.0 => rng.gen_range(-max, max),
+                _ => 0.0,
+            }
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let ret = (0..count)
+            .map(|_| {
+                let xx = uniform(&mut rng, max_strain);
+                let yy = match biaxial {
+                    true => uniform(&mut rng, max_strain),
+                    false => 0.0,
+                };
+                let angle = uniform(&mut rng, max_angle);
+                let transformations = vec![
+                    Transformation::Strain { xx, yy },
+                    Transformation::Rotation { angle },
+                    Transformation::Rattle {
+                        stddev: rattle_stddev,
+                        seed: rng.gen(),
+                    },
+                ];
+
+                let structure = transformations
+                    .iter()
+                    .fold(self.clone(), |s, t| s.transformed(t));
+                (structure, transformations)
+            })
+            .collect();
+        Ok(ret)
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Displace every atom by a normally distributed amount with the given standard deviation.
+    fn rattle(&self, stddev: f64, seed: u64) -> (Self, Transformation) {
+        let t = Transformation::Rattle { stddev, seed };
+        (self.transformed(&t), t)
+    }
+
+    /// Strain the structure along the periodic (x) axis, and optionally in-plane across the
+    /// ribbon (y) for biaxial strain.
+    fn strain(&self, xx: f64, yy: Option<f64>) -> (Self, Transformation) {
+        let t = Transformation::Strain {
+            xx,
+            yy: yy.unwrap_or(0.0),
+        };
+        (self.transformed(&t), t)
+    }
+
+    /// Rigidly rotate the atoms about the periodic axis by an angle in degrees.
+    fn rotate(&self, angle: f64) -> (Self, Transformation) {
+        let t = Transformation::Rotation { angle };
+        (self.transformed(&t), t)
+    }
+
+    /// Generate randomly perturbed copies of many structures at once, see `augmented`. The seed
+    /// of each structure is drawn from one generator for the whole batch, so batches with nearby
+    /// seeds don't share any copies.
+    #[staticmethod]
+    #[allow(clippy::too_many_arguments)]
+    fn augment_batch(
+        structures: Vec<AtomicStructure>,
+        count: usize,
+        rattle_stddev: f64,
+        max_strain: f64,
+        biaxial: bool,
+        max_angle: f64,
+        seed: u64,
+    ) -> PyResult<Vec<Augmented>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        structures
+            .iter()
+            .map(|s| {
+                let seed = rng.gen();
+                s.augmented(count, rattle_stddev, max_strain, biaxial, max_angle, seed)
+            })
+            .collect::<Result<_, _>>()
+            .map_err(PyValueError::new_err)
+    }
+}
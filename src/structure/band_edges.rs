@@ -0,0 +1,239 @@
+use super::tight_binding::{TightBinding, DEFAULT_HOPPING, TB_BOND_CUTOFF};
+use super::AtomicStructure;
+use itertools::Itertools;
+use nalgebra::{Complex, DMatrix};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::f64::consts::PI;
+
+/// hbar^2 / m_e in eV angstrom^2, to get effective masses in units of the electron mass.
+const HBAR2_OVER_ME: f64 = 7.619964;
+/// Step for the finite differences, as a fraction of the Brillouin zone.
+const FINITE_DIFFERENCE_STEP: f64 = 1e-4;
+/// Newton iterations when refining the k-point of a band extremum.
+const MAX_REFINE_STEPS: usize = 20;
+
+/// Band edges of the pz tight-binding model of a ribbon, with carrier effective masses and the
+/// localization of the frontier states on the edges of the ribbon.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandEdges {
+    /// Band gap in eV, zero (or negative, for overlapping bands) for metals.
+    #[pyo3(get)]
+    pub gap: f64,
+    /// Valence band maximum in eV, on the tight-binding energy scale (zero is the pz on-site
+    /// energy).
+    #[pyo3(get)]
+    pub vbm: f64,
+    /// Conduction band minimum in eV.
+    #[pyo3(get)]
+    pub cbm: f64,
+    /// Fractional k-point (along the periodic axis, in [-0.5, 0.5)) of the VBM.
+    #[pyo3(get)]
+    pub vbm_kpoint: f64,
+    /// Fractional k-point of the CBM.
+    #[pyo3(get)]
+    pub cbm_kpoint: f64,
+    /// Hole effective mass at the VBM, in electron masses (positive for a maximum).
+    #[pyo3(get)]
+    pub hole_mass: f64,
+    /// Electron effective mass at the CBM, in electron masses (positive for a minimum).
+    #[pyo3(get)]
+    pub electron_mass: f64,
+    /// Fraction of the VBM state on the edge carbons.
+    #[pyo3(get)]
+    pub vbm_edge_weight: f64,
+    /// Fraction of the CBM state on the edge carbons.
+    #[pyo3(get)]
+    pub cbm_edge_weight: f64,
+    /// In-gap edge states: states of the highest valence and lowest conduction band that are
+    /// localized on the edges, with energies between the delocalized states of those bands, as
+    /// (fractional k-point, band index, energy in eV, edge weight).
+    #[pyo3(get)]
+    pub edge_states: Vec<(f64, usize, f64, f64)>,
+}
+
+#[pymethods]
+impl BandEdges {
+    /// Whether there are any in-gap edge states.
+    #[getter]
+    fn has_edge_states(&self) -> bool {
+        !self.edge_states.is_empty()
+    }
+}
+
+impl TightBinding {
+    /// Energy of `band` at `k`.
+    fn band_energy(&self, band: usize, k: f64) -> f64 {
+        self.bands(k).0[band]
+    }
+
+    /// First and second derivative of `band` at `k` from central differences.
+    fn band_derivatives(&self, band: usize, k: f64, step: f64) -> (f64, f64) {
+        let (before, at, after) = (
+            self.band_energy(band, k - step),
+            self.band_energy(band, k),
+            self.band_energy(band, k + step),
+        );
+        (
+            (after - before) / (2.0 * step),
+            (after - 2.0 * at + before) / (step * step),
+        )
+    }
+
+    /// Refine a band extremum near `k` (from a k-point mesh with the given spacing) with Newton
+    /// steps on the finite difference derivatives, returning the k-point and the curvature there.
+    fn refine_extremum(&self, band: usize, mut k: f64, spacing: f64) -> (f64, f64) {
+        let step = FINITE_DIFFERENCE_STEP * 2.0 * PI / self.length;
+        for _ in 0..MAX_REFINE_STEPS {
+            let (slope, curvature) = self.band_derivatives(band, k, step);
+            if curvature == 0.0 {
+                break;
+            }
+            // stay near the mesh point, in case the band isn't parabolic there
+            let newton = (-slope / curvature).max(-spacing).min(spacing);
+            k += newton;
+            if newton.abs() < 1e-9 * spacing {
+                break;
+            }
+        }
+        (k, self.band_derivatives(band, k, step).1)
+    }
+}
+
+impl AtomicStructure {
+    /// Carbons bonded to exactly two other carbons (see `adjacency::calc_bonds`), i.e. the edge
+    /// atoms of a ribbon whether or not it's passivated.
+    pub fn edge_carbons(&self) -> Vec<usize> {
+        let is_carbon = |i: usize| self.atoms[i].elem == "C";
+        self.bonds(TB_BOND_CUTOFF)
+            .iter()
+            .enumerate()
+            .filter(|&(i, bonds)| {
+                is_carbon(i) && bonds.iter().filter(|b| is_carbon(b.to)).count() == 2
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Band edges of the nearest-neighbor pz tight-binding model (see `TightBinding`) on a mesh
+    /// of `num_kpoints`, with the extrema refined off the mesh to get the effective masses from
+    /// the band curvature. States of the frontier bands with at least `edge_threshold` of their
+    /// weight on the edge carbons (see `edge_carbons`) are edge states, and are reported if
+    /// they're in the gap of the remaining, delocalized states.
+    pub fn band_edges(
+        &self,
+        num_kpoints: usize,
+        hopping: f64,
+        edge_threshold: f64,
+    ) -> Result<BandEdges, String> {
+        if num_kpoints < 3 {
+            return Err("need at least three k-points".into());
+        }
+        if hopping <= 0.0 {
+            return Err("hopping must be positive".into());
+        }
+        let tb = TightBinding::new(self, hopping)?;
+        let occupied = tb.num_occupied();
+        if occupied == 0 || occupied == tb.num_orbitals {
+            return Err("need both occupied and unoccupied bands".into());
+        }
+        let (valence, conduction) = (occupied - 1, occupied);
+
+        // orbitals are the carbons in order
+        let carbons = (0..self.atoms.len())
+            .filter(|&i| self.atoms[i].elem == "C")
+            .collect_vec();
+        let edges = self.edge_carbons();
+        let is_edge = carbons.iter().map(|i| edges.contains(i)).collect_vec();
+        let edge_weight = |states: &DMatrix<Complex<f64>>, band: usize| -> f64 {
+            let column = states.column(band);
+            (0..tb.num_orbitals)
+                .filter(|&j| is_edge[j])
+                .map(|j| column[j].norm_sqr())
+                .sum()
+        };
+
+        let kpoints = tb.kpoint_mesh(num_kpoints);
+        let bands = kpoints.iter().map(|&k| tb.bands(k)).collect_vec();
+        let to_fractional = |k: f64| {
+            let fractional = k * tb.length / (2.0 * PI);
+            fractional - (fractional + 0.5).floor()
+        };
+
+        // edge states are in the gap if they're between the delocalized states of the valence
+        // and conduction bands
+        let mut localized = vec![];
+        let (mut bulk_vbm, mut bulk_cbm) = (f64::NEG_INFINITY, f64::INFINITY);
+        for (&k, (energies, states)) in kpoints.iter().zip(&bands) {
+            for band in valence..=conduction {
+                let weight = edge_weight(states, band);
+                let energy = energies[band];
+                if weight >= edge_threshold {
+                    localized.push((to_fractional(k), band, energy, weight));
+                } else if band == valence {
+                    bulk_vbm = bulk_vbm.max(energy);
+                } else {
+                    bulk_cbm = bulk_cbm.min(energy);
+                }
+            }
+        }
+        let edge_states = localized
+            .into_iter()
+            .filter(|&(_, _, energy, _)| bulk_vbm < energy && energy < bulk_cbm)
+            .collect();
+
+        let spacing = kpoints[1] - kpoints[0];
+        // band energies are finite, see `TightBinding::bands`
+        let mesh_extremum = |band: usize, sign: f64| {
+            (0..num_kpoints)
+                .max_by(|&a, &b| {
+                    let (ea, eb) = (sign * bands[a].0[band], sign * bands[b].0[band]);
+                    ea.partial_cmp(&eb).unwrap()
+                })
+                .map(|i| kpoints[i])
+                .unwrap()
+        };
+        let (vbm_k, vbm_curvature) =
+            tb.refine_extremum(valence, mesh_extremum(valence, 1.0), spacing);
+        let (cbm_k, cbm_curvature) =
+            tb.refine_extremum(conduction, mesh_extremum(conduction, -1.0), spacing);
+        let (vbm, cbm) = (
+            tb.band_energy(valence, vbm_k),
+            tb.band_energy(conduction, cbm_k),
+        );
+
+        Ok(BandEdges {
+            gap: cbm - vbm,
+            vbm,
+            cbm,
+            vbm_kpoint: to_fractional(vbm_k),
+            cbm_kpoint: to_fractional(cbm_k),
+            hole_mass: -HBAR2_OVER_ME / vbm_curvature,
+            electron_mass: HBAR2_OVER_ME / cbm_curvature,
+            vbm_edge_weight: edge_weight(&tb.bands(vbm_k).1, valence),
+            cbm_edge_weight: edge_weight(&tb.bands(cbm_k).1, conduction),
+            edge_states,
+        })
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Tight-binding band edges, effective masses, and edge states, see
+    /// `AtomicStructure::band_edges`.
+    #[name = "band_edges"]
+    fn py_band_edges(
+        &self,
+        num_kpoints: Option<usize>,
+        hopping: Option<f64>,
+        edge_threshold: Option<f64>,
+    ) -> PyResult<BandEdges> {
+        self.band_edges(
+            num_kpoints.unwrap_or(100),
+            hopping.unwrap_or(DEFAULT_HOPPING),
+            edge_threshold.unwrap_or(0.5),
+        )
+        .map_err(PyValueError::new_err)
+    }
+}
@@ -0,0 +1,391 @@
+use super::{Atom, AtomicStructure};
+use crate::adjacency::{passivate, PassivationOptions, CAP};
+use itertools::Itertools;
+use nalgebra::Vector3;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Cutoff used to find bonds when applying defects.
+const DEFECT_BOND_CUTOFF: f64 = 1.6;
+/// C-H bond length used when re-passivating a structure without any C-H bonds.
+const DEFAULT_CH_BOND: f64 = 1.09047;
+/// Tolerance (in angstroms) when matching atoms under a symmetry operation.
+const SYMMETRY_TOLERANCE: f64 = 1e-3;
+
+/// A type of point defect, see `Defect`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefectKind {
+    Vacancy,
+    StoneWales,
+    Adatom { elem: String, height: f64 },
+}
+
+/// A point defect on a ribbon structure, given by the indices of the atoms it involves.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Defect {
+    /// Remove the atoms and re-passivate, see `AtomicStructure::with_vacancies`.
+    Vacancy { sites: Vec<usize> },
+    /// Rotate a C-C bond, see `AtomicStructure::with_stone_wales`.
+    StoneWales { bond: [usize; 2] },
+    /// Add an atom above the given sites, see `AtomicStructure::with_adatom`.
+    Adatom {
+        sites: Vec<usize>,
+        elem: String,
+        height: f64,
+    },
+}
+
+impl DefectKind {
+    /// Parse a defect kind as given from Python: "vacancy", "stone_wales", or "adatom" (a
+    /// hydrogen 1.1 angstroms above the site by default).
+    pub fn from_py(kind: &str, element: Option<&str>, height: Option<f64>) -> PyResult<Self> {
+        match kind {
+            "vacancy" => Ok(DefectKind::Vacancy),
+            "stone_wales" => Ok(DefectKind::StoneWales),
+            "adatom" => Ok(DefectKind::Adatom {
+                elem: element.unwrap_or("H").to_string(),
+                height: height.unwrap_or(1.1),
+            }),
+            other => Err(PyValueError::new_err(format!(
+                "unknown defect kind '{}'",
+                other
+            ))),
+        }
+    }
+
+    pub fn defect(&self, sites: Vec<usize>) -> Result<Defect, String> {
+        match self {
+            DefectKind::Vacancy => Ok(Defect::Vacancy { sites }),
+            DefectKind::StoneWales => match *sites.as_slice() {
+                [a, b] => Ok(Defect::StoneWales { bond: [a, b] }),
+                _ => Err("a Stone-Wales defect needs exactly two sites".to_string()),
+            },
+            DefectKind::Adatom { elem, height } => Ok(Defect::Adatom {
+                sites,
+                elem: elem.clone(),
+                height: *height,
+            }),
+        }
+    }
+}
+
+impl Defect {
+    pub fn sites(&self) -> Vec<usize> {
+        match self {
+            Defect::Vacancy { sites } | Defect::Adatom { sites, .. } => sites.clone(),
+            Defect::StoneWales { bond } => bond.to_vec(),
+        }
+    }
+}
+
+impl AtomicStructure {
+    fn check_sites(&self, sites: &[usize]) -> Result<(), String> {
+        if sites.is_empty() {
+            return Err("no sites given".to_string());
+        }
+        match sites.iter().find(|&&i| i >= self.atoms.len()) {
+            Some(i) => Err(format!(
+                "site {} out of range for {} atoms",
+                i,
+                self.atoms.len()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn is_carbon(&self, i: usize) -> bool {
+        self.atoms[i].elem == "C"
+    }
+
+    fn xyz(&self, i: usize) -> Vector3<f64> {
+        Vector3::from(self.atoms[i].xyz)
+    }
+
+    pub fn with_defect(&self, defect: &Defect) -> Result<Self, String> {
+        match defect {
+            Defect::Vacancy { sites } => self.with_vacancies(sites),
+            Defect::StoneWales { bond } => self.with_stone_wales(bond[0], bond[1]),
+            Defect::Adatom {
+                sites,
+                elem,
+                height,
+            } => self.with_adatom(sites, elem, *height),
+        }
+    }
+
+    /// Remove the given atoms, along with any hydrogen left without a neighbor and any carbon left
+    /// with fewer than two carbon neighbors (repeatedly), then `passivate` each carbon that lost a
+    /// carbon neighbor, failing if one can't be capped. The new hydrogen atoms are placed at the
+    /// end, using the structure's mean C-H bond length. Removing a hydrogen leaves a dangling bond.
+    pub fn with_vacancies(&self, sites: &[usize]) -> Result<Self, String> {
+        self.check_sites(sites)?;
+        let bonds = self.bonds(DEFECT_BOND_CUTOFF);
+        let (lattice, coords) = (self.lattice_matrix(), self.coord_matrix());
+
+        let mut removed = vec![false; self.atoms.len()];
+        for &i in sites {
+            removed[i] = true;
+        }
+
+        let lost_carbon = |removed: &[bool], i: usize| {
+            bonds[i]
+                .iter()
+                .any(|b| removed[b.to] && self.is_carbon(b.to))
+        };
+        loop {
+            let prune = (0..self.atoms.len())
+                .filter(|&i| !removed[i])
+                .filter(|&i| {
+                    let mut remaining = bonds[i].iter().filter(|b| !removed[b.to]);
+                    match self.is_carbon(i) {
+                        true => {
+                            lost_carbon(&removed, i)
+                                && remaining.filter(|b| self.is_carbon(b.to)).count() < 2
+                        }
+                        false => !bonds[i].is_empty() && remaining.next().is_none(),
+                    }
+                })
+                .collect_vec();
+            if prune.is_empty() {
+                break;
+            }
+            for i in prune {
+                removed[i] = true;
+            }
+        }
+
+        let ch_bonds = (0..self.atoms.len())
+            .filter(|&i| self.is_carbon(i))
+            .flat_map(|i| bonds[i].iter().filter(|b| self.atoms[b.to].elem == "H"))
+            .map(|b| b.to_delta(&lattice, &coords).norm())
+            .collect_vec();
+        let ch_bond = match ch_bonds.len() {
+            0 => DEFAULT_CH_BOND,
+            n => ch_bonds.iter().sum::<f64>() / n as f64,
+        };
+
+        let kept = (0..self.atoms.len()).filter(|&i| !removed[i]).collect_vec();
+        let mut ret = self.clone();
+        ret.atoms = kept.iter().map(|&i| self.atoms[i].clone()).collect();
+
+        let mut options = PassivationOptions {
+            cutoff_distance: DEFECT_BOND_CUTOFF,
+            ..Default::default()
+        };
+        options.set_bond_length("C", CAP, ch_bond);
+        let elements = ret.atoms.iter().map(|a| a.elem.as_str()).collect_vec();
+        let (caps, report) = passivate(
+            &ret.lattice_matrix(),
+            &ret.coord_matrix(),
+            &elements,
+            &options,
+        )?;
+
+        // only re-passivate the carbons the vacancy opened up, existing dangling bonds stay
+        if let Some(&(site, _)) =
+            (report.unsaturated.iter()).find(|&&(site, _)| lost_carbon(&removed, kept[site]))
+        {
+            return Err(format!("couldn't passivate site {}", kept[site]));
+        }
+        let hydrogen = (report.passivated.iter())
+            .filter(|(site, _)| lost_carbon(&removed, kept[*site]))
+            .flat_map(|(_, added)| added.iter().map(|&k| caps[k - kept.len()]))
+            .map(|h| Atom {
+                elem: CAP.to_string(),
+                xyz: [h[0], h[1], h[2]],
+            })
+            .collect_vec();
+        ret.atoms.extend(hydrogen);
+        Ok(ret)
+    }
+
+    /// Rotate the bond between carbon atoms `i` and `j` by 90 degrees (about z, through its
+    /// midpoint), turning the four hexagons around it into a 5-7-7-5 Stone-Wales defect. Both atoms
+    /// must have three carbon neighbors.
+    pub fn with_stone_wales(&self, i: usize, j: usize) -> Result<Self, String> {
+        self.check_sites(&[i, j])?;
+        let bonds = self.bonds(DEFECT_BOND_CUTOFF);
+        let carbon_neighbors = |k: usize| bonds[k].iter().filter(|b| self.is_carbon(b.to)).count();
+        if !self.is_carbon(i) || !self.is_carbon(j) {
+            return Err("Stone-Wales defects need a C-C bond".to_string());
+        }
+        let bond = bonds[i]
+            .iter()
+            .find(|b| b.to == j)
+            .ok_or_else(|| format!("atoms {} and {} are not bonded", i, j))?;
+        if carbon_neighbors(i) != 3 || carbon_neighbors(j) != 3 {
+            return Err("Stone-Wales defects need atoms with three carbon neighbors".to_string());
+        }
+
+        let delta = bond.to_delta(&self.lattice_matrix(), &self.coord_matrix());
+        let mid = self.xyz(i) + delta / 2.0;
+        let rotated = Vector3::new(-delta[1], delta[0], delta[2]);
+
+        let mut ret = self.clone();
+        for &(k, sign) in &[(i, -0.5), (j, 0.5)] {
+            let xyz = mid + sign * rotated;
+            ret.atoms[k].xyz = [xyz[0], xyz[1], xyz[2]];
+        }
+        Ok(ret)
+    }
+
+    /// Add an atom `height` above (along z) the center of the given sites, e.g. one atom for a
+    /// top site, two for a bridge site, or a ring for a hollow site. Sites that are bonded across
+    /// the periodic boundary are taken as their closest images.
+    pub fn with_adatom(&self, sites: &[usize], elem: &str, height: f64) -> Result<Self, String> {
+        self.check_sites(sites)?;
+        let lattice = self.lattice_matrix();
+        let inverse = lattice
+            .try_inverse()
+            .ok_or("lattice vectors are not linearly independent")?;
+
+        let origin = self.xyz(sites[0]);
+        let center = sites
+            .iter()
+            .map(|&i| {
+                let delta = self.xyz(i) - origin;
+                delta - lattice * (inverse * delta).map(f64::round)
+            })
+            .sum::<Vector3<f64>>()
+            / sites.len() as f64
+            + origin;
+
+        let mut ret = self.clone();
+        ret.atoms.push(Atom {
+            elem: elem.to_string(),
+            xyz: [center[0], center[1], center[2] + height],
+        });
+        Ok(ret)
+    }
+
+    /// Permutations of the atoms under every symmetry of a ribbon (periodic along x, with the
+    /// first lattice vector along x): translations along x, mirrors normal to x and y, and their
+    /// combinations. Always includes the identity.
+    pub(crate) fn ribbon_symmetries(&self) -> Vec<Vec<usize>> {
+        let n = self.atoms.len();
+        if n == 0 {
+            return vec![Vec::new()];
+        }
+        let length = self.lattice_vectors[0][0];
+        let center_y = self.atoms.iter().map(|a| a.xyz[1]).sum::<f64>() / n as f64;
+
+        let find = |elem: &str, [x, y, z]: [f64; 3]| {
+            self.atoms.iter().position(|a| {
+                let dx = x - a.xyz[0];
+                a.elem == elem
+                    && (dx - length * (dx / length).round()).abs() < SYMMETRY_TOLERANCE
+                    && (y - a.xyz[1]).abs() < SYMMETRY_TOLERANCE
+                    && (z - a.xyz[2]).abs() < SYMMETRY_TOLERANCE
+            })
+        };
+
+        let first = &self.atoms[0];
+        let mut symmetries = Vec::new();
+        for &x_sign in &[1.0, -1.0] {
+            for &y_sign in &[1.0, -1.0] {
+                // every symmetry maps the first atom to some atom of the same element, which fixes
+                // the translation
+                for target in self.atoms.iter().filter(|a| a.elem == first.elem) {
+                    let shift = target.xyz[0] - x_sign * first.xyz[0];
+                    let permutation = self
+                        .atoms
+                        .iter()
+                        .map(|a| {
+                            let [x, y, z] = a.xyz;
+                            let xyz = [x_sign * x + shift, center_y + y_sign * (y - center_y), z];
+                            find(&a.elem, xyz)
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    if let Some(permutation) = permutation {
+                        symmetries.push(permutation);
+                    }
+                }
+            }
+        }
+        symmetries.into_iter().unique().collect()
+    }
+
+    /// All symmetry-distinct single defects of the given kind (vacancies and adatoms on carbon
+    /// atoms, Stone-Wales rotations of bonds between carbon atoms with three carbon neighbors),
+    /// along with how many equivalent defects each one represents.
+    pub fn distinct_defects(&self, kind: &DefectKind) -> Vec<(Defect, usize)> {
+        let bonds = self.bonds(DEFECT_BOND_CUTOFF);
+        let carbon = (0..self.atoms.len()).filter(|&i| self.is_carbon(i));
+        let candidates: Vec<Vec<usize>> = match kind {
+            DefectKind::Vacancy | DefectKind::Adatom { .. } => carbon.map(|i| vec![i]).collect(),
+            DefectKind::StoneWales => {
+                let interior =
+                    |i: usize| bonds[i].iter().filter(|b| self.is_carbon(b.to)).count() == 3;
+                carbon
+                    .filter(|&i| interior(i))
+                    .flat_map(|i| {
+                        bonds[i]
+                            .iter()
+                            .filter(move |b| b.to > i && interior(b.to))
+                            .map(move |b| vec![i, b.to])
+                    })
+                    .unique()
+                    .collect()
+            }
+        };
+
+        let symmetries = self.ribbon_symmetries();
+        let orbit = |sites: &[usize]| {
+            symmetries
+                .iter()
+                .map(|p| sites.iter().map(|&i| p[i]).sorted().collect_vec())
+                .unique()
+                .collect_vec()
+        };
+
+        candidates
+            .iter()
+            .filter_map(|sites| {
+                let orbit = orbit(sites);
+                // only keep the smallest representative of each orbit
+                match orbit.iter().min() == Some(sites) {
+                    true => Some((kind.defect(sites.clone()).unwrap(), orbit.len())),
+                    false => None,
+                }
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Apply a single defect: "vacancy" removes the atoms at `sites` and re-passivates,
+    /// "stone_wales" rotates the bond between the two `sites`, and "adatom" adds an `element`
+    /// atom `height` above the center of `sites`.
+    #[name = "with_defect"]
+    fn py_with_defect(
+        &self,
+        kind: &str,
+        sites: Vec<usize>,
+        element: Option<&str>,
+        height: Option<f64>,
+    ) -> PyResult<Self> {
+        let defect = DefectKind::from_py(kind, element, height)?
+            .defect(sites)
+            .map_err(PyValueError::new_err)?;
+        self.with_defect(&defect).map_err(PyValueError::new_err)
+    }
+
+    /// All symmetry-distinct single defects of the given kind as (structure, sites, multiplicity).
+    #[name = "distinct_defects"]
+    fn py_distinct_defects(
+        &self,
+        kind: &str,
+        element: Option<&str>,
+        height: Option<f64>,
+    ) -> PyResult<Vec<(Self, Vec<usize>, usize)>> {
+        let kind = DefectKind::from_py(kind, element, height)?;
+        self.distinct_defects(&kind)
+            .into_iter()
+            .map(|(defect, multiplicity)| {
+                let structure = self.with_defect(&defect).map_err(PyValueError::new_err)?;
+                Ok((structure, defect.sites(), multiplicity))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,129 @@
+use super::AtomicStructure;
+use itertools::Itertools;
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+
+/// Cutoff used to find bonds when computing descriptors.
+const DESCRIPTOR_BOND_CUTOFF: f64 = 1.6;
+
+/// 64-bit FNV-1a, used instead of `DefaultHasher` so that labels are stable across Rust versions
+/// (they end up in saved models).
+pub(super) fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl AtomicStructure {
+    /// Names of the values returned by `descriptors`, in order.
+    pub const DESCRIPTOR_NAMES: [&'static str; 7] = [
+        "carbon_density",
+        "hydrogen_density",
+        "hydrogen_fraction",
+        "mean_cc_bond",
+        "edge_carbon_fraction",
+        "mean_coordination",
+        "width",
+    ];
+
+    /// Fixed-length descriptors of a (periodic along x) ribbon structure. Densities are per
+    /// angstrom of periodic length, "edge" carbon atoms are those with fewer than three carbon
+    /// neighbors, and `width` is the extent of the carbon atoms along y.
+    pub fn descriptors(&self) -> Vec<f64> {
+        let bonds = self.bonds(DESCRIPTOR_BOND_CUTOFF);
+        let (lattice, coords) = (self.lattice_matrix(), self.coord_matrix());
+        let is_carbon = |i: usize| self.atoms[i].elem == "C";
+
+        let carbon = (0..self.atoms.len())
+            .filter(|&i| is_carbon(i))
+            .collect_vec();
+        let n_carbon = carbon.len() as f64;
+        let n_hydrogen = self.atoms.iter().filter(|a| a.elem == "H").count() as f64;
+        let length = lattice.column(0).norm();
+
+        let cc_bonds = carbon
+            .iter()
+            .flat_map(|&i| bonds[i].iter().filter(|b| is_carbon(b.to)))
+            .map(|b| b.to_delta(&lattice, &coords).norm())
+            .collect_vec();
+        let edge_carbon = carbon
+            .iter()
+            .filter(|&&i| bonds[i].iter().filter(|b| is_carbon(b.to)).count() < 3)
+            .count();
+        let total_coordination: usize = carbon.iter().map(|&i| bonds[i].len()).sum();
+
+        let carbon_y = carbon.iter().map(|&i| self.atoms[i].xyz[1]);
+        let width = match carbon_y.minmax().into_option() {
+            Some((min, max)) => max - min,
+            None => 0.0,
+        };
+
+        // avoid NaN for structures with no carbon atoms or bonds
+        let ratio = |a: f64, b: f64| if b > 0.0 { a / b } else { 0.0 };
+        vec![
+            n_carbon / length,
+            n_hydrogen / length,
+            ratio(n_hydrogen, n_carbon + n_hydrogen),
+            ratio(cc_bonds.iter().sum(), cc_bonds.len() as f64),
+            ratio(edge_carbon as f64, n_carbon),
+            ratio(total_coordination as f64, n_carbon),
+            width,
+        ]
+    }
+
+    /// Weisfeiler-Lehman subtree features of the (periodic) bond graph: counts of each atom label
+    /// after each of `iterations` rounds of relabeling every atom by its label and the sorted
+    /// labels of its neighbors, starting from the element. Counts are normalized to unit length so
+    /// that supercells of a structure have the same features.
+    pub fn wl_features(&self, iterations: usize) -> BTreeMap<u64, f64> {
+        let bonds = self.bonds(DESCRIPTOR_BOND_CUTOFF);
+        let mut labels = self
+            .atoms
+            .iter()
+            .map(|a| fnv1a(a.elem.bytes()))
+            .collect_vec();
+
+        let mut counts = BTreeMap::new();
+        for iteration in 0..=iterations {
+            for &label in &labels {
+                *counts.entry(label).or_insert(0.0) += 1.0;
+            }
+            if iteration == iterations {
+                break;
+            }
+
+            labels = bonds
+                .iter()
+                .enumerate()
+                .map(|(i, bonds)| {
+                    let neighbors = bonds.iter().map(|b| labels[b.to]).sorted();
+                    let bytes = std::iter::once(labels[i])
+                        .chain(neighbors)
+                        .flat_map(|l| l.to_le_bytes().to_vec());
+                    fnv1a(bytes)
+                })
+                .collect();
+        }
+
+        let norm = counts.values().map(|c| c * c).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            for count in counts.values_mut() {
+                *count /= norm;
+            }
+        }
+        counts
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Structure descriptors as (name, value) pairs, see `DESCRIPTOR_NAMES`.
+    #[name = "descriptors"]
+    fn py_descriptors(&self) -> Vec<(&'static str, f64)> {
+        Self::DESCRIPTOR_NAMES
+            .iter()
+            .copied()
+            .zip(self.descriptors())
+            .collect()
+    }
+}
@@ -0,0 +1,481 @@
+use super::AtomicStructure;
+use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt::{self, Write};
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+use vasp_poscar::Poscar;
+
+/// Atomic masses (in amu) of the elements we expect to see in ribbon (and substrate)
+/// calculations, needed for Quantum ESPRESSO inputs.
+fn atomic_mass(elem: &str) -> Option<f64> {
+    match elem {
+        "H" => Some(1.008),
+        "B" => Some(10.81),
+        "C" => Some(12.011),
+        "N" => Some(14.007),
+        "O" => Some(15.999),
+        "F" => Some(18.998),
+        "Si" => Some(28.085),
+        "S" => Some(32.06),
+        "Ni" => Some(58.693),
+        "Cu" => Some(63.546),
+        "Pd" => Some(106.42),
+        "Ag" => Some(107.868),
+        "Pt" => Some(195.084),
+        "Au" => Some(196.967),
+        _ => None,
+    }
+}
+
+/// Which DFT code to write inputs for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DftCode {
+    /// POSCAR, INCAR, KPOINTS (scf mesh), and a non-self-consistent band structure run in
+    /// `bands/` (POSCAR, INCAR with ICHARG = 11, KPOINTS in line mode), which needs the CHGCAR
+    /// of the scf run copied into it
+    Vasp,
+    /// pw.x inputs scf.in and bands.in
+    QuantumEspresso,
+}
+
+impl std::str::FromStr for DftCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vasp" => Ok(DftCode::Vasp),
+            "qe" | "pw" | "quantum_espresso" => Ok(DftCode::QuantumEspresso),
+            _ => Err(format!("unknown DFT code '{}'", s)),
+        }
+    }
+}
+
+/// Value of a single input setting, formatted as appropriate for each code.
+#[derive(Debug, Clone, PartialEq, FromPyObject)]
+pub enum Setting {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+/// Fill in the `{{key}}` placeholders of a template with `value(key)`.
+fn render(template: &str, value: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let end = start
+            + rest[start..]
+                .find("}}")
+                .ok_or("unterminated '{{' in template")?;
+        let key = rest[start + 2..end].trim();
+        let value = value(key).ok_or_else(|| format!("unknown template placeholder '{}'", key))?;
+        rendered.push_str(&value);
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Write a float the way it would usually appear in an input file, e.g. 400 or 1e-6.
+fn write_float(f: &mut impl Write, v: f64) -> fmt::Result {
+    match v.abs() {
+        x if x != 0.0 && !(1e-3..1e6).contains(&x) => write!(f, "{:e}", v),
+        _ => write!(f, "{}", v),
+    }
+}
+
+impl Setting {
+    fn fmt_incar(&self, f: &mut impl Write) -> fmt::Result {
+        match self {
+            Setting::Bool(true) => write!(f, ".TRUE."),
+            Setting::Bool(false) => write!(f, ".FALSE."),
+            Setting::Int(v) => write!(f, "{}", v),
+            Setting::Float(v) => write_float(f, *v),
+            Setting::Str(v) => write!(f, "{}", v),
+        }
+    }
+
+    fn fmt_namelist(&self, f: &mut impl Write) -> fmt::Result {
+        match self {
+            Setting::Bool(true) => write!(f, ".true."),
+            Setting::Bool(false) => write!(f, ".false."),
+            Setting::Int(v) => write!(f, "{}", v),
+            Setting::Float(v) => write_float(f, *v),
+            Setting::Str(v) => write!(f, "'{}'", v),
+        }
+    }
+}
+
+/// Settings for writing a complete set of DFT inputs for ribbons (periodic along x). The k-point
+/// mesh is Gamma-centered, with as many points along the ribbon as needed for a spacing of at most
+/// `k_spacing` and a single point along the vacuum axes. Band structures follow the path from
+/// Gamma to X along the ribbon.
+///
+/// Templates replace the generated file of the same name (or add a new one, e.g. a job script).
+/// Their `{{key}}` placeholders are filled in with `name`, `num_atoms`, `num_species`,
+/// `kpoint_mesh` ("a b c"), `band_points`, and, for generated files, `default` (the generated
+/// contents). The pw.x inputs also have `cards` (species, cell, positions, and k-points), so a
+/// template can give its own namelists.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DftInputs {
+    pub code: DftCode,
+    /// maximum distance between k-points along the periodic axis (1/angstrom, including the 2π)
+    pub k_spacing: f64,
+    /// number of k-points on the band structure path
+    pub band_points: usize,
+    /// INCAR tags for VASP, or "namelist.key" for Quantum ESPRESSO, added to (or replacing) the
+    /// defaults
+    pub settings: BTreeMap<String, Setting>,
+    /// pseudopotential file for each element (Quantum ESPRESSO only), "<element>.UPF" by default
+    pub pseudopotentials: BTreeMap<String, String>,
+    /// contents of each file (by its path relative to the output directory) with placeholders
+    pub templates: BTreeMap<String, String>,
+}
+
+impl DftInputs {
+    pub fn new(
+        code: DftCode,
+        k_spacing: f64,
+        band_points: usize,
+        settings: BTreeMap<String, Setting>,
+        pseudopotentials: BTreeMap<String, String>,
+        templates: BTreeMap<String, String>,
+    ) -> Result<Self, String> {
+        if !(k_spacing > 0.0 && k_spacing.is_finite()) {
+            return Err("k-point spacing must be positive".to_string());
+        }
+        if band_points < 2 {
+            return Err("need at least two band structure k-points".to_string());
+        }
+        if code == DftCode::QuantumEspresso {
+            if let Some(key) = settings.keys().find(|k| !k.contains('.')) {
+                let msg = format!("setting '{}' should be given as 'namelist.key'", key);
+                return Err(msg);
+            }
+        }
+
+        let inside = |file: &str| {
+            let mut components = Path::new(file).components().peekable();
+            components.peek().is_some() && components.all(|c| matches!(c, Component::Normal(_)))
+        };
+        if let Some(file) = templates.keys().find(|f| !inside(f)) {
+            let msg = format!(
+                "template '{}' must be a path inside the output directory",
+                file
+            );
+            return Err(msg);
+        }
+
+        Ok(Self {
+            code,
+            k_spacing,
+            band_points,
+            settings,
+            pseudopotentials,
+            templates,
+        })
+    }
+
+    /// Gamma-centered k-point mesh, sampled only along the periodic (first) axis.
+    pub fn kpoint_mesh(&self, structure: &AtomicStructure) -> [usize; 3] {
+        let lattice = structure.lattice_matrix();
+        let (a, b, c) = (lattice.column(0), lattice.column(1), lattice.column(2));
+        let volume = a.dot(&b.cross(&c)).abs();
+        let reciprocal_length = 2.0 * std::f64::consts::PI * b.cross(&c).norm() / volume;
+        let n = (reciprocal_length / self.k_spacing).ceil() as usize;
+        [n.max(1), 1, 1]
+    }
+
+    /// INCAR for the scf run, or for the non-self-consistent band structure run, which reads
+    /// the charge density of the scf run.
+    fn incar(&self, name: &str, bands: bool) -> String {
+        let mut tags: BTreeMap<String, Setting> = vec![
+            ("SYSTEM", Setting::Str(name.to_string())),
+            ("PREC", Setting::Str("Accurate".to_string())),
+            ("ENCUT", Setting::Float(400.0)),
+            ("EDIFF", Setting::Float(1e-6)),
+            ("ISMEAR", Setting::Int(0)),
+            ("SIGMA", Setting::Float(0.05)),
+            ("LREAL", Setting::Str("Auto".to_string())),
+            ("LWAVE", Setting::Bool(false)),
+            ("LCHARG", Setting::Bool(!bands)),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        tags.extend(self.settings.clone());
+        // the band structure has to start from the scf charge density, so it can't be overridden
+        if bands {
+            tags.insert("ICHARG".to_string(), Setting::Int(11));
+        }
+
+        let mut incar = String::new();
+        for (key, value) in &tags {
+            write!(incar, "{} = ", key).unwrap();
+            value.fmt_incar(&mut incar).unwrap();
+            incar.push('\n');
+        }
+        incar
+    }
+
+    fn kpoints(&self, structure: &AtomicStructure) -> String {
+        let [a, b, c] = self.kpoint_mesh(structure);
+        format!(
+            "Gamma-centered mesh along the ribbon\n0\nGamma\n{} {} {}\n0 0 0\n",
+            a, b, c
+        )
+    }
+
+    fn band_kpoints(&self) -> String {
+        format!(
+            "Gamma to X along the ribbon\n{}\nLine-mode\nReciprocal\n\
+             0.0 0.0 0.0 ! G\n0.5 0.0 0.0 ! X\n",
+            self.band_points
+        )
+    }
+
+    fn pw_input(
+        &self,
+        structure: &AtomicStructure,
+        name: &str,
+        calculation: &str,
+    ) -> Result<String, String> {
+        let species = structure
+            .atoms
+            .iter()
+            .map(|a| &a.elem)
+            .unique()
+            .collect_vec();
+
+        let mut namelists: BTreeMap<String, BTreeMap<String, Setting>> = BTreeMap::new();
+        let defaults = vec![
+            ("control.prefix", Setting::Str(name.to_string())),
+            ("control.pseudo_dir", Setting::Str("./".to_string())),
+            ("control.outdir", Setting::Str("./tmp".to_string())),
+            ("system.ibrav", Setting::Int(0)),
+            ("system.nat", Setting::Int(structure.atoms.len() as i64)),
+            ("system.ntyp", Setting::Int(species.len() as i64)),
+            ("system.ecutwfc", Setting::Float(40.0)),
+            ("system.ecutrho", Setting::Float(320.0)),
+            ("system.occupations", Setting::Str("smearing".to_string())),
+            ("system.smearing", Setting::Str("gaussian".to_string())),
+            ("system.degauss", Setting::Float(0.005)),
+            ("electrons.conv_thr", Setting::Float(1e-8)),
+        ];
+        // the calculation type depends on the file, so it can't be overridden
+        let fixed = ("control.calculation", Setting::Str(calculation.to_string()));
+        let settings = defaults
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .chain(self.settings.clone())
+            .chain(std::iter::once(fixed).map(|(k, v)| (k.to_string(), v)));
+        for (key, value) in settings {
+            let (namelist, key) = key.split_at(key.find('.').unwrap());
+            namelists
+                .entry(namelist.to_ascii_lowercase())
+                .or_default()
+                .insert(key[1..].to_string(), value);
+        }
+
+        let mut pw = String::new();
+        // pw.x expects the namelists in this order
+        let order = ["control", "system", "electrons", "ions", "cell"];
+        let namelists = namelists
+            .into_iter()
+            .sorted_by_key(|(n, _)| order.iter().position(|o| o == n).unwrap_or(order.len()));
+        for (namelist, values) in namelists {
+            writeln!(pw, "&{}", namelist.to_ascii_uppercase()).unwrap();
+            for (key, value) in values {
+                write!(pw, "  {} = ", key).unwrap();
+                value.fmt_namelist(&mut pw).unwrap();
+                pw.push('\n');
+            }
+            writeln!(pw, "/").unwrap();
+        }
+
+        pw.push_str(&self.pw_cards(structure, calculation)?);
+        Ok(pw)
+    }
+
+    /// Cards of a pw.x input, everything after the namelists.
+    fn pw_cards(&self, structure: &AtomicStructure, calculation: &str) -> Result<String, String> {
+        let species = structure
+            .atoms
+            .iter()
+            .map(|a| &a.elem)
+            .unique()
+            .collect_vec();
+
+        let mut pw = String::new();
+        writeln!(pw, "ATOMIC_SPECIES").unwrap();
+        for elem in species {
+            let mass = atomic_mass(elem).ok_or_else(|| format!("unknown mass for '{}'", elem))?;
+            let pseudo = match self.pseudopotentials.get(elem) {
+                Some(pseudo) => pseudo.clone(),
+                None => format!("{}.UPF", elem),
+            };
+            writeln!(pw, "  {} {} {}", elem, mass, pseudo).unwrap();
+        }
+
+        writeln!(pw, "CELL_PARAMETERS angstrom").unwrap();
+        for [x, y, z] in &structure.lattice_vectors {
+            writeln!(pw, "  {:.10} {:.10} {:.10}", x, y, z).unwrap();
+        }
+        writeln!(pw, "ATOMIC_POSITIONS angstrom").unwrap();
+        for atom in &structure.atoms {
+            let [x, y, z] = atom.xyz;
+            writeln!(pw, "  {} {:.10} {:.10} {:.10}", atom.elem, x, y, z).unwrap();
+        }
+
+        match calculation {
+            "bands" => {
+                writeln!(pw, "K_POINTS crystal_b\n2").unwrap();
+                writeln!(pw, "  0.0 0.0 0.0 {}", self.band_points - 1).unwrap();
+                writeln!(pw, "  0.5 0.0 0.0 1").unwrap();
+            }
+            _ => {
+                let [a, b, c] = self.kpoint_mesh(structure);
+                writeln!(pw, "K_POINTS automatic\n  {} {} {} 0 0 0", a, b, c).unwrap();
+            }
+        }
+        Ok(pw)
+    }
+
+    /// Contents of every input file for the structure, as (path relative to the output
+    /// directory, contents).
+    pub fn files(
+        &self,
+        structure: &AtomicStructure,
+        name: &str,
+    ) -> Result<Vec<(String, String)>, String> {
+        let mut files = match self.code {
+            DftCode::Vasp => {
+                let poscar: Poscar = structure.try_into().map_err(|e| format!("{}", e))?;
+                let mut raw = poscar.into_raw();
+                raw.comment = name.to_string();
+                let poscar = format!("{}", raw.validate().map_err(|e| format!("{}", e))?);
+
+                vec![
+                    ("POSCAR", poscar.clone()),
+                    ("INCAR", self.incar(name, false)),
+                    ("KPOINTS", self.kpoints(structure)),
+                    ("bands/POSCAR", poscar),
+                    ("bands/INCAR", self.incar(name, true)),
+                    ("bands/KPOINTS", self.band_kpoints()),
+                ]
+            }
+            DftCode::QuantumEspresso => vec![
+                ("scf.in", self.pw_input(structure, name, "scf")?),
+                ("bands.in", self.pw_input(structure, name, "bands")?),
+            ],
+        }
+        .into_iter()
+        .map(|(file, contents)| (file.to_string(), contents))
+        .collect_vec();
+
+        let [a, b, c] = self.kpoint_mesh(structure);
+        let num_species = structure.atoms.iter().map(|a| &a.elem).unique().count();
+        for (file, template) in &self.templates {
+            let generated = files.iter().position(|(f, _)| f == file);
+            let cards = match (self.code, file.as_str()) {
+                (DftCode::QuantumEspresso, "scf.in") => Some(self.pw_cards(structure, "scf")?),
+                (DftCode::QuantumEspresso, "bands.in") => Some(self.pw_cards(structure, "bands")?),
+                _ => None,
+            };
+            let contents = render(template, |key| match key {
+                "name" => Some(name.to_string()),
+                "num_atoms" => Some(structure.atoms.len().to_string()),
+                "num_species" => Some(num_species.to_string()),
+                "kpoint_mesh" => Some(format!("{} {} {}", a, b, c)),
+                "band_points" => Some(self.band_points.to_string()),
+                "default" => generated.map(|i| files[i].1.clone()),
+                "cards" => cards.clone(),
+                _ => None,
+            })
+            .map_err(|e| format!("{}: {}", file, e))?;
+
+            match generated {
+                Some(i) => files[i].1 = contents,
+                None => files.push((file.clone(), contents)),
+            }
+        }
+        Ok(files)
+    }
+
+    /// Write every input file for the structure into `directory`, creating it if needed.
+    pub fn write(
+        &self,
+        structure: &AtomicStructure,
+        directory: &Path,
+        name: &str,
+    ) -> io::Result<()> {
+        let files = self
+            .files(structure, name)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        for (file, contents) in files {
+            let path = directory.join(file);
+            fs::create_dir_all(path.parent().unwrap_or(directory))?;
+            fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl DftInputs {
+    /// Input settings for "vasp" (default) or "qe" (pw.x), see `DftInputs`. `k_spacing` defaults
+    /// to 0.15 1/angstrom and `band_points` to 40.
+    #[new]
+    pub fn py_new(
+        code: Option<&str>,
+        k_spacing: Option<f64>,
+        band_points: Option<usize>,
+        settings: Option<BTreeMap<String, Setting>>,
+        pseudopotentials: Option<BTreeMap<String, String>>,
+        templates: Option<BTreeMap<String, String>>,
+    ) -> PyResult<Self> {
+        let code = code
+            .unwrap_or("vasp")
+            .parse()
+            .map_err(PyValueError::new_err)?;
+        Self::new(
+            code,
+            k_spacing.unwrap_or(0.15),
+            band_points.unwrap_or(40),
+            settings.unwrap_or_default(),
+            pseudopotentials.unwrap_or_default(),
+            templates.unwrap_or_default(),
+        )
+        .map_err(PyValueError::new_err)
+    }
+
+    #[name = "kpoint_mesh"]
+    fn py_kpoint_mesh(&self, structure: &AtomicStructure) -> (usize, usize, usize) {
+        let [a, b, c] = self.kpoint_mesh(structure);
+        (a, b, c)
+    }
+
+    /// Contents of every input file as a {file name: contents} dict.
+    #[name = "files"]
+    fn py_files(
+        &self,
+        structure: &AtomicStructure,
+        name: &str,
+    ) -> PyResult<BTreeMap<String, String>> {
+        let files = self.files(structure, name).map_err(PyValueError::new_err)?;
+        Ok(files.into_iter().collect())
+    }
+
+    #[name = "write"]
+    fn py_write(&self, structure: &AtomicStructure, directory: &str, name: &str) -> io::Result<()> {
+        self.write(structure, Path::new(directory), name)
+    }
+}
@@ -0,0 +1,513 @@
+use super::{Atom, AtomicStructure, DftCode};
+use crate::agnr::AGNR;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use roxmltree::{Document, Node};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const HARTREE_TO_EV: f64 = 27.211386245988;
+const BOHR_TO_ANGSTROM: f64 = 0.529177210903;
+
+/// States with an occupation above this (out of 1) count as occupied.
+const OCCUPIED: f64 = 0.5;
+/// Tolerance (eV) when deciding whether the smallest direct gap is the fundamental gap.
+const DIRECT_TOL: f64 = 1e-4;
+
+/// Results parsed from the XML output of a DFT run, either VASP's `vasprun.xml` or Quantum
+/// ESPRESSO's `data-file-schema.xml` (also written as `<prefix>.xml` in `outdir`).
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DftOutput {
+    pub code: DftCode,
+    /// Total (free) energy of the final structure in eV.
+    #[pyo3(get)]
+    pub energy: f64,
+    /// Fermi energy in eV, if the run reported one.
+    #[pyo3(get)]
+    pub fermi_energy: Option<f64>,
+    /// Final structure of the run, in angstroms.
+    #[pyo3(get)]
+    pub structure: AtomicStructure,
+    /// K-points in fractional (reciprocal lattice) coordinates.
+    pub kpoints: Vec<[f64; 3]>,
+    /// Kohn-Sham eigenvalues in eV, indexed by [spin][kpoint][band].
+    #[pyo3(get)]
+    pub eigenvalues: Vec<Vec<Vec<f64>>>,
+    /// Occupations between 0 and 1, same shape as `eigenvalues`.
+    #[pyo3(get)]
+    pub occupations: Vec<Vec<Vec<f64>>>,
+}
+
+/// Band edges of a `DftOutput`. K-points are given as indices into `DftOutput::kpoints`.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BandGap {
+    /// Fundamental gap in eV, zero for metals.
+    #[pyo3(get)]
+    pub gap: f64,
+    /// Smallest gap between occupied and unoccupied states at the same k-point, in eV.
+    #[pyo3(get)]
+    pub direct_gap: f64,
+    /// Whether the fundamental gap is direct, always false for metals.
+    #[pyo3(get)]
+    pub is_direct: bool,
+    /// Valence band maximum (highest occupied state) in eV.
+    #[pyo3(get)]
+    pub vbm: f64,
+    /// Conduction band minimum (lowest unoccupied state) in eV.
+    #[pyo3(get)]
+    pub cbm: f64,
+    #[pyo3(get)]
+    pub vbm_kpoint: usize,
+    #[pyo3(get)]
+    pub cbm_kpoint: usize,
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    tag: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |n| n.is_element() && n.tag_name().name() == tag)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, tag: &'a str) -> Result<Node<'a, 'input>, String> {
+    children(node, tag)
+        .next()
+        .ok_or_else(|| format!("missing <{}> in <{}>", tag, node.tag_name().name()))
+}
+
+/// Child element with the given tag and `name` attribute (how vasprun.xml labels everything).
+fn named<'a, 'input>(
+    node: Node<'a, 'input>,
+    tag: &'a str,
+    name: &str,
+) -> Result<Node<'a, 'input>, String> {
+    children(node, tag)
+        .find(|n| n.attribute("name") == Some(name))
+        .ok_or_else(|| format!("missing <{} name=\"{}\">", tag, name))
+}
+
+fn parse_floats(node: Node) -> Result<Vec<f64>, String> {
+    node.text()
+        .unwrap_or("")
+        .split_whitespace()
+        .map(|v| {
+            v.parse()
+                .map_err(|_| format!("invalid number '{}' in <{}>", v, node.tag_name().name()))
+        })
+        .collect()
+}
+
+fn parse_float(node: Node) -> Result<f64, String> {
+    match *parse_floats(node)? {
+        [value] => Ok(value),
+        _ => Err(format!(
+            "expected one number in <{}>",
+            node.tag_name().name()
+        )),
+    }
+}
+
+fn parse_vec3(node: Node) -> Result<[f64; 3], String> {
+    match *parse_floats(node)? {
+        [x, y, z] => Ok([x, y, z]),
+        _ => Err(format!(
+            "expected 3 numbers in <{}>",
+            node.tag_name().name()
+        )),
+    }
+}
+
+/// Rescale occupations given per spin-degenerate band (between 0 and 2) to between 0 and 1.
+fn normalize_occupations(occupations: &mut [Vec<Vec<f64>>]) {
+    let max = occupations
+        .iter()
+        .flatten()
+        .flatten()
+        .cloned()
+        .fold(0.0, f64::max);
+    if max > 1.0 + 1e-6 {
+        for occ in occupations.iter_mut().flatten().flatten() {
+            *occ /= 2.0;
+        }
+    }
+}
+
+impl DftOutput {
+    /// Parse either kind of XML output, detected from the root element.
+    pub fn from_xml(xml: &str) -> Result<Self, String> {
+        let doc = Document::parse(xml).map_err(|e| format!("invalid XML: {}", e))?;
+        let root = doc.root_element();
+        match root.tag_name().name() {
+            "modeling" => Self::from_vasprun(root),
+            "espresso" => Self::from_qe(root),
+            other => Err(format!("unrecognized DFT output with root <{}>", other)),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let xml = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::from_xml(&xml).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn from_vasprun(root: Node) -> Result<Self, String> {
+        let kpoints = children(named(child(root, "kpoints")?, "varray", "kpointlist")?, "v")
+            .map(parse_vec3)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let atoms = child(named(child(root, "atominfo")?, "array", "atoms")?, "set")?;
+        let species = children(atoms, "rc")
+            .map(|rc| {
+                let elem = child(rc, "c")?.text().unwrap_or("").trim();
+                Ok(elem.to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // runs that were cut short don't have a final structure, so fall back to the last one
+        let structure = children(root, "structure")
+            .find(|n| n.attribute("name") == Some("finalpos"))
+            .or_else(|| {
+                root.descendants()
+                    .filter(|n| n.is_element() && n.tag_name().name() == "structure")
+                    .last()
+            })
+            .ok_or("missing <structure>")?;
+        let structure = Self::vasprun_structure(structure, species)?;
+
+        let calculation = children(root, "calculation")
+            .last()
+            .ok_or("missing <calculation>")?;
+        let energy = parse_float(named(child(calculation, "energy")?, "i", "e_fr_energy")?)?;
+        let fermi_energy = match children(calculation, "dos").next() {
+            Some(dos) => Some(parse_float(named(dos, "i", "efermi")?)?),
+            None => None,
+        };
+
+        let mut eigenvalues = vec![];
+        let mut occupations = vec![];
+        let spins = child(child(child(calculation, "eigenvalues")?, "array")?, "set")?;
+        for spin in children(spins, "set") {
+            let (mut spin_eigs, mut spin_occs) = (vec![], vec![]);
+            for kpoint in children(spin, "set") {
+                let (mut eigs, mut occs) = (vec![], vec![]);
+                for row in children(kpoint, "r") {
+                    match *parse_floats(row)? {
+                        [eig, occ] => {
+                            eigs.push(eig);
+                            occs.push(occ);
+                        }
+                        _ => return Err("expected eigenvalue and occupation in <r>".into()),
+                    }
+                }
+                spin_eigs.push(eigs);
+                spin_occs.push(occs);
+            }
+            eigenvalues.push(spin_eigs);
+            occupations.push(spin_occs);
+        }
+
+        Self::new(
+            DftCode::Vasp,
+            energy,
+            fermi_energy,
+            structure,
+            kpoints,
+            eigenvalues,
+            occupations,
+        )
+    }
+
+    fn vasprun_structure(node: Node, species: Vec<String>) -> Result<AtomicStructure, String> {
+        let basis = children(named(child(node, "crystal")?, "varray", "basis")?, "v")
+            .map(parse_vec3)
+            .collect::<Result<Vec<_>, _>>()?;
+        let lattice_vectors = match *basis {
+            [a, b, c] => [a, b, c],
+            _ => return Err("expected 3 basis vectors".into()),
+        };
+
+        let positions = children(named(node, "varray", "positions")?, "v")
+            .map(parse_vec3)
+            .collect::<Result<Vec<_>, _>>()?;
+        if positions.len() != species.len() {
+            return Err("number of positions and atoms differ".into());
+        }
+
+        let atoms = species
+            .into_iter()
+            .zip(positions)
+            .map(|(elem, frac)| {
+                let mut xyz = [0.0; 3];
+                for (v, &f) in lattice_vectors.iter().zip(&frac) {
+                    for k in 0..3 {
+                        xyz[k] += f * v[k];
+                    }
+                }
+                Atom { elem, xyz }
+            })
+            .collect();
+
+        Ok(AtomicStructure {
+            lattice_vectors,
+            atoms,
+        })
+    }
+
+    fn from_qe(root: Node) -> Result<Self, String> {
+        let output = child(root, "output")?;
+
+        let atomic_structure = child(output, "atomic_structure")?;
+        let alat = atomic_structure
+            .attribute("alat")
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or("missing alat in <atomic_structure>")?;
+        let cell = child(atomic_structure, "cell")?;
+        let bohr_lattice = [
+            parse_vec3(child(cell, "a1")?)?,
+            parse_vec3(child(cell, "a2")?)?,
+            parse_vec3(child(cell, "a3")?)?,
+        ];
+        let to_angstrom = |[x, y, z]: [f64; 3]| {
+            [
+                x * BOHR_TO_ANGSTROM,
+                y * BOHR_TO_ANGSTROM,
+                z * BOHR_TO_ANGSTROM,
+            ]
+        };
+        let atoms = children(child(atomic_structure, "atomic_positions")?, "atom")
+            .map(|atom| {
+                Ok(Atom {
+                    elem: atom
+                        .attribute("name")
+                        .ok_or("missing atom name")?
+                        .to_string(),
+                    xyz: to_angstrom(parse_vec3(atom)?),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let structure = AtomicStructure {
+            lattice_vectors: [
+                to_angstrom(bohr_lattice[0]),
+                to_angstrom(bohr_lattice[1]),
+                to_angstrom(bohr_lattice[2]),
+            ],
+            atoms,
+        };
+
+        let energy = HARTREE_TO_EV * parse_float(child(child(output, "total_energy")?, "etot")?)?;
+
+        let bands = child(output, "band_structure")?;
+        let lsda = match children(bands, "lsda").next() {
+            Some(node) => node.text().map(str::trim) == Some("true"),
+            None => false,
+        };
+        let fermi_energy = match children(bands, "fermi_energy").next() {
+            Some(node) => Some(HARTREE_TO_EV * parse_float(node)?),
+            None => None,
+        };
+        // with spin polarization, each k-point lists all spin up bands, then all spin down ones
+        let nbnd_up = match children(bands, "nbnd_up").next() {
+            Some(node) => Some(parse_float(node)? as usize),
+            None => None,
+        };
+
+        let num_spins = if lsda { 2 } else { 1 };
+        let mut kpoints = vec![];
+        let mut eigenvalues = vec![vec![]; num_spins];
+        let mut occupations = vec![vec![]; num_spins];
+        for ks in children(bands, "ks_energies") {
+            // k-points are cartesian in units of 2 pi / alat
+            let k = parse_vec3(child(ks, "k_point")?)?;
+            let fractional = |a: [f64; 3]| (a[0] * k[0] + a[1] * k[1] + a[2] * k[2]) / alat;
+            kpoints.push([
+                fractional(bohr_lattice[0]),
+                fractional(bohr_lattice[1]),
+                fractional(bohr_lattice[2]),
+            ]);
+
+            let eigs = parse_floats(child(ks, "eigenvalues")?)?;
+            let occs = parse_floats(child(ks, "occupations")?)?;
+            if eigs.len() != occs.len() {
+                return Err("number of eigenvalues and occupations differ".into());
+            }
+            let split = if lsda {
+                nbnd_up.unwrap_or(eigs.len() / 2).min(eigs.len())
+            } else {
+                eigs.len()
+            };
+            let ranges = [0..split, split..eigs.len()];
+            for (spin, range) in ranges.iter().take(num_spins).enumerate() {
+                let spin_eigs = eigs[range.clone()].iter().map(|e| e * HARTREE_TO_EV);
+                eigenvalues[spin].push(spin_eigs.collect());
+                occupations[spin].push(occs[range.clone()].to_vec());
+            }
+        }
+
+        Self::new(
+            DftCode::QuantumEspresso,
+            energy,
+            fermi_energy,
+            structure,
+            kpoints,
+            eigenvalues,
+            occupations,
+        )
+    }
+
+    fn new(
+        code: DftCode,
+        energy: f64,
+        fermi_energy: Option<f64>,
+        structure: AtomicStructure,
+        kpoints: Vec<[f64; 3]>,
+        eigenvalues: Vec<Vec<Vec<f64>>>,
+        mut occupations: Vec<Vec<Vec<f64>>>,
+    ) -> Result<Self, String> {
+        if eigenvalues.is_empty() {
+            return Err("no eigenvalues found".into());
+        }
+        if eigenvalues.iter().any(|spin| spin.len() != kpoints.len()) {
+            return Err("number of k-points and eigenvalue sets differ".into());
+        }
+        if eigenvalues
+            .iter()
+            .flatten()
+            .zip(occupations.iter().flatten())
+            .any(|(eigs, occs)| eigs.len() != occs.len())
+        {
+            return Err("number of eigenvalues and occupations differ".into());
+        }
+        normalize_occupations(&mut occupations);
+
+        Ok(Self {
+            code,
+            energy,
+            fermi_energy,
+            structure,
+            kpoints,
+            eigenvalues,
+            occupations,
+        })
+    }
+
+    /// Band edges from the occupations, or `None` if every state is occupied (or every state is
+    /// empty).
+    pub fn band_gap(&self) -> Option<BandGap> {
+        let mut vbm: Option<(f64, usize)> = None;
+        let mut cbm: Option<(f64, usize)> = None;
+        let mut direct_gap = f64::INFINITY;
+        for k in 0..self.kpoints.len() {
+            let (mut highest, mut lowest) = (f64::NEG_INFINITY, f64::INFINITY);
+            for (eigs, occs) in self.eigenvalues.iter().zip(&self.occupations) {
+                for (&e, &occ) in eigs[k].iter().zip(&occs[k]) {
+                    if occ > OCCUPIED {
+                        highest = highest.max(e);
+                    } else {
+                        lowest = lowest.min(e);
+                    }
+                }
+            }
+
+            if vbm.map_or(true, |(v, _)| highest > v) && highest.is_finite() {
+                vbm = Some((highest, k));
+            }
+            if cbm.map_or(true, |(c, _)| lowest < c) && lowest.is_finite() {
+                cbm = Some((lowest, k));
+            }
+            direct_gap = direct_gap.min(lowest - highest);
+        }
+
+        let ((vbm, vbm_kpoint), (cbm, cbm_kpoint)) = (vbm?, cbm?);
+        let gap = f64::max(cbm - vbm, 0.0);
+        Some(BandGap {
+            gap,
+            direct_gap: direct_gap.max(0.0),
+            is_direct: gap > 0.0 && direct_gap - gap < DIRECT_TOL,
+            vbm,
+            cbm,
+            vbm_kpoint,
+            cbm_kpoint,
+        })
+    }
+
+    /// Scalar labels for training: the total energy (also per atom), the Fermi energy, and the
+    /// band gap properties, with the VBM/CBM k-points given as their fractional coordinate along
+    /// the ribbon.
+    pub fn labels(&self) -> BTreeMap<String, f64> {
+        let mut labels = BTreeMap::new();
+        labels.insert("energy".to_string(), self.energy);
+        let num_atoms = self.structure.atoms.len().max(1) as f64;
+        labels.insert("energy_per_atom".to_string(), self.energy / num_atoms);
+        if let Some(fermi_energy) = self.fermi_energy {
+            labels.insert("fermi_energy".to_string(), fermi_energy);
+        }
+        if let Some(gap) = self.band_gap() {
+            labels.insert("band_gap".to_string(), gap.gap);
+            labels.insert("direct_gap".to_string(), gap.direct_gap);
+            labels.insert("is_direct".to_string(), gap.is_direct as u8 as f64);
+            labels.insert("vbm".to_string(), gap.vbm);
+            labels.insert("cbm".to_string(), gap.cbm);
+            labels.insert("vbm_k".to_string(), self.kpoints[gap.vbm_kpoint][0]);
+            labels.insert("cbm_k".to_string(), self.kpoints[gap.cbm_kpoint][0]);
+        }
+        labels
+    }
+
+    /// The AGNR this run was for. The final structure is recognized if possible (see
+    /// `AGNR::from_structure`), otherwise `name` is tried as an AGNR name, which is how
+    /// `agnr inputs` and `write_dft_inputs` name their directories.
+    pub fn agnr(&self, name: Option<&str>) -> Option<AGNR> {
+        AGNR::from_structure(&self.structure)
+            .or_else(|| AGNR::from_name(name?).filter(AGNR::is_periodic))
+    }
+}
+
+#[pymethods]
+impl DftOutput {
+    /// Parse a `vasprun.xml` or Quantum ESPRESSO XML output file.
+    #[staticmethod]
+    #[name = "read"]
+    fn py_read(path: &str) -> PyResult<Self> {
+        Self::read(Path::new(path)).map_err(PyValueError::new_err)
+    }
+
+    /// Parse the contents of a `vasprun.xml` or Quantum ESPRESSO XML output file.
+    #[staticmethod]
+    #[name = "from_xml"]
+    fn py_from_xml(xml: &str) -> PyResult<Self> {
+        Self::from_xml(xml).map_err(PyValueError::new_err)
+    }
+
+    /// Either "vasp" or "qe".
+    #[getter(code)]
+    fn py_code(&self) -> &'static str {
+        match self.code {
+            DftCode::Vasp => "vasp",
+            DftCode::QuantumEspresso => "qe",
+        }
+    }
+
+    #[getter(kpoints)]
+    fn py_kpoints(&self) -> Vec<(f64, f64, f64)> {
+        self.kpoints.iter().map(|&[x, y, z]| (x, y, z)).collect()
+    }
+
+    #[name = "band_gap"]
+    fn py_band_gap(&self) -> Option<BandGap> {
+        self.band_gap()
+    }
+
+    #[name = "labels"]
+    fn py_labels(&self) -> BTreeMap<String, f64> {
+        self.labels()
+    }
+
+    /// See `DftOutput::agnr`.
+    #[name = "agnr"]
+    fn py_agnr(&self, name: Option<&str>) -> Option<AGNR> {
+        self.agnr(name)
+    }
+}
@@ -0,0 +1,426 @@
+use super::descriptors::fnv1a;
+use super::AtomicStructure;
+use crate::adjacency::graph::CompressedGraph;
+use crate::adjacency::BondMeta;
+use itertools::Itertools;
+use pyo3::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::iter::once;
+
+/// Default cutoff used to find bonds when hashing or comparing structures.
+const GRAPH_BOND_CUTOFF: f64 = 1.6;
+
+/// A lattice vector in units of the lattice vectors, e.g. a bond's image offset.
+type Offset = [i64; 3];
+
+fn offset(meta: &BondMeta) -> Offset {
+    let [a, b, c] = meta.image_offset;
+    [a as i64, b as i64, c as i64]
+}
+
+fn add(a: Offset, b: Offset) -> Offset {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: Offset, b: Offset) -> Offset {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn mix(values: impl IntoIterator<Item = u64>) -> u64 {
+    fnv1a(values.into_iter().flat_map(|v| v.to_le_bytes().to_vec()))
+}
+
+fn num_classes(colors: &[u64]) -> usize {
+    colors.iter().unique().count()
+}
+
+/// One round of Weisfeiler-Lehman relabeling: each atom's new color combines its color with the
+/// sorted colors of its bonded neighbors. Bonds between periodic images of the same atom are
+/// marked, since that doesn't depend on which image is in the cell.
+fn refine(graph: &CompressedGraph<BondMeta>, colors: &[u64]) -> Vec<u64> {
+    graph
+        .vertices()
+        .map(|v| {
+            let neighbors = graph
+                .edges_at(v)
+                .iter()
+                .map(|e| mix(vec![colors[e.to], (e.to == v) as u64]))
+                .sorted();
+            mix(once(colors[v]).chain(neighbors))
+        })
+        .collect()
+}
+
+/// Reduce integer vectors to a basis (in echelon form) of the lattice they span.
+fn lattice_basis(mut vectors: Vec<Offset>) -> Vec<Offset> {
+    let mut basis = vec![];
+    vectors.retain(|&v| v != [0; 3]);
+    for col in 0..3 {
+        loop {
+            // the vector with the smallest nonzero entry in this column is used to reduce the
+            // others, until it's the only one left with a nonzero entry
+            let pivot = vectors
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v[col] != 0)
+                .min_by_key(|(_, v)| v[col].abs())
+                .map(|(i, _)| i);
+            let pivot = match pivot {
+                Some(i) => vectors.swap_remove(i),
+                None => break,
+            };
+
+            let mut reduced = true;
+            for v in &mut vectors {
+                let q = v[col].div_euclid(pivot[col]);
+                *v = sub(*v, [q * pivot[0], q * pivot[1], q * pivot[2]]);
+                reduced &= v[col] == 0;
+            }
+            if reduced {
+                basis.push(pivot);
+                break;
+            }
+            vectors.push(pivot);
+        }
+        vectors.retain(|&v| v != [0; 3]);
+    }
+    basis
+}
+
+fn minor(basis: &[Offset], rows: &[usize], cols: &[usize]) -> i64 {
+    match rows.len() {
+        1 => basis[rows[0]][cols[0]],
+        _ => (0..rows.len())
+            .map(|i| {
+                let sign = if i % 2 == 0 { 1 } else { -1 };
+                let rest = cols.iter().copied().filter(|&c| c != cols[i]).collect_vec();
+                sign * basis[rows[0]][cols[i]] * minor(basis, &rows[1..], &rest)
+            })
+            .sum(),
+    }
+}
+
+/// Invariant factors (the Smith normal form diagonal) of the lattice spanned by `vectors`, one
+/// for each dimension of the span. These don't depend on the choice of lattice vectors.
+fn invariant_factors(vectors: Vec<Offset>) -> Vec<i64> {
+    let basis = lattice_basis(vectors);
+    let mut factors = vec![];
+    let mut previous = 1;
+    for k in 1..=basis.len() {
+        // the product of the first k factors is the gcd of all k by k minors
+        let divisor = (0..basis.len())
+            .combinations(k)
+            .cartesian_product((0..3).combinations(k))
+            .map(|(rows, cols)| minor(&basis, &rows, &cols))
+            .fold(0, gcd);
+        factors.push(divisor / previous);
+        previous = divisor;
+    }
+    factors
+}
+
+/// Image of every atom reached by walking a spanning tree of its component of the bond graph,
+/// starting from the component's lowest atom. Cycles of bonds add up to a lattice vector that
+/// doesn't depend on the choice of images (their "voltage").
+fn tree_images(graph: &CompressedGraph<BondMeta>) -> Vec<Offset> {
+    let mut images = vec![None; graph.n_vertices()];
+    for root in graph.vertices() {
+        if images[root].is_some() {
+            continue;
+        }
+        images[root] = Some([0; 3]);
+        let mut queue = VecDeque::from(vec![root]);
+        while let Some(v) = queue.pop_front() {
+            let image = images[v].unwrap();
+            for e in graph.edges_at(v) {
+                if images[e.to].is_none() {
+                    images[e.to] = Some(add(image, offset(&e.meta)));
+                    queue.push_back(e.to);
+                }
+            }
+        }
+    }
+    images.into_iter().map(Option::unwrap).collect()
+}
+
+/// Maps lattice vectors of one structure to the other by permuting them and flipping their signs.
+#[derive(Debug, Copy, Clone)]
+struct AxisMap {
+    perm: [usize; 3],
+    signs: [i64; 3],
+}
+
+/// Sign of lattice vector `i` for the sign flips encoded in `bits`.
+fn sign(bits: usize, i: usize) -> i64 {
+    if bits & (1 << i) == 0 {
+        1
+    } else {
+        -1
+    }
+}
+
+impl AxisMap {
+    fn all() -> impl Iterator<Item = Self> {
+        (0..3).permutations(3).flat_map(|perm| {
+            (0..8).map(move |bits| Self {
+                perm: [perm[0], perm[1], perm[2]],
+                signs: [sign(bits, 0), sign(bits, 1), sign(bits, 2)],
+            })
+        })
+    }
+
+    fn apply(&self, v: Offset) -> Offset {
+        let (p, s) = (self.perm, self.signs);
+        [s[0] * v[p[0]], s[1] * v[p[1]], s[2] * v[p[2]]]
+    }
+}
+
+/// Backtracking search for an isomorphism between two periodic bond graphs. Atoms are mapped
+/// in breadth-first order, so every atom after the first in each component is bonded to one
+/// that's already mapped, which fixes its image in the other structure.
+struct Matcher<'a> {
+    graphs: [&'a CompressedGraph<BondMeta>; 2],
+    colors: [&'a [u64]; 2],
+    /// atoms of the first graph in the order they're mapped, along with a bond from an already
+    /// mapped atom (`None` for the first atom of each component)
+    order: &'a [(usize, Option<(usize, Offset)>)],
+    axes: AxisMap,
+    mapping: Vec<Option<usize>>,
+    used: Vec<bool>,
+    /// image in the second structure of each mapped atom's image in the cell of the first one
+    shifts: Vec<Offset>,
+}
+
+impl Matcher<'_> {
+    fn search(&mut self, depth: usize) -> bool {
+        let (v, anchor) = match self.order.get(depth) {
+            Some(&next) => next,
+            None => return true,
+        };
+        let [g1, g2] = self.graphs;
+        let color = self.colors[0][v];
+
+        let mut candidates = match anchor {
+            // whole components can be moved to any image, so the first atom stays in the cell
+            None => g2
+                .vertices()
+                .filter(|&w| !self.used[w] && self.colors[1][w] == color)
+                .map(|w| (w, [0; 3]))
+                .collect_vec(),
+            Some((u, bond)) => {
+                let (mapped, shift) = (self.mapping[u].unwrap(), self.shifts[u]);
+                let expected = self.axes.apply(bond);
+                g2.edges_at(mapped)
+                    .iter()
+                    .filter(|e| !self.used[e.to] && self.colors[1][e.to] == color)
+                    .map(|e| (e.to, add(shift, sub(offset(&e.meta), expected))))
+                    .collect_vec()
+            }
+        };
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        for (w, shift) in candidates {
+            self.mapping[v] = Some(w);
+            self.used[w] = true;
+            self.shifts[v] = shift;
+
+            if self.bonds_match(v, g1, g2) && self.search(depth + 1) {
+                return true;
+            }
+            self.mapping[v] = None;
+            self.used[w] = false;
+        }
+        false
+    }
+
+    /// Whether the bonds from `v` to already mapped atoms (including itself) are exactly the
+    /// bonds between their counterparts.
+    fn bonds_match(
+        &self,
+        v: usize,
+        g1: &CompressedGraph<BondMeta>,
+        g2: &CompressedGraph<BondMeta>,
+    ) -> bool {
+        let w = self.mapping[v].unwrap();
+        let expected = g1
+            .edges_at(v)
+            .iter()
+            .filter_map(|e| {
+                let to = self.mapping[e.to]?;
+                let bond = self.axes.apply(offset(&e.meta));
+                Some((to, sub(add(bond, self.shifts[e.to]), self.shifts[v])))
+            })
+            .sorted();
+        let actual = g2
+            .edges_at(w)
+            .iter()
+            .filter(|e| self.used[e.to])
+            .map(|e| (e.to, offset(&e.meta)))
+            .sorted();
+        expected.eq(actual)
+    }
+}
+
+impl AtomicStructure {
+    fn element_colors(&self) -> Vec<u64> {
+        self.atoms.iter().map(|a| fnv1a(a.elem.bytes())).collect()
+    }
+
+    /// Hash of the periodic bond graph with atoms labelled by element, equal for structures that
+    /// are the same material regardless of atom order, the choice of periodic images, relaxation,
+    /// or permuting and reversing the lattice vectors (the same changes `is_isomorphic` allows).
+    /// Atoms are labelled by Weisfeiler-Lehman refinement until the labels stop splitting atoms
+    /// into new classes, then each connected component is hashed along with the invariant
+    /// factors of the lattice spanned by its bond cycles (which tells e.g. ribbons and molecules
+    /// apart). Supercells hash differently from their unit cell. Different hashes mean the
+    /// structures differ, but equal hashes should be confirmed with `is_isomorphic`.
+    pub fn graph_hash(&self, cutoff_distance: f64) -> u64 {
+        let graph = self.graph(cutoff_distance);
+        let mut colors = self.element_colors();
+        loop {
+            let next = refine(&graph, &colors);
+            let stable = num_classes(&next) == num_classes(&colors);
+            colors = next;
+            if stable {
+                break;
+            }
+        }
+
+        let images = tree_images(&graph);
+        let components = graph.connected_components().into_iter().map(|component| {
+            let voltages = component
+                .iter()
+                .flat_map(|&v| {
+                    let images = &images;
+                    graph
+                        .edges_at(v)
+                        .iter()
+                        .map(move |e| sub(add(images[v], offset(&e.meta)), images[e.to]))
+                })
+                .filter(|&voltage| voltage != [0; 3])
+                .collect_vec();
+
+            let colors = component.iter().map(|&v| colors[v]).sorted();
+            let factors = invariant_factors(voltages).into_iter().map(|f| f as u64);
+            // the marker keeps colors and factors from running together
+            mix(colors.chain(once(u64::MAX)).chain(factors))
+        });
+
+        mix(once(self.atoms.len() as u64).chain(components.sorted()))
+    }
+
+    /// Whether two structures have the same periodic bond graph: a one-to-one mapping between
+    /// their atoms that keeps elements and maps every bond, including which periodic image it's
+    /// to, onto a bond of the other structure. Lattice vectors may be permuted and reversed, but
+    /// otherwise have to describe the same cell.
+    pub fn is_isomorphic(&self, other: &Self, cutoff_distance: f64) -> bool {
+        let (g1, g2) = (self.graph(cutoff_distance), other.graph(cutoff_distance));
+        if g1.n_vertices() != g2.n_vertices() || g1.n_edges() != g2.n_edges() {
+            return false;
+        }
+
+        // refine both in lockstep so that colors can be compared between them
+        let (mut c1, mut c2) = (self.element_colors(), other.element_colors());
+        loop {
+            if c1.iter().sorted().ne(c2.iter().sorted()) {
+                return false;
+            }
+            let (next1, next2) = (refine(&g1, &c1), refine(&g2, &c2));
+            let stable = num_classes(&next1) == num_classes(&c1);
+            c1 = next1;
+            c2 = next2;
+            if stable && c1.iter().sorted().eq(c2.iter().sorted()) {
+                break;
+            } else if stable {
+                return false;
+            }
+        }
+
+        let mut order = vec![];
+        let mut seen = vec![false; g1.n_vertices()];
+        for component in g1.connected_components() {
+            let root = component[0];
+            seen[root] = true;
+            order.push((root, None));
+            let mut queue = VecDeque::from(vec![root]);
+            while let Some(u) = queue.pop_front() {
+                for e in g1.edges_at(u) {
+                    if !seen[e.to] {
+                        seen[e.to] = true;
+                        order.push((e.to, Some((u, offset(&e.meta)))));
+                        queue.push_back(e.to);
+                    }
+                }
+            }
+        }
+
+        let n = g1.n_vertices();
+        AxisMap::all().any(|axes| {
+            Matcher {
+                graphs: [&g1, &g2],
+                colors: [&c1, &c2],
+                order: &order,
+                axes,
+                mapping: vec![None; n],
+                used: vec![false; n],
+                shifts: vec![[0; 3]; n],
+            }
+            .search(0)
+        })
+    }
+
+    /// Indices of the first of each group of isomorphic structures, in order. Structures are only
+    /// compared if their hashes match.
+    pub fn unique_structures(structures: &[Self], cutoff_distance: f64) -> Vec<usize> {
+        let mut groups: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut unique = vec![];
+        for (i, structure) in structures.iter().enumerate() {
+            let group = groups
+                .entry(structure.graph_hash(cutoff_distance))
+                .or_default();
+            let is_new = !group
+                .iter()
+                .any(|&j| structures[j].is_isomorphic(structure, cutoff_distance));
+            if is_new {
+                group.push(i);
+                unique.push(i);
+            }
+        }
+        unique
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Hash of the periodic bond graph (bonds are up to `cutoff`, default 1.6 angstroms) as 16
+    /// hex digits, see `AtomicStructure::graph_hash`.
+    #[name = "graph_hash"]
+    fn py_graph_hash(&self, cutoff: Option<f64>) -> String {
+        format!(
+            "{:016x}",
+            self.graph_hash(cutoff.unwrap_or(GRAPH_BOND_CUTOFF))
+        )
+    }
+
+    /// Whether `other` has the same periodic bond graph, see `AtomicStructure::is_isomorphic`.
+    #[name = "is_isomorphic"]
+    fn py_is_isomorphic(&self, other: &AtomicStructure, cutoff: Option<f64>) -> bool {
+        self.is_isomorphic(other, cutoff.unwrap_or(GRAPH_BOND_CUTOFF))
+    }
+
+    /// Indices of the first of each group of isomorphic structures.
+    #[staticmethod]
+    #[name = "unique_structures"]
+    fn py_unique_structures(structures: Vec<AtomicStructure>, cutoff: Option<f64>) -> Vec<usize> {
+        Self::unique_structures(&structures, cutoff.unwrap_or(GRAPH_BOND_CUTOFF))
+    }
+}
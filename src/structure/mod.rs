@@ -1,8 +1,47 @@
+use crate::adjacency::graph::CompressedGraph;
+use crate::adjacency::{
+    calc_bonds, calc_graph, passivate, Bond, BondMeta, Hybridization, PassivationOptions,
+    PassivationReport, CAP,
+};
+use crate::CoordMat;
+use nalgebra::Matrix3;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use vasp_poscar::Poscar;
 
+mod augment;
+mod band_edges;
+mod defects;
+mod descriptors;
+mod dft;
+mod dft_output;
+mod isomorphism;
+mod multilayer;
+mod optics;
 mod poscar;
+#[cfg(feature = "relax")]
+mod relax;
+mod stm;
+mod substrate;
+mod svg;
+mod tight_binding;
+mod xyz;
+
+pub use augment::{Augmented, Transformation};
+pub use band_edges::BandEdges;
+pub use defects::{Defect, DefectKind};
+pub use dft::{DftCode, DftInputs, Setting};
+pub use dft_output::{BandGap, DftOutput};
+pub use multilayer::{Multilayer, Stacking};
+pub use optics::{OpticalSpectrum, OpticsOptions, Polarization};
+#[cfg(feature = "relax")]
+pub use relax::{Minimizer, Relaxation};
+pub use stm::{StmImage, StmOptions};
+pub use substrate::{Substrate, SubstrateMatch};
+pub use svg::SvgOptions;
+pub use tight_binding::TightBinding;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Atom {
@@ -19,6 +58,45 @@ pub struct AtomicStructure {
 
 #[pymethods]
 impl AtomicStructure {
+    #[new]
+    pub fn new(
+        lattice: Vec<Vec<f64>>,
+        types: Vec<String>,
+        coords: Vec<Vec<f64>>,
+    ) -> PyResult<Self> {
+        fn to_vec3(v: &[f64]) -> PyResult<[f64; 3]> {
+            match *v {
+                [x, y, z] => Ok([x, y, z]),
+                _ => Err(PyValueError::new_err("expected a vector with 3 elements")),
+            }
+        }
+
+        if lattice.len() != 3 {
+            return Err(PyValueError::new_err("expected 3 lattice vectors"));
+        }
+        if types.len() != coords.len() {
+            return Err(PyValueError::new_err("number of types and coords differ"));
+        }
+
+        Ok(Self {
+            lattice_vectors: [
+                to_vec3(&lattice[0])?,
+                to_vec3(&lattice[1])?,
+                to_vec3(&lattice[2])?,
+            ],
+            atoms: types
+                .into_iter()
+                .zip(&coords)
+                .map(|(elem, xyz)| {
+                    Ok(Atom {
+                        elem,
+                        xyz: to_vec3(xyz)?,
+                    })
+                })
+                .collect::<PyResult<_>>()?,
+        })
+    }
+
     fn lattice(&self) -> Vec<Vec<f64>> {
         self.lattice_vectors.iter().map(|v| v.to_vec()).collect()
     }
@@ -35,4 +113,143 @@ impl AtomicStructure {
         let poscar: Poscar = self.try_into().unwrap();
         format!("{}", poscar)
     }
+
+    fn to_xyz_string(&self) -> String {
+        let mut xyz = String::new();
+        self.write_xyz(&mut xyz).unwrap();
+        xyz
+    }
+
+    /// Cap undercoordinated atoms with hydrogen, returning the capped structure and a report of
+    /// what was done. By default only carbon is capped, as sp2 with 3 bonds. `rules` adds or
+    /// replaces the rule of each element with (valence, "sp2" or "sp3"), `bond_lengths` sets
+    /// the length of the bond from each element to hydrogen, and bonds are between atoms closer
+    /// than `cutoff_distance` (default 1.6).
+    #[name = "passivate"]
+    fn py_passivate(
+        &self,
+        rules: Option<HashMap<String, (usize, &str)>>,
+        bond_lengths: Option<HashMap<String, f64>>,
+        cutoff_distance: Option<f64>,
+    ) -> PyResult<(Self, PassivationReport)> {
+        let mut options = PassivationOptions::default();
+        for (elem, (valence, hybridization)) in rules.unwrap_or_default() {
+            let hybridization = match hybridization {
+                "sp2" => Hybridization::Sp2,
+                "sp3" => Hybridization::Sp3,
+                other => {
+                    let msg = format!("unknown hybridization '{}'", other);
+                    return Err(PyValueError::new_err(msg));
+                }
+            };
+            options.set_rule(&elem, valence, hybridization);
+        }
+        for (elem, length) in bond_lengths.unwrap_or_default() {
+            options.set_bond_length(&elem, CAP, length);
+        }
+        if let Some(cutoff_distance) = cutoff_distance {
+            options.cutoff_distance = cutoff_distance;
+        }
+        self.passivated(&options).map_err(PyValueError::new_err)
+    }
+}
+
+impl AtomicStructure {
+    /// Cap undercoordinated atoms, see `adjacency::passivate`. The caps are appended after the
+    /// existing atoms.
+    pub fn passivated(
+        &self,
+        options: &PassivationOptions,
+    ) -> Result<(Self, PassivationReport), String> {
+        let elements = self
+            .atoms
+            .iter()
+            .map(|a| a.elem.as_str())
+            .collect::<Vec<_>>();
+        let (caps, report) = passivate(
+            &self.lattice_matrix(),
+            &self.coord_matrix(),
+            &elements,
+            options,
+        )?;
+        let mut ret = self.clone();
+        ret.atoms.extend(caps.iter().map(|h| Atom {
+            elem: CAP.to_string(),
+            xyz: [h[0], h[1], h[2]],
+        }));
+        Ok((ret, report))
+    }
+
+    /// Lattice vectors as the columns of a matrix, as expected by `adjacency`.
+    pub fn lattice_matrix(&self) -> Matrix3<f64> {
+        Matrix3::from_iterator(self.lattice_vectors.iter().flat_map(|v| v.iter().copied()))
+    }
+
+    /// Cartesian atom positions as the columns of a matrix, as expected by `adjacency`.
+    pub fn coord_matrix(&self) -> CoordMat<f64> {
+        CoordMat::from_iterator(
+            self.atoms.len(),
+            self.atoms.iter().flat_map(|a| a.xyz.iter().copied()),
+        )
+    }
+
+    /// Bonds for each atom, see `adjacency::calc_bonds`.
+    pub fn bonds(&self, cutoff_distance: f64) -> Vec<Vec<Bond>> {
+        calc_bonds(
+            &self.lattice_matrix(),
+            &self.coord_matrix(),
+            cutoff_distance,
+        )
+    }
+
+    /// Bond graph with periodic bond metadata, see `adjacency::calc_graph`.
+    pub fn graph(&self, cutoff_distance: f64) -> CompressedGraph<BondMeta> {
+        calc_graph(
+            &self.lattice_matrix(),
+            &self.coord_matrix(),
+            cutoff_distance,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adjacency::{bond_vector, wraps_boundary};
+    use nalgebra::Vector3;
+
+    /// Primitive cell of graphene, with its atoms at 1/3 and 2/3 along the diagonal of the cell.
+    fn graphene() -> AtomicStructure {
+        let a = 1.42045 * f64::sqrt(3.0);
+        let (a1, a2) = ([a, 0.0, 0.0], [a / 2.0, a * f64::sqrt(3.0) / 2.0, 0.0]);
+        let site = |f: f64| Atom {
+            elem: "C".to_string(),
+            xyz: [f * (a1[0] + a2[0]), f * (a1[1] + a2[1]), 7.5],
+        };
+        AtomicStructure {
+            lattice_vectors: [a1, a2, [0.0, 0.0, 15.0]],
+            atoms: vec![site(1.0 / 3.0), site(2.0 / 3.0)],
+        }
+    }
+
+    #[test]
+    fn graphene_graph() {
+        let graph = graphene().graph(1.6);
+        assert_eq!(graph.n_vertices(), 2);
+
+        for atom in 0..2 {
+            let edges = graph.edges_at(atom);
+            // three neighbors, two of which are across the boundary of the primitive cell
+            assert_eq!(edges.len(), 3);
+            assert!(edges.iter().all(|e| e.to == 1 - atom));
+            assert_eq!(edges.iter().filter(|e| wraps_boundary(e)).count(), 2);
+
+            // bonds are 120 degrees apart, so they add up to nothing
+            let sum: Vector3<f64> = edges.iter().map(bond_vector).sum();
+            assert!(sum.norm() < 1e-9);
+            for edge in edges {
+                assert!((edge.meta.length - 1.42045).abs() < 1e-9);
+            }
+        }
+    }
 }
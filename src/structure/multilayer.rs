@@ -0,0 +1,340 @@
+use super::{Atom, AtomicStructure};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fmt::{self, Write};
+
+/// Largest number of periodic images of a single layer considered when looking for a common
+/// periodic length.
+const MAX_LAYER_REPEATS: usize = 100;
+/// Tolerance (in angstroms) when matching periodic lengths.
+const LENGTH_TOLERANCE: f64 = 1e-4;
+/// Longest distance (in angstroms) considered a bond when measuring a layer's C-C bond length.
+const BOND_CUTOFF: f64 = 1.6;
+
+/// How successive layers are shifted in-plane relative to the first.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Stacking {
+    /// Every layer directly above the first.
+    AA,
+    /// Every other layer shifted by `shift` (one C-C bond along x for Bernal stacking).
+    AB { shift: [f64; 2] },
+    /// Layer `i` shifted by `i * shift`.
+    Offset { shift: [f64; 2] },
+}
+
+impl Stacking {
+    pub fn shift(&self, layer: usize) -> [f64; 2] {
+        match *self {
+            Stacking::AA => [0.0, 0.0],
+            Stacking::AB { shift } if layer % 2 == 1 => shift,
+            Stacking::AB { .. } => [0.0, 0.0],
+            Stacking::Offset { shift: [x, y] } => [layer as f64 * x, layer as f64 * y],
+        }
+    }
+
+    /// Bernal stacking on top of `layer`, shifting every other layer along x by the layer's
+    /// shortest C-C bond, so it matches however the layer was built.
+    pub fn bernal(layer: &AtomicStructure) -> Result<Self, String> {
+        let lattice = layer.lattice_matrix();
+        let coords = layer.coord_matrix();
+        let is_carbon = |i: usize| layer.atoms[i].elem == "C";
+        let cc_bond = layer
+            .bonds(BOND_CUTOFF)
+            .iter()
+            .flatten()
+            .filter(|bond| is_carbon(bond.from) && is_carbon(bond.to))
+            .map(|bond| bond.to_delta(&lattice, &coords).norm())
+            .fold(f64::INFINITY, f64::min);
+        if !cc_bond.is_finite() {
+            return Err("no C-C bonds to derive the AB shift from, give a shift".to_string());
+        }
+        Ok(Stacking::AB {
+            shift: [cc_bond, 0.0],
+        })
+    }
+}
+
+/// A single layer of a `Multilayer`, as needed to describe it in a `layers.yaml` file.
+#[derive(Debug, Clone, PartialEq)]
+struct LayerInfo {
+    /// in-plane sites of the layer's own (unrepeated) cell, already centered in the full cell
+    sites: Vec<[f64; 2]>,
+    species: Vec<String>,
+    length: f64,
+    repeats: usize,
+    shift: [f64; 2],
+}
+
+/// Several periodic (along x) layers stacked along z, sharing a common periodic length.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Multilayer {
+    #[pyo3(get)]
+    pub structure: AtomicStructure,
+    /// Index of the layer that each atom in `structure` belongs to.
+    #[pyo3(get)]
+    pub layer_indices: Vec<usize>,
+    #[pyo3(get)]
+    pub layer_sep: f64,
+    #[pyo3(get)]
+    pub vacuum_sep: f64,
+    layers: Vec<LayerInfo>,
+}
+
+/// Smallest number of repeats of each length such that all of them are the same.
+fn common_repeats(lengths: &[f64]) -> Option<Vec<usize>> {
+    (1..=MAX_LAYER_REPEATS).find_map(|n| {
+        let target = n as f64 * lengths[0];
+        lengths
+            .iter()
+            .map(|&length| {
+                let repeats = (target / length).round();
+                match repeats >= 1.0 && (repeats * length - target).abs() < LENGTH_TOLERANCE {
+                    true => Some(repeats as usize),
+                    false => None,
+                }
+            })
+            .collect()
+    })
+}
+
+impl AtomicStructure {
+    /// Supercell with `repeats` copies of the structure along the first lattice vector.
+    pub fn repeated_x(&self, repeats: usize) -> Self {
+        let a = self.lattice_vectors[0];
+        let mut ret = self.clone();
+        ret.lattice_vectors[0] = [
+            repeats as f64 * a[0],
+            repeats as f64 * a[1],
+            repeats as f64 * a[2],
+        ];
+        ret.atoms = (0..repeats)
+            .flat_map(|r| {
+                let r = r as f64;
+                self.atoms.iter().map(move |atom| Atom {
+                    elem: atom.elem.clone(),
+                    xyz: [
+                        atom.xyz[0] + r * a[0],
+                        atom.xyz[1] + r * a[1],
+                        atom.xyz[2] + r * a[2],
+                    ],
+                })
+            })
+            .collect();
+        ret
+    }
+}
+
+impl Multilayer {
+    /// Stack layers (each periodic along x, with an orthogonal cell like those built from AGNRs)
+    /// along z with the given interlayer distance. Layers with different periodic lengths are
+    /// repeated up to their least common multiple, and every layer is centered across the
+    /// ribbon (along y) before applying the stacking shift.
+    pub fn new(
+        layers: &[AtomicStructure],
+        stacking: Stacking,
+        layer_sep: f64,
+        vacuum_sep: f64,
+    ) -> Result<Self, String> {
+        if layers.is_empty() {
+            return Err("need at least one layer".to_string());
+        }
+        if layers.iter().any(|s| s.atoms.is_empty()) {
+            return Err("layers must contain at least one atom".to_string());
+        }
+        if !(layer_sep > 0.0 && layer_sep.is_finite()) {
+            return Err(format!(
+                "layer separation must be finite and positive, got {}",
+                layer_sep
+            ));
+        }
+        if !(vacuum_sep >= 0.0 && vacuum_sep.is_finite()) {
+            return Err(format!(
+                "vacuum separation must be finite and non-negative, got {}",
+                vacuum_sep
+            ));
+        }
+        let is_orthogonal = |s: &AtomicStructure| {
+            let [a, b, c] = s.lattice_vectors;
+            [a[1], a[2], b[0], b[2], c[0], c[1]]
+                .iter()
+                .all(|x| x.abs() < LENGTH_TOLERANCE)
+        };
+        if !layers.iter().all(is_orthogonal) {
+            return Err("layers must have orthogonal (diagonal) lattices".to_string());
+        }
+
+        let lengths: Vec<f64> = layers.iter().map(|s| s.lattice_vectors[0][0]).collect();
+        let repeats = common_repeats(&lengths).ok_or_else(|| {
+            format!(
+                "no common periodic length within {} repeats of each layer",
+                MAX_LAYER_REPEATS
+            )
+        })?;
+
+        let width = layers
+            .iter()
+            .map(|s| s.lattice_vectors[1][1])
+            .fold(0.0, f64::max);
+        let n_layers = layers.len();
+        let height = (n_layers - 1) as f64 * layer_sep + vacuum_sep;
+
+        let mut structure = AtomicStructure {
+            lattice_vectors: [
+                [repeats[0] as f64 * lengths[0], 0.0, 0.0],
+                [0.0, width, 0.0],
+                [0.0, 0.0, height],
+            ],
+            atoms: Vec::new(),
+        };
+        let mut layer_indices = Vec::new();
+        let mut infos = Vec::new();
+
+        for (i, layer) in layers.iter().enumerate() {
+            let mean = |k: usize| {
+                layer.atoms.iter().map(|a| a.xyz[k]).sum::<f64>() / layer.atoms.len() as f64
+            };
+            let (center_y, center_z) = (mean(1), mean(2));
+            let z = vacuum_sep / 2.0 + i as f64 * layer_sep;
+
+            let centered = AtomicStructure {
+                lattice_vectors: layer.lattice_vectors,
+                atoms: layer
+                    .atoms
+                    .iter()
+                    .map(|atom| Atom {
+                        elem: atom.elem.clone(),
+                        xyz: [
+                            atom.xyz[0],
+                            atom.xyz[1] - center_y + width / 2.0,
+                            atom.xyz[2] - center_z + z,
+                        ],
+                    })
+                    .collect(),
+            };
+
+            let shift = stacking.shift(i);
+            let shifted = centered
+                .repeated_x(repeats[i])
+                .atoms
+                .into_iter()
+                .map(|atom| Atom {
+                    xyz: [atom.xyz[0] + shift[0], atom.xyz[1] + shift[1], atom.xyz[2]],
+                    ..atom
+                });
+            structure.atoms.extend(shifted);
+            layer_indices.extend(vec![i; layer.atoms.len() * repeats[i]]);
+
+            infos.push(LayerInfo {
+                sites: centered
+                    .atoms
+                    .iter()
+                    .map(|a| [a.xyz[0], a.xyz[1]])
+                    .collect(),
+                species: centered.atoms.iter().map(|a| a.elem.clone()).collect(),
+                length: lengths[i],
+                repeats: repeats[i],
+                shift,
+            });
+        }
+
+        Ok(Self {
+            structure,
+            layer_indices,
+            layer_sep,
+            vacuum_sep,
+            layers: infos,
+        })
+    }
+
+    /// Write the layer description in rsp2's `layers.yaml` format. Sites are cartesian (with
+    /// `a: 1.0`) and the stacking shift is fractional in each layer's own lattice. rsp2 has no
+    /// notion of species here, so they are written under an extra `species` key that it ignores.
+    pub fn write_layers_yaml<W: Write>(&self, w: &mut W) -> fmt::Result {
+        let [a, b, _] = self.structure.lattice_vectors;
+        writeln!(w, "a: 1.0")?;
+        writeln!(w, "lattice:")?;
+        writeln!(w, "- [{:.8}, {:.8}]", a[0], a[1])?;
+        writeln!(w, "- [{:.8}, {:.8}]", b[0], b[1])?;
+        writeln!(w, "layer-sep: {:.8}", self.layer_sep)?;
+        writeln!(w, "vacuum-sep: {:.8}", self.vacuum_sep)?;
+        writeln!(w, "layer:")?;
+        for layer in &self.layers {
+            writeln!(w, "- cart-lattice:")?;
+            writeln!(w, "  - [{:.8}, 0.0]", layer.length)?;
+            writeln!(w, "  - [0.0, {:.8}]", b[1])?;
+            writeln!(w, "  cart-sites:")?;
+            for [x, y] in &layer.sites {
+                writeln!(w, "  - [{:.8}, {:.8}]", x, y)?;
+            }
+            writeln!(w, "  species: [{}]", layer.species.join(", "))?;
+            writeln!(w, "  repeat: [{}, 1]", layer.repeats)?;
+            writeln!(
+                w,
+                "  shift: [{:.8}, {:.8}]",
+                layer.shift[0] / layer.length,
+                layer.shift[1] / b[1]
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl Multilayer {
+    /// Stack structures along z, see `Multilayer::new`. Stacking is one of "ab" (default, every
+    /// other layer shifted by `shift`, by default one C-C bond of the bottom layer along x, see
+    /// `Stacking::bernal`), "aa", or "offset" (layer `i` shifted by `i * shift`).
+    #[new]
+    pub fn py_new(
+        layers: Vec<AtomicStructure>,
+        stacking: Option<&str>,
+        layer_sep: Option<f64>,
+        shift: Option<(f64, f64)>,
+        vacuum_sep: Option<f64>,
+    ) -> PyResult<Self> {
+        let shift = shift.map(|(x, y)| [x, y]);
+        let stacking = match (stacking.unwrap_or("ab"), shift) {
+            ("aa", _) => Stacking::AA,
+            ("ab", Some(shift)) => Stacking::AB { shift },
+            ("ab", None) => match layers.first() {
+                Some(bottom) => Stacking::bernal(bottom).map_err(PyValueError::new_err)?,
+                // no layers at all, which `Multilayer::new` reports
+                None => Stacking::AA,
+            },
+            ("offset", Some(shift)) => Stacking::Offset { shift },
+            ("offset", None) => {
+                return Err(PyValueError::new_err("offset stacking requires a shift"));
+            }
+            (other, _) => {
+                let msg = format!("unknown stacking '{}'", other);
+                return Err(PyValueError::new_err(msg));
+            }
+        };
+
+        Self::new(
+            &layers,
+            stacking,
+            layer_sep.unwrap_or(3.35),
+            vacuum_sep.unwrap_or(15.0),
+        )
+        .map_err(PyValueError::new_err)
+    }
+
+    #[getter]
+    fn num_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Number of copies of each layer's own cell in the common periodic cell.
+    #[getter]
+    fn layer_repeats(&self) -> Vec<usize> {
+        self.layers.iter().map(|l| l.repeats).collect()
+    }
+
+    fn layers_yaml(&self) -> String {
+        let mut yaml = String::new();
+        self.write_layers_yaml(&mut yaml).unwrap();
+        yaml
+    }
+}
@@ -0,0 +1,234 @@
+use super::tight_binding::{TightBinding, DEFAULT_HOPPING};
+use super::AtomicStructure;
+use itertools::{iproduct, Itertools};
+use nalgebra::Vector3;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::f64::consts::PI;
+
+/// Transitions with a smaller energy (eV) are degenerate states, not optical transitions.
+const MIN_TRANSITION_ENERGY: f64 = 1e-6;
+/// Transitions weaker than this fraction of the strongest one are dark.
+const BRIGHT_THRESHOLD: f64 = 0.01;
+/// Bright transitions closer than this (eV) are reported once.
+const MERGE_TOLERANCE: f64 = 1e-3;
+
+/// Direction of the electric field of the incoming light.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Polarization {
+    /// Along the periodic axis (the first lattice vector).
+    Along,
+    /// In the plane of the ribbon, perpendicular to the periodic axis.
+    Across,
+}
+
+impl Polarization {
+    /// Unit vector of the field, assuming the ribbon lies in the plane of the first two lattice
+    /// vectors.
+    pub fn direction(&self, structure: &AtomicStructure) -> Vector3<f64> {
+        let a = Vector3::from(structure.lattice_vectors[0]).normalize();
+        match self {
+            Polarization::Along => a,
+            Polarization::Across => {
+                let b = Vector3::from(structure.lattice_vectors[1]);
+                (b - a * a.dot(&b)).normalize()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpticsOptions {
+    pub polarization: Polarization,
+    /// Number of k-points in the uniform mesh over the Brillouin zone.
+    pub num_kpoints: usize,
+    /// Half width at half maximum of the Lorentzian broadening, in eV.
+    pub broadening: f64,
+    /// The spectrum is evaluated at `num_energies` evenly spaced energies from 0 to `max_energy`.
+    pub max_energy: f64,
+    pub num_energies: usize,
+    /// Nearest-neighbor hopping of the pz tight-binding model, in eV.
+    pub hopping: f64,
+    /// Number of bright transitions to report.
+    pub num_transitions: usize,
+}
+
+impl Default for OpticsOptions {
+    fn default() -> Self {
+        Self {
+            polarization: Polarization::Along,
+            num_kpoints: 200,
+            broadening: 0.05,
+            max_energy: 6.0,
+            num_energies: 601,
+            hopping: DEFAULT_HOPPING,
+            num_transitions: 4,
+        }
+    }
+}
+
+/// Independent-particle absorption spectrum of a periodic ribbon.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpticalSpectrum {
+    /// Photon energies in eV.
+    #[pyo3(get)]
+    pub energies: Vec<f64>,
+    /// Absorption at each energy, proportional to omega * epsilon_2 (arbitrary units).
+    #[pyo3(get)]
+    pub absorption: Vec<f64>,
+    /// Lowest bright transitions as (energy in eV, strength relative to the strongest transition),
+    /// ordered by energy.
+    #[pyo3(get)]
+    pub bright_transitions: Vec<(f64, f64)>,
+}
+
+/// Vertical transition from valence band `v` to conduction band `c` at one k-point.
+#[derive(Debug, Copy, Clone)]
+struct Transition {
+    energy: f64,
+    /// |<c|e.dH/dk|v>|^2 / energy
+    strength: f64,
+}
+
+/// Index of the k-points before and after `i` on the periodic mesh.
+fn mesh_neighbors(i: usize, n: usize) -> (usize, usize) {
+    ((i + n - 1) % n, (i + 1) % n)
+}
+
+impl AtomicStructure {
+    /// Optical absorption of the pi electrons in the independent-particle approximation, using
+    /// momentum matrix elements from the nearest-neighbor pz tight-binding model. The structure
+    /// must be periodic along its first lattice vector.
+    pub fn optical_absorption(&self, options: &OpticsOptions) -> Result<OpticalSpectrum, String> {
+        if options.num_kpoints == 0 {
+            return Err("need at least one k-point".into());
+        }
+        if options.num_energies < 2 {
+            return Err("need at least two energies".into());
+        }
+        if !(options.broadening > 0.0 && options.max_energy > 0.0 && options.hopping > 0.0) {
+            return Err("broadening, maximum energy and hopping must be positive".into());
+        }
+
+        let tb = TightBinding::new(self, options.hopping)?;
+        let direction = options.polarization.direction(self);
+        let occupied = tb.num_occupied();
+        let num_bands = tb.num_orbitals;
+
+        // transitions[k][v][c - occupied]
+        let transitions = tb
+            .kpoint_mesh(options.num_kpoints)
+            .into_iter()
+            .map(|k| {
+                let (energies, states) = tb.bands(k);
+                let momentum =
+                    states.adjoint() * tb.hamiltonian_derivative(k, &direction) * &states;
+                (0..occupied)
+                    .map(|v| {
+                        (occupied..num_bands)
+                            .map(|c| {
+                                let energy = energies[c] - energies[v];
+                                let strength = match energy > MIN_TRANSITION_ENERGY {
+                                    true => momentum[(c, v)].norm_sqr() / energy,
+                                    false => 0.0,
+                                };
+                                Transition { energy, strength }
+                            })
+                            .collect_vec()
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        let all = || transitions.iter().flatten().flatten();
+
+        let step = options.max_energy / (options.num_energies - 1) as f64;
+        let energies = (0..options.num_energies)
+            .map(|i| i as f64 * step)
+            .collect_vec();
+        let gamma = options.broadening;
+        let norm = 1.0 / (options.num_kpoints as f64 * tb.length);
+        let absorption = energies
+            .iter()
+            .map(|&x| {
+                let sum: f64 = all()
+                    .map(|t| t.strength * (gamma / PI) / ((x - t.energy).powi(2) + gamma * gamma))
+                    .sum();
+                norm * sum
+            })
+            .collect();
+
+        // bright transitions are band edges of the joint density of states, i.e. local minima of
+        // the transition energy over the k-mesh for a pair of bands, that have enough strength
+        let max_strength = all().map(|t| t.strength).fold(0.0, f64::max);
+        let n = options.num_kpoints;
+        let mut bright = vec![];
+        for (v, c, i) in iproduct!(0..occupied, 0..num_bands - occupied, 0..n) {
+            let (before, after) = mesh_neighbors(i, n);
+            let t = transitions[i][v][c];
+            let is_minimum = t.energy <= transitions[before][v][c].energy
+                && t.energy <= transitions[after][v][c].energy;
+            if is_minimum && max_strength > 0.0 && t.strength >= BRIGHT_THRESHOLD * max_strength {
+                bright.push((t.energy, t.strength / max_strength));
+            }
+        }
+        // NaN energies fail the comparisons above, so they're never bright
+        bright.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        // merge (nearly) degenerate transitions, e.g. at k and -k, keeping the strongest
+        let mut bright_transitions: Vec<(f64, f64)> = vec![];
+        for (energy, strength) in bright {
+            match bright_transitions.last_mut() {
+                Some(last) if energy - last.0 < MERGE_TOLERANCE => last.1 = last.1.max(strength),
+                _ => bright_transitions.push((energy, strength)),
+            }
+        }
+        bright_transitions.truncate(options.num_transitions);
+
+        Ok(OpticalSpectrum {
+            energies,
+            absorption,
+            bright_transitions,
+        })
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Independent-particle optical absorption from the pz tight-binding model, see
+    /// `AtomicStructure::optical_absorption`. Polarization is either "along" (default) or
+    /// "across" the periodic axis.
+    #[name = "optical_absorption"]
+    #[allow(clippy::too_many_arguments)]
+    fn py_optical_absorption(
+        &self,
+        polarization: Option<&str>,
+        num_kpoints: Option<usize>,
+        broadening: Option<f64>,
+        max_energy: Option<f64>,
+        num_energies: Option<usize>,
+        hopping: Option<f64>,
+        num_transitions: Option<usize>,
+    ) -> PyResult<OpticalSpectrum> {
+        let defaults = OpticsOptions::default();
+        let polarization = match polarization.unwrap_or("along") {
+            "along" => Polarization::Along,
+            "across" => Polarization::Across,
+            other => {
+                let msg = format!("unknown polarization '{}'", other);
+                return Err(PyValueError::new_err(msg));
+            }
+        };
+        let options = OpticsOptions {
+            polarization,
+            num_kpoints: num_kpoints.unwrap_or(defaults.num_kpoints),
+            broadening: broadening.unwrap_or(defaults.broadening),
+            max_energy: max_energy.unwrap_or(defaults.max_energy),
+            num_energies: num_energies.unwrap_or(defaults.num_energies),
+            hopping: hopping.unwrap_or(defaults.hopping),
+            num_transitions: num_transitions.unwrap_or(defaults.num_transitions),
+        };
+        self.optical_absorption(&options)
+            .map_err(PyValueError::new_err)
+    }
+}
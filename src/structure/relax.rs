@@ -0,0 +1,221 @@
+use super::AtomicStructure;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use rsp2_array_types::V3;
+use rsp2_minimize::cg::AlgorithmState;
+use rsp2_potentials::rebo::nonreactive::{self as rebo, Params};
+use rsp2_structure::{Coords, CoordsKind, Element, Lattice};
+
+/// Maximum timestep used by FIRE, in its (unitless) time units.
+const FIRE_TIMESTEP_MAX: f64 = 0.1;
+/// Initial and maximum step guesses for the ACGSD linesearch (Å), same as rsp2's defaults.
+const ACGSD_ALPHA_GUESS_FIRST: f64 = 0.01;
+const ACGSD_ALPHA_GUESS_MAX: f64 = 0.1;
+
+/// Algorithm used to minimize the energy.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Minimizer {
+    Acgsd,
+    Fire,
+}
+
+/// Result of relaxing a structure.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone)]
+pub struct Relaxation {
+    #[pyo3(get)]
+    pub structure: AtomicStructure,
+    /// Final potential energy (eV).
+    #[pyo3(get)]
+    pub energy: f64,
+    /// Largest force on any atom (or the cell boundary, if relaxed) in eV/Å.
+    #[pyo3(get)]
+    pub max_force: f64,
+    #[pyo3(get)]
+    pub iterations: u64,
+    #[pyo3(get)]
+    pub converged: bool,
+}
+
+/// Energy and gradient of a structure using REBO, with the positions given as a flat array in
+/// the reference cell. If `relax_cell_x` is set, the last element of `position` is the change in
+/// length of the periodic (x) axis, applied as a uniform strain to both the lattice and the atoms.
+/// Its gradient is then the force on the cell boundary (dE/dL).
+struct RelaxFn<'a> {
+    reference: &'a AtomicStructure,
+    elements: Vec<Element>,
+    params: Params,
+    relax_cell_x: bool,
+}
+
+impl RelaxFn<'_> {
+    fn cell_length(&self) -> f64 {
+        self.reference.lattice_vectors[0][0]
+    }
+
+    /// Scale factor along x relative to the reference cell.
+    fn scale(&self, position: &[f64]) -> f64 {
+        match self.relax_cell_x {
+            true => 1.0 + position.last().unwrap() / self.cell_length(),
+            false => 1.0,
+        }
+    }
+
+    fn structure(&self, position: &[f64]) -> AtomicStructure {
+        let scale = self.scale(position);
+        let mut structure = self.reference.clone();
+        structure.lattice_vectors[0][0] *= scale;
+        for (atom, xyz) in structure.atoms.iter_mut().zip(position.chunks_exact(3)) {
+            atom.xyz = [scale * xyz[0], xyz[1], xyz[2]];
+        }
+        structure
+    }
+
+    fn compute(&self, position: &[f64]) -> Result<(f64, Vec<f64>), String> {
+        let structure = self.structure(position);
+        let lattice = Lattice::from_vectors(&[
+            V3(structure.lattice_vectors[0]),
+            V3(structure.lattice_vectors[1]),
+            V3(structure.lattice_vectors[2]),
+        ]);
+        let carts = structure.atoms.iter().map(|a| V3(a.xyz)).collect();
+        let coords = Coords::new(lattice, CoordsKind::Carts(carts));
+
+        let interactions = rebo::find_all_interactions(&self.params, &coords, &self.elements)
+            .map_err(|e| e.to_string())?;
+        let (energy, bond_grads) =
+            rebo::compute_by_bond(&self.params, &interactions, &coords, false)
+                .map_err(|e| e.to_string())?;
+
+        // chain rule to get the gradient with respect to the unstrained positions and cell length
+        let scale = self.scale(position);
+        let mut gradient = vec![0.0; position.len()];
+        for bond in &bond_grads {
+            for k in 0..3 {
+                gradient[3 * bond.plus_site + k] += bond.grad[k];
+                gradient[3 * bond.minus_site + k] -= bond.grad[k];
+            }
+            if self.relax_cell_x {
+                *gradient.last_mut().unwrap() +=
+                    bond.grad[0] * bond.cart_vector[0] / (scale * self.cell_length());
+            }
+        }
+        for atom_grad in gradient.chunks_exact_mut(3) {
+            atom_grad[0] *= scale;
+        }
+
+        Ok((energy, gradient))
+    }
+}
+
+/// Largest force on an atom (or the cell boundary) for the given (flat) gradient.
+fn max_force(gradient: &[f64]) -> f64 {
+    let atom_forces = gradient
+        .chunks_exact(3)
+        .map(|g| f64::sqrt(g[0] * g[0] + g[1] * g[1] + g[2] * g[2]));
+    let cell_force = gradient.chunks_exact(3).remainder().iter().map(|g| g.abs());
+    atom_forces.chain(cell_force).fold(0.0, f64::max)
+}
+
+impl AtomicStructure {
+    /// Relax the structure using the REBO potential until the largest force on any atom is below
+    /// `fmax` (eV/Å). If `relax_cell_x` is set, the length of the periodic (x) axis is relaxed as
+    /// well, until the force on the cell boundary (dE/dL) is also below `fmax`.
+    pub fn relaxed(
+        &self,
+        minimizer: Minimizer,
+        fmax: f64,
+        relax_cell_x: bool,
+        max_iterations: u64,
+    ) -> Result<Relaxation, String> {
+        let elements = self
+            .atoms
+            .iter()
+            .map(|a| Element::from_symbol(&a.elem).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let relax_fn = RelaxFn {
+            reference: self,
+            elements,
+            params: Params::new_lammps(),
+            relax_cell_x,
+        };
+
+        let mut initial: Vec<f64> = self.atoms.iter().flat_map(|a| a.xyz.to_vec()).collect();
+        if relax_cell_x {
+            initial.push(0.0);
+        }
+
+        let stop_condition = move |state: AlgorithmState<'_>| {
+            state.iterations >= max_iterations || max_force(state.gradient) <= fmax
+        };
+
+        let compute = |pos: &[f64]| relax_fn.compute(pos);
+        let (position, iterations) = match minimizer {
+            Minimizer::Acgsd => rsp2_minimize::cg::Builder::new_acgsd()
+                .alpha_guess_first(ACGSD_ALPHA_GUESS_FIRST)
+                .alpha_guess_max(ACGSD_ALPHA_GUESS_MAX)
+                .stop_condition(stop_condition)
+                .run(&initial, compute)
+                .map(|out| (out.position, out.iterations))
+                .map_err(|e| e.to_string())?,
+            Minimizer::Fire => {
+                let params = rsp2_minimize::fire::Params::new(FIRE_TIMESTEP_MAX);
+                rsp2_minimize::fire::Builder::new(&params)
+                    .stop_condition(stop_condition)
+                    .run(&initial, compute)
+                    .map(|out| (out.position, out.iterations))
+                    .map_err(|e| e.to_string())?
+            }
+        };
+
+        let (energy, gradient) = relax_fn.compute(&position)?;
+        let max_force = max_force(&gradient);
+
+        Ok(Relaxation {
+            structure: relax_fn.structure(&position),
+            energy,
+            max_force,
+            iterations,
+            converged: max_force <= fmax,
+        })
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Relax the structure with a classical potential (only "rebo" is supported) using either the
+    /// "acgsd" (default) or "fire" minimizer, optionally relaxing the periodic cell length.
+    fn relax(
+        &self,
+        potential: Option<&str>,
+        fmax: Option<f64>,
+        relax_cell_x: Option<bool>,
+        max_iterations: Option<u64>,
+        minimizer: Option<&str>,
+    ) -> PyResult<Relaxation> {
+        match potential.unwrap_or("rebo") {
+            "rebo" => {}
+            other => {
+                let msg = format!("unsupported potential '{}'", other);
+                return Err(PyValueError::new_err(msg));
+            }
+        }
+        let minimizer = match minimizer.unwrap_or("acgsd") {
+            "acgsd" => Minimizer::Acgsd,
+            "fire" => Minimizer::Fire,
+            other => {
+                let msg = format!("unknown minimizer '{}'", other);
+                return Err(PyValueError::new_err(msg));
+            }
+        };
+
+        self.relaxed(
+            minimizer,
+            fmax.unwrap_or(0.01),
+            relax_cell_x.unwrap_or(true),
+            max_iterations.unwrap_or(1000),
+        )
+        .map_err(PyRuntimeError::new_err)
+    }
+}
@@ -0,0 +1,245 @@
+use super::optics::Polarization;
+use super::tight_binding::{TightBinding, DEFAULT_HOPPING};
+use super::AtomicStructure;
+use itertools::{iproduct, Itertools};
+use nalgebra::{Complex, Vector3};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Slater exponent of the carbon 2p orbital (1.625 / bohr) in 1/angstrom.
+pub const CARBON_2P_DECAY: f64 = 3.0708;
+/// Orbitals are left out at points more than this many decay lengths further away than the tip
+/// height, where they've decayed by more than exp(-10) relative to directly below the tip.
+const ORBITAL_RANGE: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StmOptions {
+    /// Sample bias in volts, positive biases image unoccupied states between the Fermi level
+    /// (the middle of the gap) and `bias` above it, negative biases image occupied states.
+    pub bias: f64,
+    /// Height of the tip above the plane of the ribbon, in angstroms.
+    pub height: f64,
+    /// Pixel size in angstroms.
+    pub resolution: f64,
+    /// Number of cells along the periodic axis in the image.
+    pub repeats: usize,
+    /// Distance from the outermost carbon atoms to the edges of the image, in angstroms.
+    pub margin: f64,
+    pub num_kpoints: usize,
+    /// Nearest-neighbor hopping of the pz tight-binding model, in eV.
+    pub hopping: f64,
+    /// Decay constant of the Slater-type pz orbitals, in 1/angstrom.
+    pub decay: f64,
+}
+
+impl Default for StmOptions {
+    fn default() -> Self {
+        Self {
+            bias: 1.0,
+            height: 3.0,
+            resolution: 0.1,
+            repeats: 3,
+            margin: 3.0,
+            num_kpoints: 50,
+            hopping: DEFAULT_HOPPING,
+            decay: CARBON_2P_DECAY,
+        }
+    }
+}
+
+/// Constant-height STM image, i.e. the local density of states at the tip.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StmImage {
+    /// LDOS (arbitrary units) at the center of each pixel, rows go across the ribbon (starting
+    /// from the bottom) and columns along its periodic axis.
+    #[pyo3(get)]
+    pub values: Vec<Vec<f64>>,
+    /// Edges of the image as (left, right, bottom, top), in angstroms along and across the
+    /// periodic axis, as expected by matplotlib's `imshow(..., origin="lower")`.
+    #[pyo3(get)]
+    pub extent: (f64, f64, f64, f64),
+    /// Energies (eV) of the states that were integrated over, on the tight-binding energy scale.
+    #[pyo3(get)]
+    pub energy_window: (f64, f64),
+}
+
+/// A periodic image of a pz orbital that contributes to the wavefunction at a pixel.
+#[derive(Debug, Copy, Clone)]
+struct OrbitalValue {
+    orbital: usize,
+    /// position of the image along the periodic axis, for the phase of the Bloch sum
+    axial: f64,
+    value: f64,
+}
+
+impl AtomicStructure {
+    /// Simulated constant-height STM image of a periodic ribbon in the Tersoff-Hamann picture:
+    /// the density of the tight-binding states within the bias window at each pixel, using
+    /// Slater-type pz orbitals on the carbon atoms. The ribbon should lie in the plane of its
+    /// first two lattice vectors.
+    pub fn stm_image(&self, options: &StmOptions) -> Result<StmImage, String> {
+        if options.bias == 0.0 {
+            return Err("bias must be nonzero".into());
+        }
+        if options.num_kpoints == 0 || options.repeats == 0 {
+            return Err("need at least one k-point and one repeat".into());
+        }
+        let positive = [
+            options.height,
+            options.resolution,
+            options.hopping,
+            options.decay,
+        ];
+        if !positive.iter().all(|&x| x > 0.0) || options.margin < 0.0 {
+            return Err(
+                "height, resolution, hopping and decay must be positive, margin non-negative"
+                    .into(),
+            );
+        }
+
+        let tb = TightBinding::new(self, options.hopping)?;
+
+        // coordinates along the axis, across the ribbon, and normal to its plane
+        let axis = Polarization::Along.direction(self);
+        let across = Polarization::Across.direction(self);
+        let normal = axis.cross(&across);
+        let sites = tb
+            .positions
+            .iter()
+            .map(|r| Vector3::new(axis.dot(r), across.dot(r), normal.dot(r)))
+            .collect_vec();
+        let plane = sites.iter().map(|s| s.z).sum::<f64>() / sites.len() as f64;
+        let tip = plane + options.height;
+        let (ymin, ymax) = sites.iter().map(|s| s.y).minmax().into_option().unwrap();
+        let (left, right) = (0.0, options.repeats as f64 * tb.length);
+        let (bottom, top) = (ymin - options.margin, ymax + options.margin);
+        let nx = usize::max(1, ((right - left) / options.resolution).ceil() as usize);
+        let ny = usize::max(1, ((top - bottom) / options.resolution).ceil() as usize);
+        let (dx, dy) = ((right - left) / nx as f64, (top - bottom) / ny as f64);
+
+        // orbitals (including their periodic images) close enough to each pixel to contribute
+        let range = options.height + ORBITAL_RANGE / options.decay;
+        let pixels = iproduct!(0..ny, 0..nx)
+            .map(|(iy, ix)| {
+                let x = left + (ix as f64 + 0.5) * dx;
+                let point = Vector3::new(x, bottom + (iy as f64 + 0.5) * dy, tip);
+                let mut values = vec![];
+                for (orbital, site) in sites.iter().enumerate() {
+                    let first = ((x - site.x - range) / tb.length).ceil() as i64;
+                    let last = ((x - site.x + range) / tb.length).floor() as i64;
+                    for image in first..=last {
+                        let center = site + Vector3::x() * (image as f64 * tb.length);
+                        let r = point - center;
+                        let distance = r.norm();
+                        if distance <= range {
+                            values.push(OrbitalValue {
+                                orbital,
+                                axial: center.x,
+                                value: r.z * (-options.decay * distance).exp(),
+                            });
+                        }
+                    }
+                }
+                values
+            })
+            .collect_vec();
+
+        // the bias window starts at the middle of the gap, there's at least one k-point
+        let kpoints = tb.kpoint_mesh(options.num_kpoints);
+        let bands = kpoints.iter().map(|&k| tb.bands(k)).collect_vec();
+        let occupied = tb.num_occupied();
+        let homo = match occupied {
+            0 => None,
+            n => Some(
+                bands
+                    .iter()
+                    .map(|(e, _)| e[n - 1])
+                    .fold(f64::NEG_INFINITY, f64::max),
+            ),
+        };
+        let lumo = match occupied < tb.num_orbitals {
+            true => Some(
+                bands
+                    .iter()
+                    .map(|(e, _)| e[occupied])
+                    .fold(f64::INFINITY, f64::min),
+            ),
+            false => None,
+        };
+        let fermi = match (homo, lumo) {
+            (Some(homo), Some(lumo)) => (homo + lumo) / 2.0,
+            (Some(e), None) | (None, Some(e)) => e,
+            (None, None) => unreachable!("there's at least one orbital"),
+        };
+        let energy_window = match options.bias > 0.0 {
+            true => (fermi, fermi + options.bias),
+            false => (fermi + options.bias, fermi),
+        };
+
+        let mut ldos = vec![0.0; pixels.len()];
+        let mut num_states = 0;
+        for (&k, (energies, states)) in kpoints.iter().zip(&bands) {
+            let in_window =
+                |&n: &usize| energy_window.0 <= energies[n] && energies[n] <= energy_window.1;
+            for n in (0..tb.num_orbitals).filter(in_window) {
+                num_states += 1;
+                let state = states.column(n);
+                for (density, values) in ldos.iter_mut().zip(&pixels) {
+                    let psi = values.iter().fold(Complex::new(0.0, 0.0), |psi, v| {
+                        let (sin, cos) = (k * v.axial).sin_cos();
+                        psi + state[v.orbital] * Complex::new(cos, sin) * v.value
+                    });
+                    *density += psi.norm_sqr();
+                }
+            }
+        }
+        if num_states == 0 {
+            return Err(
+                "no states within the bias window, try a larger bias or more k-points".into(),
+            );
+        }
+
+        let norm = 1.0 / options.num_kpoints as f64;
+        Ok(StmImage {
+            values: ldos
+                .chunks(nx)
+                .map(|row| row.iter().map(|x| x * norm).collect())
+                .collect(),
+            extent: (left, right, bottom, top),
+            energy_window,
+        })
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Simulated constant-height STM image at the given sample bias (volts), see
+    /// `AtomicStructure::stm_image`.
+    #[name = "stm_image"]
+    #[allow(clippy::too_many_arguments)]
+    fn py_stm_image(
+        &self,
+        bias: f64,
+        height: Option<f64>,
+        resolution: Option<f64>,
+        repeats: Option<usize>,
+        margin: Option<f64>,
+        num_kpoints: Option<usize>,
+        hopping: Option<f64>,
+        decay: Option<f64>,
+    ) -> PyResult<StmImage> {
+        let defaults = StmOptions::default();
+        let options = StmOptions {
+            bias,
+            height: height.unwrap_or(defaults.height),
+            resolution: resolution.unwrap_or(defaults.resolution),
+            repeats: repeats.unwrap_or(defaults.repeats),
+            margin: margin.unwrap_or(defaults.margin),
+            num_kpoints: num_kpoints.unwrap_or(defaults.num_kpoints),
+            hopping: hopping.unwrap_or(defaults.hopping),
+            decay: decay.unwrap_or(defaults.decay),
+        };
+        self.stm_image(&options).map_err(PyValueError::new_err)
+    }
+}
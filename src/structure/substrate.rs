@@ -0,0 +1,437 @@
+use super::{Atom, AtomicStructure};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::cmp::Ordering;
+use vasp_poscar::Poscar;
+
+/// Tolerance (in angstroms) for the in-plane lattice vectors of a slab having no z component.
+const PLANE_TOLERANCE: f64 = 1e-6;
+/// Tolerance (in fractional coordinates) used when deciding whether a site is inside a supercell.
+const SITE_TOLERANCE: f64 = 1e-6;
+
+/// Lattice constants (angstroms) of the fcc metals commonly used for on-surface synthesis.
+fn fcc_lattice_constant(elem: &str) -> Option<f64> {
+    match elem {
+        "Au" => Some(4.078),
+        "Ag" => Some(4.086),
+        "Cu" => Some(3.615),
+        "Pt" => Some(3.924),
+        "Pd" => Some(3.891),
+        "Ni" => Some(3.524),
+        _ => None,
+    }
+}
+
+/// A surface slab described by its in-plane (xy) primitive cell and the atoms in it. The surface
+/// normal is along z.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Substrate {
+    /// in-plane primitive lattice vectors
+    pub cell: [[f64; 2]; 2],
+    /// atoms in the primitive cell, with z relative to the bottom of the slab
+    pub basis: Vec<Atom>,
+}
+
+/// A ribbon placed on a commensurate supercell of a substrate.
+#[pyclass(module = "agnr_ml")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubstrateMatch {
+    #[pyo3(get)]
+    pub structure: AtomicStructure,
+    /// Whether each atom in `structure` belongs to the substrate (the substrate comes first).
+    #[pyo3(get)]
+    pub is_substrate: Vec<bool>,
+    /// Strain applied to the ribbon along its periodic axis to make it commensurate.
+    #[pyo3(get)]
+    pub strain: f64,
+    /// Number of copies of the ribbon's cell in the supercell.
+    #[pyo3(get)]
+    pub ribbon_repeats: usize,
+    /// Supercell vectors in units of the substrate's primitive cell, the first is along the
+    /// ribbon.
+    pub supercell: [[i32; 2]; 2],
+}
+
+fn frac_to_cart(cell: &[[f64; 2]; 2], [i, j]: [f64; 2]) -> [f64; 2] {
+    [
+        i * cell[0][0] + j * cell[1][0],
+        i * cell[0][1] + j * cell[1][1],
+    ]
+}
+
+fn norm([x, y]: [f64; 2]) -> f64 {
+    f64::hypot(x, y)
+}
+
+impl Substrate {
+    /// An fcc(111) slab with ABC stacked layers, the hexagonal surface cell has lattice constant
+    /// `lattice_constant / sqrt(2)`.
+    pub fn fcc111(elem: &str, lattice_constant: f64, layers: usize) -> Self {
+        let a = lattice_constant / f64::sqrt(2.0);
+        let layer_sep = lattice_constant / f64::sqrt(3.0);
+        let cell = [[a, 0.0], [a / 2.0, a * f64::sqrt(3.0) / 2.0]];
+
+        let basis = (0..layers)
+            .map(|k| {
+                let offset = (k % 3) as f64 / 3.0;
+                let [x, y] = frac_to_cart(&cell, [offset, offset]);
+                Atom {
+                    elem: elem.to_string(),
+                    xyz: [x, y, k as f64 * layer_sep],
+                }
+            })
+            .collect();
+
+        Self { cell, basis }
+    }
+
+    /// AA' stacked hexagonal boron nitride, boron atoms in each layer sit above nitrogen atoms in
+    /// the one below.
+    pub fn hbn(lattice_constant: f64, layers: usize, layer_sep: f64) -> Self {
+        let a = lattice_constant;
+        let cell = [[a, 0.0], [a / 2.0, a * f64::sqrt(3.0) / 2.0]];
+
+        let mut basis = Vec::new();
+        for k in 0..layers {
+            let (first, second) = match k % 2 {
+                0 => ("B", "N"),
+                _ => ("N", "B"),
+            };
+            for &(elem, offset) in &[(first, 1.0 / 3.0), (second, 2.0 / 3.0)] {
+                let [x, y] = frac_to_cart(&cell, [offset, offset]);
+                basis.push(Atom {
+                    elem: elem.to_string(),
+                    xyz: [x, y, k as f64 * layer_sep],
+                });
+            }
+        }
+
+        Self { cell, basis }
+    }
+
+    /// Use an existing slab, with its first two lattice vectors in-plane and its surface normal
+    /// along z. The third lattice vector is ignored, and atoms are wrapped into the in-plane cell.
+    pub fn from_structure(structure: &AtomicStructure) -> Result<Self, String> {
+        let [a, b, _] = structure.lattice_vectors;
+        if a[2].abs() > PLANE_TOLERANCE || b[2].abs() > PLANE_TOLERANCE {
+            return Err("the first two lattice vectors must lie in the xy plane".to_string());
+        }
+        let det = a[0] * b[1] - a[1] * b[0];
+        if det.abs() < PLANE_TOLERANCE {
+            return Err("the first two lattice vectors must not be parallel".to_string());
+        }
+        if structure.atoms.is_empty() {
+            return Err("substrate has no atoms".to_string());
+        }
+
+        let min_z = structure
+            .atoms
+            .iter()
+            .map(|atom| atom.xyz[2])
+            .fold(f64::INFINITY, f64::min);
+        let cell = [[a[0], a[1]], [b[0], b[1]]];
+        // sites on a cell boundary go to its lower edge, like in `supercell`
+        let wrap = |f: f64| f - (f + SITE_TOLERANCE).floor();
+        let wrap_xy = |[x, y]: [f64; 2]| {
+            let fa = (x * b[1] - y * b[0]) / det;
+            let fb = (a[0] * y - a[1] * x) / det;
+            frac_to_cart(&cell, [wrap(fa), wrap(fb)])
+        };
+        let basis = structure
+            .atoms
+            .iter()
+            .map(|atom| {
+                let [x, y] = wrap_xy([atom.xyz[0], atom.xyz[1]]);
+                Atom {
+                    elem: atom.elem.clone(),
+                    xyz: [x, y, atom.xyz[2] - min_z],
+                }
+            })
+            .collect();
+
+        Ok(Self { cell, basis })
+    }
+
+    fn thickness(&self) -> f64 {
+        self.basis.iter().map(|a| a.xyz[2]).fold(0.0, f64::max)
+    }
+
+    /// All lattice vectors `(m, n)` (with coefficients up to `max_index`) whose length matches
+    /// some multiple `k <= max_repeats` of `length` within `max_strain`. Returns `(strain, k, m,
+    /// n)` with the fewest repeats (smallest supercells) first, then the smallest strains, where
+    /// the strain is what must be applied to `k * length` to match the lattice vector.
+    fn length_matches(
+        &self,
+        length: f64,
+        max_repeats: usize,
+        max_strain: f64,
+        max_index: i32,
+    ) -> Vec<(f64, usize, [i32; 2])> {
+        let mut matches = Vec::new();
+        for k in 1..=max_repeats {
+            let target = k as f64 * length;
+            for m in -max_index..=max_index {
+                for n in -max_index..=max_index {
+                    let v = frac_to_cart(&self.cell, [m as f64, n as f64]);
+                    let strain = norm(v) / target - 1.0;
+                    // only keep one of each pair of opposite vectors
+                    let canonical = m > 0 || (m == 0 && n > 0);
+                    if canonical && strain.abs() <= max_strain {
+                        matches.push((strain, k, [m, n]));
+                    }
+                }
+            }
+        }
+
+        // prefer small supercells, then small strains
+        matches.sort_by(|a, b| {
+            a.1.cmp(&b.1)
+                .then_with(|| f64::partial_cmp(&a.0.abs(), &b.0.abs()).unwrap_or(Ordering::Equal))
+                .then_with(|| a.2.cmp(&b.2))
+        });
+        matches
+    }
+
+    /// The lattice vector `(p, q)` that, together with `along`, gives the supercell closest to
+    /// rectangular with a height (perpendicular to `along`) of at least `min_height`.
+    fn across_vector(&self, along: [i32; 2], min_height: f64, max_index: i32) -> Option<[i32; 2]> {
+        let v = frac_to_cart(&self.cell, [along[0] as f64, along[1] as f64]);
+        let v_hat = [v[0] / norm(v), v[1] / norm(v)];
+
+        let mut best: Option<((f64, f64), [i32; 2])> = None;
+        for p in -max_index..=max_index {
+            for q in -max_index..=max_index {
+                // right-handed, so the cell has positive area
+                if along[0] * q - along[1] * p <= 0 {
+                    continue;
+                }
+                let w = frac_to_cart(&self.cell, [p as f64, q as f64]);
+                let height = v_hat[0] * w[1] - v_hat[1] * w[0];
+                if height < min_height {
+                    continue;
+                }
+                let shear = (v_hat[0] * w[0] + v_hat[1] * w[1]).abs();
+                let key = (shear, height);
+                if best.map_or(true, |(best_key, _)| {
+                    key.partial_cmp(&best_key) == Some(Ordering::Less)
+                }) {
+                    best = Some((key, [p, q]));
+                }
+            }
+        }
+        best.map(|(_, pq)| pq)
+    }
+
+    /// Build a supercell of the substrate with the given lattice vectors (in units of the
+    /// primitive cell), rotated so that the first one is along x.
+    fn supercell(&self, along: [i32; 2], across: [i32; 2]) -> ([[f64; 2]; 2], Vec<Atom>) {
+        let v = frac_to_cart(&self.cell, [along[0] as f64, along[1] as f64]);
+        let w = frac_to_cart(&self.cell, [across[0] as f64, across[1] as f64]);
+        let (sin, cos) = f64::atan2(-v[1], v[0]).sin_cos();
+        let rotate = |[x, y]: [f64; 2]| [cos * x - sin * y, sin * x + cos * y];
+
+        // fractional coordinates in the supercell
+        let det = v[0] * w[1] - v[1] * w[0];
+        let to_frac = |[x, y]: [f64; 2]| [(x * w[1] - y * w[0]) / det, (v[0] * y - v[1] * x) / det];
+
+        let range = along[0].abs() + along[1].abs() + across[0].abs() + across[1].abs();
+        let mut atoms = Vec::new();
+        for i in -range..=range {
+            for j in -range..=range {
+                for atom in &self.basis {
+                    let [dx, dy] = frac_to_cart(&self.cell, [i as f64, j as f64]);
+                    let xy = [atom.xyz[0] + dx, atom.xyz[1] + dy];
+                    let [fa, fb] = to_frac(xy);
+                    let inside = |f: f64| (-SITE_TOLERANCE..1.0 - SITE_TOLERANCE).contains(&f);
+                    if inside(fa) && inside(fb) {
+                        let [x, y] = rotate(xy);
+                        atoms.push(Atom {
+                            elem: atom.elem.clone(),
+                            xyz: [x, y, atom.xyz[2]],
+                        });
+                    }
+                }
+            }
+        }
+
+        ([rotate(v), rotate(w)], atoms)
+    }
+
+    /// Place a ribbon (periodic along x) on the smallest commensurate supercell of this substrate
+    /// within `max_strain`, the least strained one if there are several. The ribbon is strained
+    /// along x to match, centered across the supercell, and placed `height` above the top
+    /// substrate layer. `lateral_sep` is the minimum distance between the ribbon and its periodic
+    /// images across the ribbon.
+    #[allow(clippy::too_many_arguments)]
+    pub fn place(
+        &self,
+        ribbon: &AtomicStructure,
+        height: f64,
+        max_strain: f64,
+        max_repeats: usize,
+        lateral_sep: f64,
+        vacuum_sep: f64,
+    ) -> Result<SubstrateMatch, String> {
+        // large enough to find matches within `max_repeats` of most ribbons
+        let ribbon_length = ribbon.lattice_vectors[0][0];
+        let min_cell = f64::min(norm(self.cell[0]), norm(self.cell[1]));
+        let max_index = (2.0 * max_repeats as f64 * ribbon_length / min_cell).ceil() as i32 + 1;
+
+        let &(strain, repeats, along) = self
+            .length_matches(ribbon_length, max_repeats, max_strain, max_index)
+            .first()
+            .ok_or_else(|| {
+                format!(
+                    "no substrate vector within {} strain of up to {} ribbon cells",
+                    max_strain, max_repeats
+                )
+            })?;
+
+        let (min_y, max_y) = ribbon
+            .atoms
+            .iter()
+            .map(|a| a.xyz[1])
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), y| {
+                (lo.min(y), hi.max(y))
+            });
+        let ribbon_width = max_y - min_y;
+        let across_index = ((ribbon_width + lateral_sep) / min_cell).ceil() as i32 + 2;
+        let across = self
+            .across_vector(along, ribbon_width + lateral_sep, across_index)
+            .ok_or("no supercell wide enough for the ribbon")?;
+
+        let ([v, w], substrate_atoms) = self.supercell(along, across);
+        let expected = (along[0] * across[1] - along[1] * across[0]) as usize * self.basis.len();
+        if substrate_atoms.len() != expected {
+            return Err(format!(
+                "found {} substrate atoms in the supercell instead of {}",
+                substrate_atoms.len(),
+                expected
+            ));
+        }
+
+        let z_offset = vacuum_sep / 2.0;
+        let top = z_offset + self.thickness();
+        let c = self.thickness() + height + vacuum_sep;
+
+        let mut atoms: Vec<Atom> = substrate_atoms
+            .into_iter()
+            .map(|atom| Atom {
+                xyz: [atom.xyz[0], atom.xyz[1], atom.xyz[2] + z_offset],
+                ..atom
+            })
+            .collect();
+        let n_substrate = atoms.len();
+
+        let ribbon = ribbon.repeated_x(repeats);
+        let n = ribbon.atoms.len() as f64;
+        let center_y = ribbon.atoms.iter().map(|a| a.xyz[1]).sum::<f64>() / n;
+        let center_z = ribbon.atoms.iter().map(|a| a.xyz[2]).sum::<f64>() / n;
+        atoms.extend(ribbon.atoms.into_iter().map(|atom| Atom {
+            xyz: [
+                (1.0 + strain) * atom.xyz[0] + w[0] / 2.0,
+                atom.xyz[1] - center_y + w[1] / 2.0,
+                atom.xyz[2] - center_z + top + height,
+            ],
+            ..atom
+        }));
+
+        let is_substrate = (0..atoms.len()).map(|i| i < n_substrate).collect();
+        Ok(SubstrateMatch {
+            structure: AtomicStructure {
+                lattice_vectors: [[v[0], v[1], 0.0], [w[0], w[1], 0.0], [0.0, 0.0, c]],
+                atoms,
+            },
+            is_substrate,
+            strain,
+            ribbon_repeats: repeats,
+            supercell: [along, across],
+        })
+    }
+}
+
+#[pymethods]
+impl SubstrateMatch {
+    #[getter(supercell)]
+    fn py_supercell(&self) -> ((i32, i32), (i32, i32)) {
+        let [[m, n], [p, q]] = self.supercell;
+        ((m, n), (p, q))
+    }
+}
+
+#[pymethods]
+impl Substrate {
+    /// An fcc(111) slab of the given element, the lattice constant defaults to the experimental
+    /// value for common metals (Au, Ag, Cu, Pt, Pd, Ni).
+    #[staticmethod]
+    #[name = "fcc111"]
+    pub fn py_fcc111(
+        element: &str,
+        lattice_constant: Option<f64>,
+        layers: Option<usize>,
+    ) -> PyResult<Self> {
+        let lattice_constant = lattice_constant
+            .or_else(|| fcc_lattice_constant(element))
+            .ok_or_else(|| {
+                let msg = format!("no default lattice constant for '{}'", element);
+                PyValueError::new_err(msg)
+            })?;
+        Ok(Self::fcc111(element, lattice_constant, layers.unwrap_or(3)))
+    }
+
+    /// Hexagonal boron nitride, by default a single layer.
+    #[staticmethod]
+    #[name = "hbn"]
+    pub fn py_hbn(
+        lattice_constant: Option<f64>,
+        layers: Option<usize>,
+        layer_sep: Option<f64>,
+    ) -> Self {
+        Self::hbn(
+            lattice_constant.unwrap_or(2.504),
+            layers.unwrap_or(1),
+            layer_sep.unwrap_or(3.33),
+        )
+    }
+
+    /// Load a slab from a POSCAR file, see `from_structure`.
+    #[staticmethod]
+    pub fn from_poscar(path: &str) -> PyResult<Self> {
+        let poscar = Poscar::from_path(path).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Self::from_structure(&poscar.into()).map_err(PyValueError::new_err)
+    }
+
+    #[staticmethod]
+    #[name = "from_structure"]
+    pub fn py_from_structure(structure: &AtomicStructure) -> PyResult<Self> {
+        Self::from_structure(structure).map_err(PyValueError::new_err)
+    }
+
+    #[getter]
+    fn num_atoms(&self) -> usize {
+        self.basis.len()
+    }
+
+    /// Place a ribbon on this substrate, see `Substrate::place`.
+    #[name = "place"]
+    pub fn py_place(
+        &self,
+        ribbon: &AtomicStructure,
+        height: Option<f64>,
+        max_strain: Option<f64>,
+        max_repeats: Option<usize>,
+        lateral_sep: Option<f64>,
+        vacuum_sep: Option<f64>,
+    ) -> PyResult<SubstrateMatch> {
+        self.place(
+            ribbon,
+            height.unwrap_or(3.2),
+            max_strain.unwrap_or(0.03),
+            max_repeats.unwrap_or(10),
+            lateral_sep.unwrap_or(10.0),
+            vacuum_sep.unwrap_or(15.0),
+        )
+        .map_err(PyValueError::new_err)
+    }
+}
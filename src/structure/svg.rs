@@ -0,0 +1,225 @@
+use super::AtomicStructure;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fmt::Write;
+
+/// Options for rendering a structure as an SVG image, viewed down the z axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Pixels per angstrom.
+    pub scale: f64,
+    /// Number of periodic images to draw along the first lattice vector.
+    pub repeats: usize,
+    /// Maximum distance between bonded atoms.
+    pub bond_cutoff: f64,
+    /// Optional per-atom values (e.g. charge or displacement amplitude) used to color atoms.
+    pub values: Option<Vec<f64>>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            scale: 30.0,
+            repeats: 1,
+            bond_cutoff: 1.6,
+            values: None,
+        }
+    }
+}
+
+fn element_style(elem: &str) -> (&'static str, f64) {
+    match elem {
+        "H" => ("#f0f0f0", 0.25),
+        "C" => ("#404040", 0.4),
+        "N" => ("#3050f8", 0.4),
+        "O" => ("#ff0d0d", 0.4),
+        "B" => ("#ffb5b5", 0.4),
+        _ => ("#ff1493", 0.5),
+    }
+}
+
+/// Map a value in `[0, 1]` to a color, blue (low) to white to red (high).
+fn colormap(t: f64) -> String {
+    let t = t.max(0.0).min(1.0);
+    let (r, g, b) = if t < 0.5 {
+        let s = 2.0 * t;
+        (s, s, 1.0)
+    } else {
+        let s = 2.0 * (1.0 - t);
+        (1.0, s, s)
+    };
+    let to_u8 = |x: f64| (255.0 * x).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", to_u8(r), to_u8(g), to_u8(b))
+}
+
+impl AtomicStructure {
+    pub fn write_svg<W: Write>(&self, w: &mut W, options: &SvgOptions) -> std::fmt::Result {
+        if self.atoms.is_empty() {
+            return writeln!(w, r#"<svg xmlns="http://www.w3.org/2000/svg"/>"#);
+        }
+
+        let lattice = self.lattice_matrix();
+        let coords = self.coord_matrix();
+        let bonds = self.bonds(options.bond_cutoff);
+        let repeats = usize::max(1, options.repeats);
+        let a = self.lattice_vectors[0];
+
+        // colors for each atom, values are normalized symmetrically if any are negative so that
+        // zero is always white
+        let colors: Vec<String> = match &options.values {
+            Some(values) => {
+                assert_eq!(values.len(), self.atoms.len(), "need one value per atom");
+                let max_abs = values.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+                let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                values
+                    .iter()
+                    .map(|&v| match () {
+                        _ if max_abs == 0.0 => colormap(0.5),
+                        _ if min < 0.0 => colormap(0.5 + 0.5 * v / max_abs),
+                        _ if max > min => colormap(0.5 + 0.5 * (v - min) / (max - min)),
+                        _ => colormap(1.0),
+                    })
+                    .collect()
+            }
+            None => self
+                .atoms
+                .iter()
+                .map(|atom| element_style(&atom.elem).0.to_string())
+                .collect(),
+        };
+
+        let images = (0..repeats).map(|r| [r as f64 * a[0], r as f64 * a[1]]);
+        let positions: Vec<[f64; 2]> = images
+            .flat_map(|[dx, dy]| {
+                self.atoms
+                    .iter()
+                    .map(move |at| [at.xyz[0] + dx, at.xyz[1] + dy])
+            })
+            .collect();
+
+        // bounding box (in angstroms) with some margin
+        let margin = 1.5;
+        let fold = |f: fn(f64, f64) -> f64, init: f64, k: usize| {
+            positions.iter().map(|p| p[k]).fold(init, f)
+        };
+        let (x_min, x_max) = (
+            fold(f64::min, f64::INFINITY, 0),
+            fold(f64::max, -f64::INFINITY, 0),
+        );
+        let (y_min, y_max) = (
+            fold(f64::min, f64::INFINITY, 1),
+            fold(f64::max, -f64::INFINITY, 1),
+        );
+        let (x_min, y_min) = (x_min - margin, y_min - margin);
+        let (width, height) = (x_max - x_min + margin, y_max - y_min + margin);
+
+        // convert to pixels, note that y is flipped since svg y goes down
+        let scale = options.scale;
+        let px = |x: f64, y: f64| ((x - x_min) * scale, (y_min + height - y) * scale);
+
+        writeln!(
+            w,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.1}" height="{:.1}" viewBox="0 0 {:.1} {:.1}">"#,
+            width * scale,
+            height * scale,
+            width * scale,
+            height * scale,
+        )?;
+
+        writeln!(
+            w,
+            r##"<g stroke="#808080" stroke-width="{:.2}">"##,
+            0.15 * scale
+        )?;
+        for r in 0..repeats {
+            let offset = r as f64;
+            for bond in bonds.iter().flatten() {
+                // bonds within the cell are listed twice, so only draw them once
+                if bond.image_offset == [0, 0, 0] && bond.from > bond.to {
+                    continue;
+                }
+                let from = self.atoms[bond.from].xyz;
+                let delta = bond.to_delta(&lattice, &coords);
+                let (x0, y0) = (from[0] + offset * a[0], from[1] + offset * a[1]);
+                let (x1, y1) = px(x0 + delta[0], y0 + delta[1]);
+                let (x0, y0) = px(x0, y0);
+                writeln!(
+                    w,
+                    r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}"/>"#,
+                    x0, y0, x1, y1
+                )?;
+            }
+        }
+        writeln!(w, "</g>")?;
+
+        writeln!(
+            w,
+            r##"<g stroke="#000000" stroke-width="{:.2}">"##,
+            0.03 * scale
+        )?;
+        let n = self.atoms.len();
+        for (i, &[x, y]) in positions.iter().enumerate() {
+            let atom = &self.atoms[i % n];
+            let radius = element_style(&atom.elem).1;
+            let (cx, cy) = px(x, y);
+            writeln!(
+                w,
+                r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}"><title>{} {}</title></circle>"#,
+                cx,
+                cy,
+                radius * scale,
+                colors[i % n],
+                atom.elem,
+                i % n,
+            )?;
+        }
+        writeln!(w, "</g>")?;
+        writeln!(w, "</svg>")
+    }
+
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let mut svg = String::new();
+        self.write_svg(&mut svg, options).unwrap();
+        svg
+    }
+}
+
+#[pymethods]
+impl AtomicStructure {
+    /// Render the structure (viewed down the z axis) as an SVG image, optionally coloring atoms by
+    /// the given per-atom values and drawing multiple periodic images along the first lattice
+    /// vector.
+    #[name = "to_svg"]
+    fn py_to_svg(
+        &self,
+        values: Option<Vec<f64>>,
+        repeats: Option<usize>,
+        scale: Option<f64>,
+        bond_cutoff: Option<f64>,
+    ) -> PyResult<String> {
+        if matches!(&values, Some(v) if v.len() != self.atoms.len()) {
+            return Err(PyValueError::new_err("need exactly one value per atom"));
+        }
+
+        let default = SvgOptions::default();
+        let bond_cutoff = bond_cutoff.unwrap_or(default.bond_cutoff);
+        if !(bond_cutoff > 0.0 && bond_cutoff.is_finite()) {
+            return Err(PyValueError::new_err(format!(
+                "bond cutoff must be finite and positive, got {}",
+                bond_cutoff
+            )));
+        }
+
+        Ok(self.to_svg(&SvgOptions {
+            scale: scale.unwrap_or(default.scale),
+            repeats: repeats.unwrap_or(default.repeats),
+            bond_cutoff,
+            values,
+        }))
+    }
+
+    fn _repr_svg_(&self) -> String {
+        self.to_svg(&SvgOptions::default())
+    }
+}
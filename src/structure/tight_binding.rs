@@ -0,0 +1,143 @@
+use super::AtomicStructure;
+use itertools::Itertools;
+use nalgebra::{Complex, DMatrix, DVector, Vector3};
+
+/// Cutoff used to find C-C bonds for the tight-binding model.
+pub(super) const TB_BOND_CUTOFF: f64 = 1.6;
+
+/// Default nearest-neighbor hopping for graphene (eV).
+pub const DEFAULT_HOPPING: f64 = 2.7;
+
+/// Nearest-neighbor pz tight-binding model of the carbon atoms in a ribbon, periodic along its
+/// first lattice vector. Other atoms (i.e. hydrogen passivation) only remove pz orbitals from the
+/// pi system, so they're left out.
+#[derive(Debug, Clone)]
+pub struct TightBinding {
+    /// hopping energy, the off-diagonal elements of the Hamiltonian are -hopping
+    pub hopping: f64,
+    pub num_orbitals: usize,
+    /// cartesian position of each orbital's carbon atom
+    pub positions: Vec<Vector3<f64>>,
+    /// (from, to, cartesian vector from `from` to the bonded image of `to`), in both directions
+    bonds: Vec<(usize, usize, Vector3<f64>)>,
+    /// unit vector along the periodic axis
+    pub axis: Vector3<f64>,
+    /// periodic length of the ribbon
+    pub length: f64,
+}
+
+/// Eigenvalues (ascending) and eigenvectors (columns, in the same order) at one k-point.
+pub type Bands = (DVector<f64>, DMatrix<Complex<f64>>);
+
+impl TightBinding {
+    pub fn new(structure: &AtomicStructure, hopping: f64) -> Result<Self, String> {
+        if !hopping.is_finite() {
+            return Err("hopping must be finite".into());
+        }
+        let a = Vector3::from(structure.lattice_vectors[0]);
+        if !(a.norm().is_finite() && a.norm() > 0.0) {
+            return Err("the first lattice vector must have a finite, nonzero length".into());
+        }
+
+        let carbon = (0..structure.atoms.len())
+            .filter(|&i| structure.atoms[i].elem == "C")
+            .collect_vec();
+        if carbon.is_empty() {
+            return Err("tight-binding model needs at least one carbon atom".into());
+        }
+        let mut orbital = vec![None; structure.atoms.len()];
+        for (index, &i) in carbon.iter().enumerate() {
+            orbital[i] = Some(index);
+        }
+
+        let graph = structure.graph(TB_BOND_CUTOFF);
+        let bonds = graph
+            .edges()
+            .iter()
+            .filter_map(|e| Some((orbital[e.from]?, orbital[e.to]?, e.meta.delta)))
+            .collect();
+
+        Ok(Self {
+            hopping,
+            num_orbitals: carbon.len(),
+            positions: carbon
+                .iter()
+                .map(|&i| Vector3::from(structure.atoms[i].xyz))
+                .collect(),
+            bonds,
+            axis: a.normalize(),
+            length: a.norm(),
+        })
+    }
+
+    /// Number of doubly occupied bands for a neutral ribbon (one pz electron per carbon).
+    pub fn num_occupied(&self) -> usize {
+        self.num_orbitals / 2
+    }
+
+    /// k-points (1/angstrom, along the axis) of a uniform mesh over the Brillouin zone, starting
+    /// at Gamma.
+    pub fn kpoint_mesh(&self, num_kpoints: usize) -> Vec<f64> {
+        let spacing = 2.0 * std::f64::consts::PI / (self.length * num_kpoints as f64);
+        (0..num_kpoints).map(|i| i as f64 * spacing).collect()
+    }
+
+    /// Sum of `element(bond vector)` times the Bloch phase over all bonds, which gives the
+    /// Hamiltonian and its derivatives.
+    fn bloch_sum(
+        &self,
+        k: f64,
+        element: impl Fn(&Vector3<f64>) -> Complex<f64>,
+    ) -> DMatrix<Complex<f64>> {
+        let n = self.num_orbitals;
+        let mut matrix = DMatrix::zeros(n, n);
+        for (from, to, delta) in &self.bonds {
+            let (sin, cos) = (k * self.axis.dot(delta)).sin_cos();
+            let phase = Complex::new(cos, sin);
+            matrix[(*from, *to)] += element(delta) * phase;
+        }
+        matrix
+    }
+
+    /// Bloch Hamiltonian at `k` (1/angstrom, along the axis), using the atomic positions for the
+    /// phases so that its derivative gives the velocity operator.
+    pub fn hamiltonian(&self, k: f64) -> DMatrix<Complex<f64>> {
+        let hopping = Complex::new(-self.hopping, 0.0);
+        self.bloch_sum(k, |_| hopping)
+    }
+
+    /// Derivative of the Hamiltonian with respect to k along `direction` (a unit vector), which
+    /// is proportional to the momentum operator in that direction (eV angstrom).
+    pub fn hamiltonian_derivative(
+        &self,
+        k: f64,
+        direction: &Vector3<f64>,
+    ) -> DMatrix<Complex<f64>> {
+        self.bloch_sum(k, |delta| {
+            Complex::new(0.0, -self.hopping * direction.dot(delta))
+        })
+    }
+
+    /// Energies (eV) and states at `k`, which must be finite.
+    pub fn bands(&self, k: f64) -> Bands {
+        assert!(k.is_finite(), "k-point must be finite");
+        let eigen = self.hamiltonian(k).symmetric_eigen();
+        // with a finite hopping and k the Hamiltonian is finite, and so are its eigenvalues
+        let order = (0..self.num_orbitals)
+            .sorted_by(|&a, &b| {
+                let (a, b) = (eigen.eigenvalues[a], eigen.eigenvalues[b]);
+                a.partial_cmp(&b)
+                    .expect("eigenvalues of a finite Hamiltonian are finite")
+            })
+            .collect_vec();
+        let energies =
+            DVector::from_iterator(order.len(), order.iter().map(|&i| eigen.eigenvalues[i]));
+        let states = DMatrix::from_columns(
+            &order
+                .iter()
+                .map(|&i| eigen.eigenvectors.column(i).into_owned())
+                .collect_vec(),
+        );
+        (energies, states)
+    }
+}
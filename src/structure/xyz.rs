@@ -0,0 +1,28 @@
+use super::AtomicStructure;
+use std::fmt::{self, Write};
+
+impl AtomicStructure {
+    /// Write the structure in extended XYZ format, with the lattice stored in the comment line.
+    pub fn write_xyz<W: Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "{}", self.atoms.len())?;
+
+        let lattice = self
+            .lattice_vectors
+            .iter()
+            .flat_map(|v| v.iter())
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(
+            w,
+            "Lattice=\"{}\" Properties=species:S:1:pos:R:3 pbc=\"T T T\"",
+            lattice
+        )?;
+
+        for atom in &self.atoms {
+            let [x, y, z] = atom.xyz;
+            writeln!(w, "{} {:.8} {:.8} {:.8}", atom.elem, x, y, z)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+from agnr_ml import AGNR, ActiveLearner, GaussianProcess, generate_all_possible_agnrs
+
+
+def _pool():
+    return list(generate_all_possible_agnrs(1, 3, 2, 4))
+
+
+def _label(agnr):
+    # stand-in for DFT, maximized by the widest ribbons
+    return float(max(h - l for l, h in agnr.spec()))
+
+
+def test_finds_maximum():
+    pool = _pool()
+    learner = ActiveLearner(pool, acquisition="ei", seed=1)
+    learner.run(_label, iterations=8, batch_size=2)
+
+    assert len(learner.labelled) == 16
+    assert learner.best[1] == max(_label(agnr) for agnr in pool)
+
+
+def test_never_labels_equivalent_agnrs_twice():
+    pool = _pool()
+    # mirrored and shifted copies of every candidate should be deduplicated
+    mirrored = [AGNR(list(reversed(agnr.spec()))) for agnr in pool]
+    learner = ActiveLearner(pool + mirrored, acquisition="max_variance")
+    labelled = learner.run(_label, iterations=100, batch_size=5)
+
+    specs = [agnr.spec() for agnr, _ in labelled]
+    assert len(specs) == len(set(map(tuple, specs))) == len(pool)
+
+
+def test_checkpoint_resume(tmp_path):
+    path = str(tmp_path / "state.json")
+    model = GaussianProcess(kernel="graph")
+
+    first = ActiveLearner(_pool(), model=model, acquisition="ucb", seed=3)
+    first.run(_label, iterations=2, batch_size=2, checkpoint=path)
+
+    # saved through a temporary file, which is renamed over the checkpoint
+    assert list(tmp_path.iterdir()) == [tmp_path / "state.json"]
+
+    resumed = ActiveLearner.load(path)
+    assert resumed.iteration == 2
+    assert [(a.spec(), v) for a, v in resumed.labelled] == [(a.spec(), v) for a, v in first.labelled]
+    assert [a.spec() for a in resumed.propose(3)] == [a.spec() for a in first.propose(3)]
+
+
+def test_labels_from_outside():
+    learner = ActiveLearner(_pool())
+    learner.add_label(AGNR([(0, 4), (1, 5)]), 1.0)
+    assert all(agnr.spec() != [(0, 4), (1, 5)] for agnr in learner.propose(100))
+
+
+def test_batch_starts_with_best_and_moves_on():
+    learner = ActiveLearner(_pool(), acquisition="ucb")
+    for agnr in _pool()[:5]:
+        learner.add_label(agnr, _label(agnr))
+
+    batch = learner.propose(4)
+    assert batch[0].spec() == learner.propose(1)[0].spec()
+    assert len({tuple(agnr.spec()) for agnr in batch}) == 4
@@ -1,4 +1,4 @@
-from agnr_ml import AGNR, generate_all_possible_agnrs
+from agnr_ml import AGNR, GenerationInterrupted, generate_all_possible_agnrs
 from unittest import TestCase
 from pymatgen import Lattice, Structure
 import pytest
@@ -42,6 +42,18 @@ def test_generation_doesnt_crash():
     generate_all_possible_agnrs(2, 2, 2, 2)
 
 
+@pytest.mark.parametrize("bounds", [(0, 2, 2, 2), (2, 1, 2, 2), (1, 2, 0, 2), (1, 2, 3, 2)])
+def test_generation_bad_bounds(bounds):
+    with pytest.raises(ValueError):
+        list(generate_all_possible_agnrs(*bounds))
+
+
+def test_name_of_unnormalized_spec():
+    assert AGNR([(1, 5), (0, 4)]).name() is None
+    assert AGNR([(0, 4), (1, 5)]).name() == "415"
+    assert AGNR([(0, 4), (1, 99)]).name() is None
+
+
 # For fixed-width GNRs we expect the number of unique generated GNRs to be independent of width and follow
 # https://oeis.org/A006840 (Number of 2n-bead black-white reversible complementable necklaces with n black beads.)
 # since we essentially have two options for each hexagonal segment: shift up (white) or shift down (black) where the
@@ -52,3 +64,46 @@ def test_generation_doesnt_crash():
 def test_generation_constant_width(width: int, length: int, num_expected: int):
     agnrs = generate_all_possible_agnrs(length, length, width, width)
     assert len(list(agnrs)) == num_expected
+
+
+def test_generation_progress():
+    calls = []
+    agnrs = list(generate_all_possible_agnrs(1, 6, 2, 4, progress=lambda *args: calls.append(args)))
+
+    assert len(calls) > 1
+    nodes = [n for n, _ in calls]
+    assert nodes == sorted(nodes)
+    # the last call reports the final count
+    assert calls[-1][1] == len(agnrs)
+
+
+def test_generation_interrupted():
+    def interrupt(nodes, found):
+        raise KeyboardInterrupt
+
+    with pytest.raises(KeyboardInterrupt) as info:
+        list(generate_all_possible_agnrs(1, 6, 2, 4, progress=interrupt))
+
+    assert isinstance(info.value, GenerationInterrupted)
+    complete = {tuple(agnr.spec()) for agnr in generate_all_possible_agnrs(1, 6, 2, 4)}
+    partial = {tuple(agnr.spec()) for agnr in info.value.partial}
+    assert partial <= complete
+    assert len(partial) < len(complete)
+
+
+def test_passivation_report():
+    report = AGNR([(0, 4), (1, 5)]).passivation_report()
+    assert report.num_caps == 4
+    assert [caps for _, caps in report.passivated] == [[8], [9], [10], [11]]
+    assert report.unsaturated == []
+
+
+@pytest.mark.parametrize("bonds", [{"cc_bond": 0.0}, {"ch_bond": -1.0}, {"vacuum_sep": 0.0}])
+def test_to_structure_bad_bonds(bonds):
+    with pytest.raises(ValueError):
+        AGNR([(0, 4), (1, 5)]).to_structure(**bonds)
+
+
+def test_to_structure_inverted_spec():
+    with pytest.raises(ValueError, match="no atoms"):
+        AGNR([(4, 0), (5, 1)]).to_structure()
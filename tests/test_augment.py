@@ -0,0 +1,54 @@
+import pytest
+
+from agnr_ml import AGNR, augment_structures
+
+
+def test_augmentation_is_reproducible():
+    structure = AGNR([(0, 4), (1, 5)]).to_structure()
+    first = augment_structures([structure], 3, max_strain=0.05, biaxial=True, max_angle=10.0, seed=42)
+    second = augment_structures([structure], 3, max_strain=0.05, biaxial=True, max_angle=10.0, seed=42)
+
+    assert len(first) == 1 and len(first[0]) == 3
+    for (a, meta_a), (b, meta_b) in zip(first[0], second[0]):
+        assert a == b
+        assert meta_a == meta_b
+        assert [t["kind"] for t in meta_a] == ["strain", "rotation", "rattle"]
+
+
+def test_strain_scales_periodic_axis():
+    structure = AGNR([(0, 4), (1, 5)]).to_structure()
+    [[(strained, [strain, _, _])]] = augment_structures(
+        [structure], 1, rattle_stddev=0.0, max_strain=0.1, seed=1,
+    )
+
+    expected = structure.lattice.matrix[0][0] * (1.0 + strain["xx"])
+    assert abs(strained.lattice.matrix[0][0] - expected) < 1e-8
+    assert strain["yy"] == 0.0
+
+
+def test_batches_with_nearby_seeds_dont_overlap():
+    structure = AGNR([(0, 4), (1, 5)]).to_structure()
+    kwargs = dict(max_strain=0.05, biaxial=True, max_angle=10.0)
+    first = augment_structures([structure, structure], 2, seed=7, **kwargs)
+    second = augment_structures([structure, structure], 2, seed=8, **kwargs)
+
+    copies = [meta for batch in (first, second) for copies in batch for _, meta in copies]
+    strains = [meta[0]["xx"] for meta in copies]
+    assert len(set(strains)) == len(strains)
+
+
+@pytest.mark.parametrize(
+    "kwargs",
+    [
+        dict(max_strain=1.0),
+        dict(max_strain=-0.1),
+        dict(max_strain=float("inf")),
+        dict(max_angle=float("inf")),
+        dict(max_angle=float("nan")),
+        dict(rattle_stddev=-0.01),
+    ],
+)
+def test_bad_amplitudes(kwargs):
+    structure = AGNR([(0, 4), (1, 5)]).to_structure()
+    with pytest.raises(ValueError):
+        augment_structures([structure], 1, **kwargs)
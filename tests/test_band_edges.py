@@ -0,0 +1,79 @@
+import math
+
+import pytest
+from pymatgen import Lattice, Structure
+
+from agnr_ml import AGNR, band_edges
+
+AGNR7 = AGNR([(0, 8), (1, 7)])
+
+
+def zigzag_ribbon(chains, hydrogen=True):
+    """Zigzag graphene nanoribbon with `chains` zigzag chains, periodic along x."""
+    a, bond = 2.46, 1.42
+    species, coords = [], []
+    for n in range(chains):
+        y = 1.5 * bond * n
+        species += ["C", "C"]
+        coords += [[(n * a / 2) % a, y, 0.0], [((n + 1) * a / 2) % a, y + bond / 2, 0.0]]
+    if hydrogen:
+        top = 1.5 * bond * (chains - 1) + bond / 2
+        species += ["H", "H"]
+        coords += [[0.0, -1.09, 0.0], [(chains * a / 2) % a, top + 1.09, 0.0]]
+    width = 1.5 * bond * chains
+    return Structure(
+        lattice=Lattice([[a, 0.0, 0.0], [0.0, width + 15.0, 0.0], [0.0, 0.0, 15.0]]),
+        species=species,
+        coords=[[x, y + 7.5, z + 7.5] for x, y, z in coords],
+        coords_are_cartesian=True,
+    )
+
+
+def test_armchair():
+    edges = band_edges(AGNR7, num_kpoints=30)
+    # tight-binding gap of 7-AGNR, direct at Gamma
+    expected = min(2 * 2.7 * abs(1 + 2 * math.cos(p * math.pi / 8)) for p in range(1, 8))
+    assert edges.gap == pytest.approx(expected, abs=1e-6)
+    assert edges.vbm_kpoint == pytest.approx(0.0, abs=1e-6)
+    assert edges.cbm_kpoint == pytest.approx(0.0, abs=1e-6)
+
+    # nearest-neighbor tight binding is particle-hole symmetric
+    assert edges.hole_mass > 0
+    assert edges.hole_mass == pytest.approx(edges.electron_mass, rel=1e-4)
+    # masses scale inversely with the hopping
+    stiffer = band_edges(AGNR7, num_kpoints=30, hopping=5.4)
+    assert stiffer.electron_mass == pytest.approx(edges.electron_mass / 2, rel=1e-4)
+
+    assert not edges.has_edge_states
+    assert edges.edge_states == []
+    assert 0 < edges.cbm_edge_weight < 0.5
+
+
+def test_coarse_mesh():
+    # masses come from finite differences at the extrema, not from the mesh
+    coarse = band_edges(AGNR([(0, 6), (1, 7), (2, 8), (1, 7)]), num_kpoints=5)
+    fine = band_edges(AGNR([(0, 6), (1, 7), (2, 8), (1, 7)]), num_kpoints=80)
+    assert coarse.gap == pytest.approx(fine.gap, abs=1e-6)
+    assert coarse.cbm_kpoint == pytest.approx(fine.cbm_kpoint, abs=1e-4)
+    assert coarse.electron_mass == pytest.approx(fine.electron_mass, rel=1e-3)
+
+
+def test_zigzag_edge_states():
+    for hydrogen in [False, True]:
+        edges = band_edges(zigzag_ribbon(4, hydrogen), num_kpoints=60)
+        assert edges.gap == pytest.approx(0.0, abs=1e-2)
+        assert edges.has_edge_states
+        # the flat edge bands are between 2/3 of the way to the zone boundary and the boundary
+        assert all(abs(k) > 0.3 for k, _, _, _ in edges.edge_states)
+        assert all(weight >= 0.5 for _, _, _, weight in edges.edge_states)
+        assert all(abs(energy) < 1.0 for _, _, energy, _ in edges.edge_states)
+
+    strict = band_edges(zigzag_ribbon(4), num_kpoints=60, edge_threshold=0.99)
+    assert len(strict.edge_states) < len(edges.edge_states)
+
+
+def test_invalid_arguments():
+    with pytest.raises(ValueError):
+        band_edges(AGNR7, num_kpoints=2)
+    with pytest.raises(ValueError):
+        band_edges(AGNR7, hopping=0.0)
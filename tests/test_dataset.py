@@ -0,0 +1,44 @@
+import pytest
+
+from agnr_ml import AGNR, generate_all_possible_agnrs, read_dataset, write_dataset
+
+
+def test_dataset_round_trip(tmp_path):
+    path = str(tmp_path / "dataset.jsonl")
+    agnrs = list(generate_all_possible_agnrs(2, 4, 2, 3))
+    descriptors = [{"index": float(i)} for i in range(len(agnrs))]
+
+    assert write_dataset(path, agnrs, descriptors) == len(agnrs)
+
+    records = list(read_dataset(path))
+    assert len(records) == len(agnrs)
+    for original, (agnr, structure, desc), expected in zip(agnrs, records, descriptors):
+        assert agnr.spec() == original.spec()
+        assert structure == original.to_structure()
+        assert desc == expected
+
+
+def test_dataset_without_descriptors(tmp_path):
+    path = str(tmp_path / "dataset.jsonl")
+    write_dataset(path, [AGNR([(0, 4), (1, 5)])])
+
+    [(agnr, structure, desc)] = list(read_dataset(path))
+    assert agnr.spec() == [(0, 4), (1, 5)]
+    assert len(structure) == 12
+    assert desc == {}
+
+
+def test_dataset_length_mismatch(tmp_path):
+    path = str(tmp_path / "dataset.jsonl")
+    agnrs = [AGNR([(0, 4), (1, 5)]), AGNR([(0, 6), (1, 7)])]
+
+    with pytest.raises(ValueError, match="fewer descriptors"):
+        write_dataset(path, agnrs, [{"index": 0.0}])
+    with pytest.raises(ValueError, match="more descriptors"):
+        write_dataset(path, agnrs, [{"index": 0.0}] * 3)
+
+
+def test_dataset_invalid_spec(tmp_path):
+    path = str(tmp_path / "dataset.jsonl")
+    with pytest.raises(OSError, match="not a valid AGNR spec"):
+        write_dataset(path, [AGNR([(0, 4), (1, 5), (10, 14), (1, 5)])])
@@ -0,0 +1,80 @@
+import math
+
+import pytest
+
+from agnr_ml import AGNR, add_defect
+
+
+def count(structure, elem):
+    return sum(str(s) == elem for s in structure.species)
+
+
+def test_interior_vacancy():
+    agnr = AGNR([(0, 8), (1, 9)])
+    structure = agnr.to_structure()
+    # column 0 has carbon pairs at heights 0, 2, 4, 6, so atom 2 is in the interior
+    defected = add_defect(structure, "vacancy", [2])
+
+    assert count(defected, "C") == count(structure, "C") - 1
+    # each of the three neighbors is re-passivated
+    assert count(defected, "H") == count(structure, "H") + 3
+
+
+def test_edge_vacancy():
+    agnr = AGNR([(0, 8), (1, 9)])
+    structure = agnr.to_structure()
+    # removing one atom of an edge dimer also removes the other one and both hydrogens
+    defected = add_defect(structure, "vacancy", [0])
+    assert count(defected, "C") == count(structure, "C") - 2
+    assert count(defected, "H") == count(structure, "H")
+
+
+def test_stone_wales():
+    agnr = AGNR([(0, 8), (1, 9)])
+    structure = agnr.to_structure()
+    defected = add_defect(structure, "stone_wales", [2, 3])
+
+    assert len(defected) == len(structure)
+    before = [b - a for a, b in zip(structure.cart_coords[2], structure.cart_coords[3])]
+    after = [b - a for a, b in zip(defected.cart_coords[2], defected.cart_coords[3])]
+    assert math.hypot(*after) == pytest.approx(math.hypot(*before))
+    assert sum(a * b for a, b in zip(before, after)) == pytest.approx(0.0, abs=1e-9)
+
+    with pytest.raises(ValueError):
+        # atom 0 is on the edge
+        add_defect(structure, "stone_wales", [0, 1])
+    with pytest.raises(ValueError):
+        add_defect(structure, "stone_wales", [2, 6])
+
+
+def test_adatom():
+    structure = AGNR([(0, 8), (1, 9)]).to_structure()
+    top = add_defect(structure, "adatom", [2], element="F", height=1.4)
+    bridge = add_defect(structure, "adatom", [2, 3], height=1.5)
+
+    assert len(top) == len(structure) + 1
+    assert str(top.species[-1]) == "F"
+    assert top.cart_coords[-1][2] - structure.cart_coords[2][2] == pytest.approx(1.4)
+    expected_x = (structure.cart_coords[2][0] + structure.cart_coords[3][0]) / 2
+    assert bridge.cart_coords[-1][0] == pytest.approx(expected_x)
+
+
+def test_distinct_defects():
+    agnr = AGNR([(0, 8), (1, 9)])
+    n_carbon = count(agnr.to_structure(), "C")
+
+    for kind in ["vacancy", "adatom"]:
+        defects = agnr.distinct_defects(kind)
+        # the mirror planes leave a quarter of the sites distinct
+        assert len(defects) == n_carbon // 4
+        assert sum(m for _, _, m in defects) == n_carbon
+
+    # repeating the cell doesn't add distinct sites
+    repeated = agnr.distinct_defects("vacancy", repeats=2)
+    assert [m for _, _, m in repeated] == [2 * m for _, _, m in agnr.distinct_defects("vacancy")]
+
+    stone_wales = agnr.distinct_defects("stone_wales")
+    assert all(len(sites) == 2 for _, sites, _ in stone_wales)
+
+    with pytest.raises(ValueError):
+        agnr.distinct_defects("interstitial")
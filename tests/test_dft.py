@@ -0,0 +1,128 @@
+import os
+
+import pytest
+
+from agnr_ml import AGNR, DftInputs, generate_all_possible_agnrs, write_dft_inputs
+
+
+def test_kpoint_mesh():
+    inputs = DftInputs("vasp", k_spacing=0.1)
+    short = AGNR([(0, 6), (1, 7)])._spec.to_structure()
+    long = AGNR([(0, 6), (1, 7), (2, 8), (1, 7)])._spec.to_structure()
+
+    # 2 pi / 4.26135 angstroms is about 1.47 1/angstrom
+    assert inputs.kpoint_mesh(short) == (15, 1, 1)
+    assert inputs.kpoint_mesh(long) == (8, 1, 1)
+
+
+def test_vasp_files():
+    structure = AGNR([(0, 6), (1, 7)])._spec.to_structure()
+    inputs = DftInputs("vasp", settings={"ENCUT": 520, "LORBIT": 11, "LCHARG": False})
+    files = inputs.files(structure, "617")
+
+    assert set(files) == {"POSCAR", "INCAR", "KPOINTS", "bands/POSCAR", "bands/INCAR", "bands/KPOINTS"}
+    assert files["POSCAR"].splitlines()[0] == "617"
+    assert files["bands/POSCAR"] == files["POSCAR"]
+    incar = files["INCAR"].splitlines()
+    assert "ENCUT = 520" in incar
+    assert "LORBIT = 11" in incar
+    assert "LCHARG = .FALSE." in incar
+    assert "EDIFF = 1e-6" in incar
+    assert not any(line.startswith("ICHARG") for line in incar)
+    assert files["KPOINTS"].splitlines()[2:4] == ["Gamma", "10 1 1"]
+    assert "Line-mode" in files["bands/KPOINTS"]
+    # the band structure reads the scf charge density
+    bands_incar = files["bands/INCAR"].splitlines()
+    assert "ICHARG = 11" in bands_incar
+    assert "ENCUT = 520" in bands_incar
+
+
+def test_vasp_bands_incar_defaults():
+    structure = AGNR([(0, 6), (1, 7)])._spec.to_structure()
+    files = DftInputs("vasp", settings={"ICHARG": 2}).files(structure, "617")
+    assert "LCHARG = .TRUE." in files["INCAR"].splitlines()
+    assert "ICHARG = 2" in files["INCAR"].splitlines()
+    assert "LCHARG = .FALSE." in files["bands/INCAR"].splitlines()
+    assert "ICHARG = 11" in files["bands/INCAR"].splitlines()
+
+
+def test_templates():
+    structure = AGNR([(0, 6), (1, 7)])._spec.to_structure()
+    inputs = DftInputs("vasp", templates={
+        "INCAR": "SYSTEM = {{ name }}\nENCUT = 600\n",
+        "KPOINTS": "{{default}}",
+        "job.sh": "#!/bin/bash\n# ${HOME} {{name}}: {{num_atoms}} atoms, {{kpoint_mesh}}\n",
+    })
+    files = inputs.files(structure, "617")
+    assert files["INCAR"] == "SYSTEM = 617\nENCUT = 600\n"
+    assert files["KPOINTS"] == DftInputs("vasp").files(structure, "617")["KPOINTS"]
+    assert files["job.sh"] == "#!/bin/bash\n# ${HOME} 617: 16 atoms, 10 1 1\n"
+
+    qe = DftInputs("qe", templates={"scf.in": "&CONTROL\n  prefix = '{{name}}'\n/\n{{cards}}"})
+    scf = qe.files(structure, "617")["scf.in"]
+    assert scf.startswith("&CONTROL\n  prefix = '617'\n/\nATOMIC_SPECIES\n")
+    assert scf.splitlines()[-1] == "  10 1 1 0 0 0"
+
+
+@pytest.mark.parametrize("templates", [
+    {"INCAR": "{{unknown}}"},
+    {"INCAR": "{{name"},
+    {"job.sh": "{{default}}"},
+    {"../INCAR": ""},
+    {"/tmp/INCAR": ""},
+])
+def test_bad_templates(templates):
+    structure = AGNR([(0, 6), (1, 7)])._spec.to_structure()
+    with pytest.raises(ValueError):
+        DftInputs("vasp", templates=templates).files(structure, "617")
+
+
+def test_qe_files():
+    structure = AGNR([(0, 6), (1, 7)])._spec.to_structure()
+    inputs = DftInputs(
+        "qe",
+        band_points=20,
+        settings={"system.ecutwfc": 60.0, "system.nspin": 2, "control.calculation": "relax"},
+        pseudopotentials={"C": "C.pbe-n-kjpaw_psl.1.0.0.UPF"},
+    )
+    files = inputs.files(structure, "617")
+
+    scf, bands = files["scf.in"], files["bands.in"]
+    assert "  calculation = 'scf'" in scf.splitlines()
+    assert "  calculation = 'bands'" in bands.splitlines()
+    assert "  ecutwfc = 60" in scf.splitlines()
+    assert "  nspin = 2" in scf.splitlines()
+    assert "  nat = 16" in scf.splitlines()
+    assert "  C 12.011 C.pbe-n-kjpaw_psl.1.0.0.UPF" in scf.splitlines()
+    assert "  H 1.008 H.UPF" in scf.splitlines()
+    assert scf.splitlines()[-1] == "  10 1 1 0 0 0"
+    assert bands.splitlines()[-2:] == ["  0.0 0.0 0.0 19", "  0.5 0.0 0.0 1"]
+    # namelists must come in order
+    assert scf.index("&CONTROL") < scf.index("&SYSTEM") < scf.index("&ELECTRONS")
+
+
+def test_invalid_settings():
+    with pytest.raises(ValueError):
+        DftInputs("qe", settings={"ecutwfc": 60.0})
+    with pytest.raises(ValueError):
+        DftInputs("castep")
+    with pytest.raises(ValueError):
+        DftInputs("vasp", k_spacing=0.0)
+    with pytest.raises(ValueError):
+        DftInputs("vasp", k_spacing=float("nan"))
+    with pytest.raises(ValueError):
+        DftInputs("vasp", k_spacing=float("inf"))
+
+
+def test_batch(tmp_path):
+    agnrs = list(generate_all_possible_agnrs(1, 2, 3, 3))
+    written = write_dft_inputs(agnrs, str(tmp_path), code="qe")
+
+    assert sorted(written) == sorted(str(tmp_path / agnr.name()) for agnr in agnrs)
+    for path in written:
+        assert sorted(os.listdir(path)) == ["bands.in", "scf.in"]
+
+    written = write_dft_inputs(agnrs[:1], str(tmp_path / "vasp"), templates={"job.sh": "{{name}}"})
+    assert sorted(os.listdir(os.path.join(written[0], "bands"))) == ["INCAR", "KPOINTS", "POSCAR"]
+    with open(os.path.join(written[0], "job.sh")) as f:
+        assert f.read() == agnrs[0].name()
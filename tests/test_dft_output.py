@@ -0,0 +1,118 @@
+import os
+
+import pytest
+
+from agnr_ml import (
+    AGNR,
+    DftOutput,
+    add_defect,
+    generate_all_possible_agnrs,
+    read_dataset,
+    read_dft_outputs,
+    write_dataset,
+)
+
+DATA = os.path.join(os.path.dirname(__file__), "data", "dft")
+VASPRUN = os.path.join(DATA, "415", "vasprun.xml")
+QE_XML = os.path.join(DATA, "617", "tmp", "617.xml")
+
+
+def test_vasprun():
+    output = DftOutput.read(VASPRUN)
+
+    assert output.code == "vasp"
+    assert output.energy == pytest.approx(-93.21043817)
+    assert output.fermi_energy == pytest.approx(-0.7502)
+    assert output.kpoints == [(0.0, 0.0, 0.0), (0.25, 0.0, 0.0), (0.5, 0.0, 0.0)]
+    assert len(output.eigenvalues) == 1
+    assert [len(bands) for bands in output.eigenvalues[0]] == [22, 22, 22]
+    assert len(output.structure.types()) == 12
+
+    gap = output.band_gap()
+    # valence band maximum at X, conduction band minimum at Gamma
+    assert gap.gap == pytest.approx(1.2)
+    assert gap.direct_gap == pytest.approx(1.5)
+    assert not gap.is_direct
+    assert (gap.vbm_kpoint, gap.cbm_kpoint) == (2, 0)
+
+
+def test_qe_xml():
+    output = DftOutput.read(QE_XML)
+
+    assert output.code == "qe"
+    assert output.energy == pytest.approx(-119.1385502474581 * 27.211386245988)
+    assert output.fermi_energy == pytest.approx(-3.55)
+    # spin polarized, with bands split between the two channels
+    assert len(output.eigenvalues) == 2
+    assert len(output.eigenvalues[1][0]) == 30
+    assert output.kpoints[2] == pytest.approx((0.5, 0.0, 0.0))
+    # positions are converted from bohr
+    assert output.structure.lattice()[2][2] == pytest.approx(15.0)
+
+    gap = output.band_gap()
+    assert gap.gap == pytest.approx(0.9)
+    assert gap.is_direct
+    assert (gap.vbm, gap.cbm) == (pytest.approx(-4.0), pytest.approx(-3.1))
+
+
+def test_labels():
+    labels = DftOutput.read(VASPRUN).labels()
+    assert labels["band_gap"] == pytest.approx(1.2)
+    assert labels["is_direct"] == 0.0
+    assert labels["vbm_k"] == 0.5
+    assert labels["cbm_k"] == 0.0
+    assert labels["energy_per_atom"] == pytest.approx(-93.21043817 / 12)
+
+
+def test_recognize_structure():
+    for agnr in generate_all_possible_agnrs(1, 3, 2, 4):
+        assert AGNR.from_structure(agnr.to_structure()).spec() == agnr.spec()
+        stretched = agnr.to_structure(cc_bond=1.44)
+        assert AGNR.from_structure(stretched).spec() == agnr.spec()
+
+    # any equivalent spec is recognized as the canonical one
+    assert AGNR.from_structure(AGNR([(1, 5), (0, 4)]).to_structure()).spec() == [(0, 4), (1, 5)]
+
+    structure = AGNR([(0, 8), (1, 9)]).to_structure()
+    assert AGNR.from_structure(add_defect(structure, "vacancy", [2])) is None
+
+
+def test_agnr_from_name():
+    # without carbon the structure can't be recognized, so the name is used instead
+    with open(VASPRUN) as f:
+        xml = f.read().replace("<c>C </c>", "<c>Si</c>")
+    output = DftOutput.from_xml(xml)
+
+    assert output.agnr(None) is None
+    assert output.agnr("415").spec == [(0, 4), (1, 5)]
+    assert output.agnr("not an agnr") is None
+
+
+def test_invalid_output():
+    with pytest.raises(ValueError):
+        DftOutput.from_xml("<modeling></modeling>")
+    with pytest.raises(ValueError):
+        DftOutput.from_xml("<calculation/>")
+    with pytest.raises(ValueError):
+        DftOutput.from_xml("not xml")
+
+
+def test_labelled_dataset(tmp_path):
+    results = read_dft_outputs(DATA)
+    assert [path for path, _, _ in results] == [VASPRUN, QE_XML]
+    assert [agnr.name() for _, agnr, _ in results] == ["415", "617"]
+
+    path = str(tmp_path / "dataset.jsonl")
+    agnrs = [agnr for _, agnr, _ in results]
+    outputs = [output for _, _, output in results]
+    assert write_dataset(path, agnrs, outputs=outputs) == 2
+
+    records = list(read_dataset(path, labels=True))
+    for (agnr, structure, desc, labels), output in zip(records, outputs):
+        assert desc == {}
+        assert labels == output.labels()
+        # records keep the final structure of the run
+        assert list(structure.cart_coords[0]) == pytest.approx(output.structure.coords()[0])
+
+    # labels are only returned when asked for
+    assert all(len(record) == 3 for record in read_dataset(path))
@@ -0,0 +1,45 @@
+import pytest
+
+from agnr_ml import generate_all_possible_agnrs, genetic_search
+
+
+def num_wide_columns(agnr) -> float:
+    return float(sum(high - low for low, high in agnr.spec()))
+
+
+def mean_column_width(agnr) -> float:
+    return num_wide_columns(agnr) / len(agnr.spec())
+
+
+def test_genetic_search_finds_optimum():
+    results = genetic_search(mean_column_width, 2, 3, 2, 3, population_size=10, generations=10, seed=0)
+
+    specs = [tuple(agnr.spec()) for agnr, _ in results]
+    assert len(specs) == len(set(specs))
+    assert [v for _, v in results] == sorted((v for _, v in results), reverse=True)
+
+    # every result should be the primitive cell of one of the enumerated AGNRs, which include
+    # supercells of shorter ones
+    all_agnrs = {tuple(agnr.primitive().spec()): agnr for agnr in generate_all_possible_agnrs(2, 3, 2, 3)}
+    assert all(spec in all_agnrs for spec in specs)
+    assert all(agnr.is_primitive() for agnr, _ in results)
+
+    best = max(mean_column_width(agnr) for agnr in all_agnrs.values())
+    assert results[0][1] == best
+
+
+def test_genetic_search_is_reproducible():
+    first = genetic_search(num_wide_columns, 2, 4, 2, 4, population_size=8, generations=3, seed=7)
+    second = genetic_search(num_wide_columns, 2, 4, 2, 4, population_size=8, generations=3, seed=7)
+    assert [(a.spec(), v) for a, v in first] == [(a.spec(), v) for a, v in second]
+
+
+@pytest.mark.parametrize(
+    "kwargs",
+    [dict(mutation_rate=1.5), dict(crossover_rate=-0.1), dict(mutation_rate=float("nan")), dict(min_len=0)],
+)
+def test_genetic_search_bad_arguments(kwargs):
+    args = dict(min_len=2, max_len=3, min_width=2, max_width=3, population_size=4, generations=1)
+    args.update(kwargs)
+    with pytest.raises(ValueError):
+        genetic_search(num_wide_columns, **args)
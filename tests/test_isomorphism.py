@@ -0,0 +1,107 @@
+import math
+import random
+
+import pytest
+from pymatgen import Lattice, Structure
+
+from agnr_ml import (
+    AGNR,
+    add_defect,
+    generate_all_possible_agnrs,
+    is_same_structure,
+    structure_hash,
+    unique_structures,
+)
+
+
+def shuffled(structure, seed=0):
+    """Same structure with atoms in a random order, shifted by a random vector (wrapping atoms
+    across the periodic boundary), and slightly rattled."""
+    rng = random.Random(seed)
+    shift = [rng.uniform(0, 5) for _ in range(3)]
+    lengths = [structure.lattice.matrix[i][i] for i in range(3)]
+    atoms = [
+        (str(s), [(x + d + rng.uniform(-0.02, 0.02)) % l for x, d, l in zip(c, shift, lengths)])
+        for s, c in zip(structure.species, structure.cart_coords)
+    ]
+    rng.shuffle(atoms)
+    return Structure(
+        lattice=Lattice(structure.lattice.matrix),
+        species=[s for s, _ in atoms],
+        coords=[c for _, c in atoms],
+        coords_are_cartesian=True,
+    )
+
+
+def graphene(lattice):
+    a = 2.46
+    a1, a2 = [a, 0.0, 0.0], [a / 2, a * math.sqrt(3) / 2, 0.0]
+    sites = [[(a1[k] + a2[k]) / 3 for k in range(3)], [2 * (a1[k] + a2[k]) / 3 for k in range(3)]]
+    return Structure(
+        lattice=Lattice(lattice(a1, a2) + [[0.0, 0.0, 15.0]]),
+        species=["C", "C"],
+        coords=[[x, y, 7.5] for x, y, _ in sites],
+        coords_are_cartesian=True,
+    )
+
+
+def test_hash_invariance():
+    for agnr in generate_all_possible_agnrs(1, 3, 2, 3):
+        structure = agnr.to_structure()
+        other = shuffled(structure)
+        assert structure_hash(other) == structure_hash(structure)
+        assert is_same_structure(other, structure)
+
+
+def test_equivalent_specs():
+    spec = [(0, 4), (1, 5), (2, 6), (1, 5), (0, 4), (1, 5)]
+    width = max(h for _, h in spec)
+    mirrored = [(width - h, width - l) for l, h in reversed(spec)]
+    a, b = AGNR(spec).to_structure(), AGNR(mirrored).to_structure()
+
+    assert structure_hash(a) == structure_hash(b)
+    assert is_same_structure(a, b)
+
+
+def test_distinct_structures():
+    agnrs = list(generate_all_possible_agnrs(1, 3, 2, 3))
+    structures = [agnr.to_structure() for agnr in agnrs]
+    assert len({structure_hash(s) for s in structures}) == len(agnrs)
+
+    # a supercell isn't the same structure as its unit cell
+    with pytest.warns(UserWarning):
+        repeated = AGNR([(0, 4), (1, 5), (0, 4), (1, 5)]).to_structure()
+    assert structure_hash(repeated) != structure_hash(structures[0])
+    assert not is_same_structure(repeated, AGNR([(0, 4), (1, 5)]).to_structure())
+
+
+def test_defects():
+    structure = AGNR([(0, 8), (1, 9)]).to_structure()
+    # atoms 2 and 3 are the two atoms of an interior pair, related by a mirror plane
+    a = add_defect(structure, "vacancy", [2])
+    b = add_defect(structure, "vacancy", [3])
+    c = add_defect(structure, "vacancy", [4])
+
+    assert structure_hash(a) == structure_hash(b)
+    assert is_same_structure(a, b)
+    assert structure_hash(a) != structure_hash(c)
+    assert not is_same_structure(a, c)
+
+
+def test_different_cell():
+    primitive = graphene(lambda a1, a2: [a1, a2])
+    skewed = graphene(lambda a1, a2: [a1, [x + y for x, y in zip(a1, a2)]])
+
+    assert is_same_structure(primitive, shuffled(primitive))
+    # the lattice spanned by the bonds is the same, so the hashes collide...
+    assert structure_hash(skewed) == structure_hash(primitive)
+    # ...but the bonds connect different images in the two cells
+    assert not is_same_structure(skewed, primitive)
+
+
+def test_unique_structures():
+    a = AGNR([(0, 4), (1, 5)]).to_structure()
+    b = AGNR([(0, 6), (1, 7)]).to_structure()
+    structures = [a, shuffled(a), b, shuffled(b, seed=1), AGNR([(1, 5), (0, 4)]).to_structure()]
+
+    assert unique_structures(structures) == [0, 2]
@@ -0,0 +1,92 @@
+import pytest
+
+from agnr_ml import AGNR, GaussianProcess, KernelRidge, generate_all_possible_agnrs
+
+
+def _training_data():
+    agnrs = sorted(generate_all_possible_agnrs(1, 3, 2, 4), key=lambda a: a.spec())
+    # an arbitrary smooth function of the width for the models to learn
+    targets = [sum(h - l for l, h in agnr.spec()) / len(agnr) for agnr in agnrs]
+    return agnrs, targets
+
+
+@pytest.mark.parametrize("kernel", ["rbf", "graph"])
+def test_gaussian_process_interpolates(kernel):
+    agnrs, targets = _training_data()
+    gp = GaussianProcess(kernel=kernel).fit(agnrs, targets)
+
+    means, stds = gp.predict(agnrs)
+    for mean, std, target in zip(means, stds, targets):
+        assert mean == pytest.approx(target, abs=1e-2)
+        assert std < 1e-1
+
+
+def test_gaussian_process_uncertainty_grows_away_from_data():
+    agnrs, targets = _training_data()
+    narrow = [(a, t) for a, t in zip(agnrs, targets) if max(h - l for l, h in a.spec()) <= 6]
+    gp = GaussianProcess().fit([a for a, _ in narrow], [t for _, t in narrow])
+
+    (_, near), (_, far) = gp.predict([AGNR([(0, 6), (1, 7)])]), gp.predict([AGNR([(0, 16), (1, 17)])])
+    assert far[0] > near[0]
+
+
+def test_kernel_ridge_descriptors():
+    inputs = [[float(x), float(x) ** 2] for x in range(10)]
+    targets = [2.0 * x + 1.0 for x in range(10)]
+    model = KernelRidge(alpha=1e-6).fit(inputs, targets)
+    assert model.predict([[4.5, 20.25]])[0] == pytest.approx(10.0, abs=0.1)
+
+
+def test_graph_kernel_rejects_descriptors():
+    with pytest.raises(ValueError):
+        GaussianProcess(kernel="graph").fit([[1.0], [2.0]], [1.0, 2.0])
+
+
+def test_save_load_roundtrip(tmp_path):
+    agnrs, targets = _training_data()
+    gp = GaussianProcess(kernel="graph").fit(agnrs, targets)
+    gp.save(str(tmp_path / "gp.json"))
+    loaded = GaussianProcess.load(str(tmp_path / "gp.json"))
+    assert loaded.predict(agnrs) == gp.predict(agnrs)
+
+    krr = KernelRidge().fit(agnrs, targets)
+    krr.save(str(tmp_path / "krr.json"))
+    assert KernelRidge.load(str(tmp_path / "krr.json")).predict(agnrs) == krr.predict(agnrs)
+
+
+def test_screen_returns_best_first():
+    agnrs, targets = _training_data()
+    gp = GaussianProcess().fit(agnrs, targets)
+
+    results = gp.screen(1, 3, 2, 4, top=5)
+    assert len(results) == 5
+    means = [mean for _, mean, _ in results]
+    assert means == sorted(means, reverse=True)
+
+
+def test_screen_top_matches_full_ranking():
+    agnrs, targets = _training_data()
+    krr = KernelRidge().fit(agnrs, targets)
+
+    full = krr.screen(1, 3, 2, 4)
+    assert len(full) == len(agnrs)
+    top = krr.screen(1, 3, 2, 4, top=7)
+    assert [(a.spec(), mean) for a, mean in top] == [(a.spec(), mean) for a, mean in full[:7]]
+
+
+def test_predict_rejects_wrong_descriptor_length():
+    model = KernelRidge().fit([[1.0, 2.0], [3.0, 5.0]], [1.0, 2.0])
+    with pytest.raises(ValueError, match="expected 2 descriptors"):
+        model.predict([[1.0]])
+
+
+@pytest.mark.parametrize("kwargs", [
+    dict(length_scale=0.0),
+    dict(length_scale=float("nan")),
+    dict(kernel="graph", length_scale=-1.0),
+    dict(noise=0.0),
+    dict(noise=float("inf")),
+])
+def test_bad_hyperparameters(kwargs):
+    with pytest.raises(ValueError):
+        GaussianProcess(**kwargs)
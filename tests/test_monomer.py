@@ -0,0 +1,57 @@
+import math
+from collections import Counter
+
+import pytest
+
+from agnr_ml import AGNR, precursor_monomer
+
+
+def formula(structure):
+    return dict(Counter(str(s) for s in structure.species))
+
+
+def test_bianthryl():
+    # 10,10'-dibromo-9,9'-bianthryl, the usual precursor of 7-AGNR
+    structure, halogens = precursor_monomer(AGNR([(0, 8), (1, 7)]))
+    assert formula(structure) == {"C": 28, "H": 16, "Br": 2}
+    assert [str(structure.species[i]) for i in halogens] == ["Br", "Br"]
+
+    coords = structure.cart_coords
+    for i in halogens:
+        nearest = min(math.dist(coords[i], c) for j, c in enumerate(coords) if j != i)
+        assert nearest == pytest.approx(1.90)
+    # the halogens are at opposite ends, along the polymer axis
+    a, b = (coords[i] for i in halogens)
+    assert abs(a[0] - b[0]) > 10.0
+    assert a[1:] == pytest.approx(b[1:])
+
+
+def test_no_clashes():
+    for spec in [[(0, 8), (1, 7)], [(0, 4), (1, 3)], [(0, 8), (1, 7), (0, 8), (1, 9), (0, 10), (1, 9)]]:
+        structure, _ = precursor_monomer(AGNR(spec))
+        coords = structure.cart_coords
+        distances = [math.dist(a, b) for i, a in enumerate(coords) for b in coords[:i]]
+        assert min(distances) > 1.0
+        # every carbon is sp2, bonded to three atoms
+        for i, s in enumerate(structure.species):
+            if str(s) == "C":
+                bonded = [j for j, c in enumerate(coords) if j != i and math.dist(coords[i], c) < 2.0]
+                assert len(bonded) == 3
+
+
+def test_other_monomers():
+    # dibromobiphenyl (poly-para-phenylene), dibromobinaphthyl, and dibromoanthracene
+    assert formula(precursor_monomer(AGNR([(0, 4), (1, 3)]))[0]) == {"C": 12, "H": 8, "Br": 2}
+    assert formula(precursor_monomer(AGNR([(0, 6), (1, 5)]))[0]) == {"C": 20, "H": 12, "Br": 2}
+    structure, _ = precursor_monomer(AGNR([(0, 8), (1, 7)]), halogen="I", units=1)
+    assert formula(structure) == {"C": 14, "H": 8, "I": 2}
+
+
+def test_no_monomer():
+    # even width AGNRs can't be split into aromatic units without leaving dangling carbons
+    assert precursor_monomer(AGNR([(0, 6), (1, 7)])) is None
+
+    with pytest.raises(ValueError):
+        precursor_monomer(AGNR([(0, 8), (1, 7)]), halogen="F")
+    with pytest.raises(ValueError):
+        precursor_monomer(AGNR([(0, 8), (1, 7)]), units=0)
@@ -0,0 +1,75 @@
+import pytest
+from pymatgen import Structure
+
+from agnr_ml import AGNR, build_multilayer
+
+
+def test_bilayer_ab():
+    agnr = AGNR([(0, 6), (1, 7)])
+    single = agnr.to_structure()
+    bilayer, layers, _ = build_multilayer([agnr, agnr], stacking="ab", layer_sep=3.4)
+
+    n = len(single)
+    assert len(bilayer) == 2 * n
+    assert layers == [0] * n + [1] * n
+
+    bottom, top = bilayer.cart_coords[:n], bilayer.cart_coords[n:]
+    for b, t in zip(bottom, top):
+        assert t[0] - b[0] == pytest.approx(1.42045)
+        assert t[1] == pytest.approx(b[1])
+        assert t[2] - b[2] == pytest.approx(3.4)
+
+
+def test_ab_shift_follows_bond_length():
+    agnr = AGNR([(0, 6), (1, 7)])
+    n = len(agnr.to_structure())
+    bilayer, _, _ = build_multilayer([agnr, agnr], cc_bond=1.5)
+
+    bottom, top = bilayer.cart_coords[:n], bilayer.cart_coords[n:]
+    for b, t in zip(bottom, top):
+        assert t[0] - b[0] == pytest.approx(1.5)
+
+
+def test_aa_and_offset():
+    agnr = AGNR([(0, 6), (1, 7)])
+    aa, _, _ = build_multilayer([agnr] * 3, stacking="aa")
+    offset, _, _ = build_multilayer([agnr] * 3, stacking="offset", shift=(0.5, 0.25))
+
+    n = len(agnr.to_structure())
+    for i in range(n):
+        assert aa.cart_coords[2 * n + i][0] == pytest.approx(aa.cart_coords[i][0])
+        assert offset.cart_coords[2 * n + i][0] == pytest.approx(offset.cart_coords[i][0] + 1.0)
+        assert offset.cart_coords[2 * n + i][1] == pytest.approx(offset.cart_coords[i][1] + 0.5)
+
+
+def test_mismatched_lengths_use_common_supercell():
+    short = AGNR([(0, 6), (1, 7)])
+    long = AGNR([(0, 6), (1, 7), (2, 6), (1, 5), (0, 6), (1, 7)])
+    structure, layers, yaml = build_multilayer([short, long])
+
+    assert structure.lattice.matrix[0][0] == pytest.approx(long.to_structure().lattice.matrix[0][0])
+    assert layers.count(0) == 3 * len(short.to_structure())
+    assert layers.count(1) == len(long.to_structure())
+    assert "repeat: [3, 1]" in yaml and "repeat: [1, 1]" in yaml
+
+
+def test_offset_requires_shift():
+    with pytest.raises(ValueError):
+        build_multilayer([AGNR([(0, 6), (1, 7)])] * 2, stacking="offset")
+
+
+@pytest.mark.parametrize(
+    "kwargs",
+    [dict(layer_sep=0.0), dict(layer_sep=float("nan")), dict(vacuum_sep=-1.0), dict(vacuum_sep=float("inf"))],
+)
+def test_bad_separations(kwargs):
+    structure = AGNR([(0, 6), (1, 7)]).to_structure()
+    with pytest.raises(ValueError):
+        build_multilayer([structure, structure], **kwargs)
+
+
+def test_empty_layer():
+    structure = AGNR([(0, 6), (1, 7)]).to_structure()
+    empty = Structure(structure.lattice, [], [])
+    with pytest.raises(ValueError):
+        build_multilayer([structure, empty])
@@ -0,0 +1,37 @@
+import pytest
+
+from agnr_ml import AGNR, generate_all_possible_agnrs
+
+
+def test_neighbors_are_canonical():
+    all_agnrs = {tuple(agnr.spec()) for agnr in generate_all_possible_agnrs(1, 3, 1, 4)}
+    agnr = AGNR([(0, 4), (1, 5), (2, 6), (1, 5)])
+
+    neighbors = agnr.neighbors()
+    assert len(neighbors) > 0
+    for neighbor in neighbors:
+        spec = tuple(neighbor.spec())
+        assert spec in all_agnrs
+        assert spec != tuple(agnr.spec())
+
+
+def test_neighbors_are_symmetric():
+    agnr = AGNR([(0, 4), (1, 5), (2, 6), (1, 5)])
+    for neighbor in agnr.neighbors():
+        assert agnr.spec() in [n.spec() for n in neighbor.neighbors()]
+
+
+def test_neighbors_grow_with_edits():
+    agnr = AGNR([(0, 4), (1, 5)])
+    one = {tuple(n.spec()) for n in agnr.neighbors(1)}
+    two = {tuple(n.spec()) for n in agnr.neighbors(2)}
+    assert one < two
+
+
+def test_neighbors_are_primitive():
+    # inserting a column into a doubled cell can give the doubled cell of a neighbor
+    with pytest.warns(UserWarning):
+        doubled = AGNR([(0, 4), (1, 5), (0, 4), (1, 5)])
+    neighbors = doubled.neighbors(2)
+    assert all(neighbor.is_primitive() for neighbor in neighbors)
+    assert [(0, 4), (1, 5)] not in [n.spec() for n in neighbors]
@@ -0,0 +1,52 @@
+import math
+
+import pytest
+
+from agnr_ml import AGNR, optical_absorption
+
+# 7-AGNR, the lowest transition is the tight-binding band gap at Gamma
+AGNR7 = AGNR([(0, 8), (1, 7)])
+HOPPING = 2.7
+
+
+def tb_gap(n):
+    return min(2 * HOPPING * abs(1 + 2 * math.cos(p * math.pi / (n + 1))) for p in range(1, n + 1))
+
+
+def test_lowest_transition():
+    energies, absorption, transitions = optical_absorption(AGNR7, num_kpoints=40)
+
+    assert len(energies) == len(absorption) == 601
+    assert energies[0] == 0.0 and energies[-1] == pytest.approx(6.0)
+    assert min(absorption) >= 0.0
+    assert 0 < len(transitions) <= 4
+    assert transitions[0][0] == pytest.approx(tb_gap(7), abs=1e-6)
+    assert [e for e, _ in transitions] == sorted(e for e, _ in transitions)
+    assert all(0 < s <= 1 for _, s in transitions)
+
+    # absorption rises at the first bright transition
+    below = [a for e, a in zip(energies, absorption) if e < 0.5 * transitions[0][0]]
+    assert max(below) < max(absorption) / 10
+
+
+def test_polarization():
+    _, along, (first_along, *_) = optical_absorption(AGNR7, num_kpoints=40)
+    _, across, (first_across, *_) = optical_absorption(AGNR7, "across", num_kpoints=40)
+
+    # transitions across the ribbon change the parity of the transverse mode
+    assert first_across[0] > first_along[0] + 0.1
+    assert along != across
+
+
+def test_hopping_scales_spectrum():
+    _, _, transitions = optical_absorption(AGNR7, num_kpoints=40, hopping=2 * HOPPING)
+    assert transitions[0][0] == pytest.approx(2 * tb_gap(7), abs=1e-6)
+
+
+def test_invalid_arguments():
+    with pytest.raises(ValueError):
+        optical_absorption(AGNR7, "diagonal")
+    with pytest.raises(ValueError):
+        optical_absorption(AGNR7, broadening=0.0)
+    with pytest.raises(ValueError):
+        optical_absorption(AGNR7, num_kpoints=0)
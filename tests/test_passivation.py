@@ -0,0 +1,43 @@
+from agnr_ml import AGNR, passivate_structure
+from pymatgen import Structure
+import pytest
+
+
+def bare_ribbon():
+    structure = AGNR([(0, 4), (1, 5)]).to_structure()
+    carbons = [i for i, s in enumerate(structure.species) if str(s) == "C"]
+    return Structure(
+        lattice=structure.lattice,
+        species=["C"] * len(carbons),
+        coords=[structure.cart_coords[i] for i in carbons],
+        coords_are_cartesian=True,
+    )
+
+
+def test_passivate_matches_to_structure():
+    capped, report = passivate_structure(bare_ribbon())
+    assert capped == AGNR([(0, 4), (1, 5)]).to_structure()
+    assert report.num_caps == 4
+    assert report.unsaturated == []
+
+
+def test_custom_rules():
+    capped, report = passivate_structure(bare_ribbon(), rules={"C": (4, "sp3")}, bond_lengths={"C": 1.1})
+    # edge carbons get two tetrahedral caps, but the planar interior ones have no room for one
+    assert [(site, len(caps)) for site, caps in report.passivated] == [(0, 2), (1, 2), (6, 2), (7, 2)]
+    assert report.unsaturated == [(2, 1), (3, 1), (4, 1), (5, 1)]
+    coords = capped.cart_coords
+    for site, caps in report.passivated:
+        for cap in caps:
+            delta = [a - b for a, b in zip(coords[cap], coords[site])]
+            assert sum(d * d for d in delta) ** 0.5 == pytest.approx(1.1)
+
+
+@pytest.mark.parametrize("kwargs", [
+    dict(rules={"C": (3, "sp4")}),
+    dict(bond_lengths={"C": 0.0}),
+    dict(cutoff_distance=float("nan")),
+])
+def test_passivate_bad_options(kwargs):
+    with pytest.raises(ValueError):
+        passivate_structure(bare_ribbon(), **kwargs)
@@ -0,0 +1,24 @@
+from agnr_ml import AGNR
+import pytest
+import warnings
+
+
+def test_doubled_cell_is_not_primitive():
+    with pytest.warns(UserWarning):
+        doubled = AGNR([(0, 4), (1, 5), (0, 4), (1, 5)])
+    assert not doubled.is_primitive()
+    assert doubled.primitive().spec() == [(0, 4), (1, 5)]
+
+
+def test_primitive_is_canonical():
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        agnr = AGNR([(1, 5), (2, 6), (1, 5), (0, 4)])
+    assert agnr.is_primitive()
+    assert agnr.primitive().spec() == [(0, 4), (1, 5), (2, 6), (1, 5)]
+
+
+def test_tripled_cell():
+    with pytest.warns(UserWarning):
+        tripled = AGNR([(0, 6), (1, 5), (0, 6), (1, 7)] * 3)
+    assert tripled.primitive().spec() == [(0, 6), (1, 5), (0, 6), (1, 7)]
@@ -0,0 +1,42 @@
+import pytest
+
+from agnr_ml import AGNR, AtomicStructure, relax_structure
+
+pytestmark = pytest.mark.skipif(
+    not hasattr(AtomicStructure, "relax"), reason="built without the `relax` feature"
+)
+
+
+def test_relaxation_converges():
+    structure = AGNR([(0, 6), (1, 7)]).to_structure()
+    unrelaxed, before = relax_structure(structure, max_iterations=0)
+    relaxed, after = relax_structure(structure, fmax=0.01)
+
+    assert after["converged"]
+    assert after["max_force"] <= 0.01
+    assert after["energy"] < before["energy"]
+    assert len(relaxed) == len(structure)
+
+
+def test_cell_relaxation_removes_strain():
+    structure = AGNR([(0, 6), (1, 7)]).to_structure()
+    relaxed, _ = relax_structure(structure)
+
+    strained = structure.copy()
+    strained.apply_strain([0.03, 0.0, 0.0])
+    relaxed_strained, info = relax_structure(strained)
+
+    assert info["converged"]
+    assert relaxed_strained.lattice.a == pytest.approx(relaxed.lattice.a, abs=1e-3)
+
+
+def test_fixed_cell():
+    structure = AGNR([(0, 6), (1, 7)]).to_structure()
+    relaxed, _ = relax_structure(structure, relax_cell_x=False, minimizer="fire")
+    assert relaxed.lattice.a == pytest.approx(structure.lattice.a)
+
+
+def test_unknown_potential():
+    structure = AGNR([(0, 6), (1, 7)]).to_structure()
+    with pytest.raises(ValueError):
+        relax_structure(structure, potential="lj")
@@ -0,0 +1,48 @@
+import math
+
+import pytest
+
+from agnr_ml import AGNR, stm_image
+
+# 7-AGNR, which is mirror symmetric about its axis, with a tight-binding gap of ~1.27 eV
+AGNR7 = AGNR([(0, 8), (1, 7)])
+LENGTH = AGNR7.to_structure().lattice.matrix[0][0]
+
+
+def test_image_grid():
+    image, (left, right, bottom, top) = stm_image(AGNR7, 1.0, resolution=0.5, num_kpoints=10)
+
+    assert (left, right) == (0.0, pytest.approx(3 * LENGTH))
+    assert top - bottom > 6.0
+    assert image.shape == (math.ceil((top - bottom) / 0.5), math.ceil((right - left) / 0.5))
+    assert image.min() >= 0.0
+    assert image.max() > 0.0
+
+
+def test_symmetry():
+    # exactly 10 pixels per cell
+    resolution = LENGTH / 10 * 1.0001
+    image, _ = stm_image(AGNR7, -1.0, resolution=resolution, repeats=2, num_kpoints=10)
+    image = [list(row) for row in image]
+    assert len(image[0]) == 20
+
+    scale = max(max(row) for row in image)
+    for row, mirrored in zip(image, reversed(image)):
+        assert row == pytest.approx(mirrored, abs=1e-9 * scale)
+        assert row[:10] == pytest.approx(row[10:], abs=1e-9 * scale)
+
+
+def test_tip_height():
+    low, _ = stm_image(AGNR7, 1.0, height=2.5, resolution=0.5, num_kpoints=10)
+    high, _ = stm_image(AGNR7, 1.0, height=4.0, resolution=0.5, num_kpoints=10)
+    assert high.max() < low.max()
+
+
+def test_bias_window():
+    # no states between the middle of the gap and the band edges
+    with pytest.raises(ValueError):
+        stm_image(AGNR7, 0.5, num_kpoints=10)
+    with pytest.raises(ValueError):
+        stm_image(AGNR7, 0.0)
+    with pytest.raises(ValueError):
+        stm_image(AGNR7, 1.0, resolution=-0.1)
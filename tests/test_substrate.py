@@ -0,0 +1,112 @@
+import math
+
+import pytest
+
+from agnr_ml import AGNR, Substrate, place_on_substrate
+
+
+def test_gold_match():
+    agnr = AGNR([(0, 7), (1, 8)])
+    n_ribbon = len(agnr.to_structure())
+    structure, is_substrate, strain = place_on_substrate(agnr, "Au", height=3.0, max_strain=0.03)
+
+    assert abs(strain) <= 0.03
+    assert len(structure) == len(is_substrate)
+    assert is_substrate.count(False) % n_ribbon == 0
+    assert all(is_substrate[: is_substrate.count(True)])
+    assert {str(s) for s, sub in zip(structure.species, is_substrate) if sub} == {"Au"}
+
+    z = [c[2] for c in structure.cart_coords]
+    top = max(z for z, sub in zip(z, is_substrate) if sub)
+    ribbon_z = [z for z, sub in zip(z, is_substrate) if not sub]
+    assert sum(ribbon_z) / len(ribbon_z) - top == pytest.approx(3.0)
+
+
+def test_supercell_size():
+    substrate = Substrate.fcc111("Ag", layers=2)
+    ribbon = AGNR([(0, 6), (1, 7)])._spec.to_structure()
+    match = substrate.place(ribbon, max_strain=0.05)
+
+    (m, n), (p, q) = match.supercell
+    n_substrate = sum(match.is_substrate)
+    assert n_substrate == abs(m * q - n * p) * 2
+
+    a, b, _ = match.structure.lattice()
+    # the supercell is rotated so the ribbon runs along x
+    assert a[1] == pytest.approx(0.0, abs=1e-9)
+    length = ribbon.lattice()[0][0] * match.ribbon_repeats
+    assert a[0] == pytest.approx(length * (1 + match.strain))
+
+
+def test_smallest_supercell():
+    substrate = Substrate.fcc111("Au")
+    ribbon = AGNR([(0, 7), (1, 8)])._spec.to_structure()
+    match = substrate.place(ribbon)
+    assert match.ribbon_repeats == 2
+    assert len(match.is_substrate) < 200
+    # nothing fits with fewer repeats, even though more repeats can have less strain
+    with pytest.raises(ValueError):
+        substrate.place(ribbon, max_repeats=match.ribbon_repeats - 1)
+
+
+def test_hbn():
+    agnr = AGNR([(0, 6), (1, 7)])
+    structure, is_substrate, strain = place_on_substrate(agnr, "hBN", max_strain=0.05)
+    elems = {str(s) for s, sub in zip(structure.species, is_substrate) if sub}
+    assert elems == {"B", "N"}
+    assert abs(strain) <= 0.05
+
+
+def test_no_match():
+    agnr = AGNR([(0, 6), (1, 7)])
+    with pytest.raises(ValueError):
+        place_on_substrate(agnr, "Au", max_strain=0.0, max_repeats=1)
+    with pytest.raises(ValueError):
+        Substrate.fcc111("Xx")
+
+
+def test_from_poscar(tmp_path):
+    a = 4.078 / math.sqrt(2)
+    path = tmp_path / "POSCAR"
+    path.write_text(
+        "Au(111) monolayer\n"
+        "1.0\n"
+        f"{a} 0.0 0.0\n"
+        f"{a / 2} {a * math.sqrt(3) / 2} 0.0\n"
+        "0.0 0.0 20.0\n"
+        "Au\n"
+        "1\n"
+        "Cartesian\n"
+        "0.0 0.0 5.0\n"
+    )
+
+    agnr = AGNR([(0, 6), (1, 7)])
+    from_file = place_on_substrate(agnr, str(path))
+    builtin = place_on_substrate(agnr, "Au", layers=1)
+    assert from_file[1] == builtin[1]
+    assert from_file[2] == pytest.approx(builtin[2])
+
+
+def test_from_poscar_outside_cell(tmp_path):
+    a = 4.078 / math.sqrt(2)
+    path = tmp_path / "POSCAR"
+    # the same two-layer slab, once with the atoms inside the cell and once shifted out of it
+    text = (
+        "Au(111) bilayer\n"
+        "1.0\n"
+        f"{a} 0.0 0.0\n"
+        f"{a / 2} {a * math.sqrt(3) / 2} 0.0\n"
+        "0.0 0.0 20.0\n"
+        "Au\n"
+        "2\n"
+        "Direct\n"
+        "{} {} 0.25\n"
+        "{} {} 0.37\n"
+    )
+    path.write_text(text.format(0.0, 0.0, 1 / 3, 1 / 3))
+    inside = place_on_substrate(AGNR([(0, 6), (1, 7)]), str(path))
+    path.write_text(text.format(-2.0, 1.0, 1 / 3 + 3, 1 / 3 - 1))
+    outside = place_on_substrate(AGNR([(0, 6), (1, 7)]), str(path))
+
+    assert outside[1] == inside[1]
+    assert outside[0].cart_coords == pytest.approx(inside[0].cart_coords)
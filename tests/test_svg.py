@@ -0,0 +1,29 @@
+from agnr_ml import AGNR, structure_to_svg
+import pytest
+
+
+def test_agnr_svg():
+    agnr = AGNR([(0, 4), (1, 5)])
+    svg = agnr._repr_svg_()
+    assert svg.startswith("<svg")
+    # 12 atoms, repeated 3 times
+    assert svg.count("<circle") == 36
+
+
+def test_structure_svg_values():
+    structure = AGNR([(0, 4), (1, 5)]).to_structure()
+    values = [float(i) - 6.0 for i in range(len(structure))]
+    svg = structure_to_svg(structure, values=values, repeats=2)
+    assert svg.count("<circle") == 24
+    # the most negative value is fully blue, and the most positive is (nearly) fully red
+    assert 'fill="#0000ff"' in svg
+
+    with pytest.raises(ValueError):
+        structure_to_svg(structure, values=[1.0])
+
+
+@pytest.mark.parametrize("bond_cutoff", [0.0, -1.0, float("nan"), float("inf")])
+def test_structure_svg_bad_bond_cutoff(bond_cutoff):
+    structure = AGNR([(0, 4), (1, 5)]).to_structure()
+    with pytest.raises(ValueError):
+        structure_to_svg(structure, bond_cutoff=bond_cutoff)